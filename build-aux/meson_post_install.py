@@ -10,3 +10,5 @@ if not environ.get('DESTDIR', ''):
     call(['gtk4-update-icon-cache', '-qtf', path.join(DATA_DIR, 'icons/hicolor')])
     print("Updating desktop database...")
     call(["update-desktop-database", path.join(DATA_DIR, 'applications')])
+    print("Compiling GSettings schemas...")
+    call(["glib-compile-schemas", path.join(DATA_DIR, 'glib-2.0/schemas')])
@@ -17,23 +17,146 @@
 //
 // SPDX-License-Identifier: GPL-3.0-only
 
-use std::cell::RefCell;
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+    rc::Rc,
+};
 
 use gtk::{
     gio,
-    glib::{self, clone, Continue, Receiver},
+    glib::{self, clone, Continue},
     prelude::*,
     subclass::prelude::*,
 };
 use log::{info, warn};
-use pipewire::{channel::Sender, spa::Direction};
 
 use crate::{
+    backend::{self, GraphBackend, Source},
+    dbus_service::DBusService,
+    dump::{GraphDump, Position},
+    layouts::Layouts,
+    model::{self, Graph, GraphEvent},
+    node_labels::{NodeLabel, NodeLabels},
+    pipewire_connection,
+    presets::{Preset, PresetDiff, Presets},
+    theme::{self, ColorScheme},
     view::{self},
-    GtkMessage, MediaType, NodeType, PipewireLink, PipewireMessage,
+    virtual_nodes::{VirtualNode, VirtualNodeKind, VirtualNodes, VIRTUAL_NODE_PROPERTY},
+    GtkMessage, LinkOptions, MediaType, PipewireLink, PipewireMessage,
 };
 
-static STYLE: &str = include_str!("style.css");
+/// Id of the application, which is also the id of its settings schema.
+const APP_ID: &str = "org.freedesktop.ryuukyu.Helvum";
+
+/// Factor the zoom changes by with each step.
+const ZOOM_STEP: f64 = 1.25;
+
+/// Show a minimap of the graphview in the bottom right corner of the scrolled window containing it.
+fn overview_overlay(
+    graphview: &view::GraphView,
+    scrollwindow: &gtk::ScrolledWindow,
+) -> gtk::Overlay {
+    let minimap = view::Minimap::new(graphview, scrollwindow);
+    minimap.set_halign(gtk::Align::End);
+    minimap.set_valign(gtk::Align::End);
+
+    let overlay = gtk::Overlay::new();
+    overlay.set_child(Some(scrollwindow));
+    overlay.add_overlay(&minimap);
+
+    overlay
+}
+
+/// Get the settings of the application, if their schema is installed.
+fn load_settings() -> Option<gio::Settings> {
+    let installed = gio::SettingsSchemaSource::default()
+        .and_then(|source| source.lookup(APP_ID, true))
+        .is_some();
+    if !installed {
+        warn!(
+            "Settings schema {} is not installed, preferences will not be stored",
+            APP_ID
+        );
+        return None;
+    }
+
+    Some(gio::Settings::new(APP_ID))
+}
+
+/// Describe a link between two ports of the graph by their full names.
+fn link_name(graph: &Graph, port_from: u32, port_to: u32) -> String {
+    format!(
+        "{} → {}",
+        graph.port_full_name(port_from).unwrap_or_default(),
+        graph.port_full_name(port_to).unwrap_or_default()
+    )
+}
+
+/// The running backend of a tab.
+struct Connection {
+    backend: Box<dyn GraphBackend>,
+    /// Source receiving the messages of the backend in the main context.
+    receiver_source: glib::SourceId,
+}
+
+/// A tab of the main window, showing the graph of a single remote.
+struct RemoteTab {
+    source: Source,
+    graphview: view::GraphView,
+    /// Read-only copy of the graph of the backend.
+    graph: RefCell<Graph>,
+    /// The connection to the source, until the tab is closed.
+    connection: RefCell<Option<Connection>>,
+    /// The scrolled window containing the graphview.
+    scrollwindow: gtk::ScrolledWindow,
+    /// The content of the tab in the notebook.
+    page: gtk::Widget,
+    /// Panel next to the graph listing the differences to the preset the links are compared with.
+    comparison_panel: view::ComparisonPanel,
+    /// Name of the preset the links are compared with and the differences to it, if they are compared.
+    comparison: RefCell<Option<(String, PresetDiff)>>,
+    /// Whether the backend has sent the whole graph it had when it was started.
+    synced: Cell<bool>,
+}
+
+impl RemoteTab {
+    /// Get the name of the source as shown to the user, which also identifies its stored layout.
+    fn remote_name(&self) -> String {
+        self.source.display_name()
+    }
+
+    /// Send a message to the backend, returning whether it could be sent.
+    fn send(&self, message: GtkMessage) -> bool {
+        self.connection
+            .borrow()
+            .as_ref()
+            .map_or(false, |connection| connection.backend.send(message))
+    }
+
+    /// Toggle the link between the two ports, creating it with the default options.
+    fn toggle_link(&self, port_from: u32, port_to: u32) -> Result<(), String> {
+        if self.send(GtkMessage::ToggleLink {
+            port_from,
+            port_to,
+            options: LinkOptions::default(),
+        }) {
+            Ok(())
+        } else {
+            Err("Not connected to the remote".to_string())
+        }
+    }
+
+    /// Stop the backend, if it is running.
+    fn disconnect(&self) {
+        let connection = self.connection.borrow_mut().take();
+        if let Some(connection) = connection {
+            connection.backend.stop();
+            // Messages still waiting to be received belong to a graph that is no longer shown.
+            glib::source_remove(connection.receiver_source);
+        }
+    }
+}
 
 mod imp {
     use super::*;
@@ -42,8 +165,32 @@ mod imp {
 
     #[derive(Default)]
     pub struct Application {
-        pub(super) graphview: view::GraphView,
-        pub(super) pw_sender: OnceCell<RefCell<Sender<GtkMessage>>>,
+        /// Tabs of the main window, one for each remote that is connected to.
+        pub(super) tabs: RefCell<Vec<Rc<RemoteTab>>>,
+        pub(super) notebook: OnceCell<gtk::Notebook>,
+        pub(super) window: OnceCell<gtk::ApplicationWindow>,
+        /// Graphviews of all open offline graph windows, along with the graphs they show.
+        pub(super) offline_graphs: RefCell<Vec<(view::GraphView, Graph)>>,
+        /// Label at the bottom of the main window announcing changes to the graph.
+        pub(super) status_label: OnceCell<gtk::Label>,
+        /// Custom labels and colors of nodes chosen by the user.
+        pub(super) node_labels: RefCell<NodeLabels>,
+        /// Node positions stored for each remote.
+        pub(super) layouts: RefCell<Layouts>,
+        /// Our stylesheets and the color scheme they are shown in.
+        pub(super) theme: OnceCell<theme::Theme>,
+        /// Settings chosen by the user, if their schema is installed.
+        pub(super) settings: OnceCell<gio::Settings>,
+        /// Presets saved by the user for each remote.
+        pub(super) presets: RefCell<Presets>,
+        /// Menu of the presets of the current tab's remote.
+        pub(super) presets_menu: OnceCell<gio::Menu>,
+        /// Detailed names of the actions applying presets that have a keyboard shortcut set.
+        pub(super) preset_accels: RefCell<Vec<String>>,
+        /// Our D-Bus interface, while it is exported on the session bus.
+        pub(super) dbus_service: RefCell<Option<DBusService>>,
+        /// Virtual nodes kept for each remote.
+        pub(super) virtual_nodes: RefCell<VirtualNodes>,
     }
 
     #[glib::object_subclass]
@@ -56,33 +203,115 @@ mod imp {
     impl ObjectImpl for Application {}
     impl ApplicationImpl for Application {
         fn activate(&self, app: &Self::Type) {
-            let scrollwindow = gtk::ScrolledWindowBuilder::new()
-                .child(&self.graphview)
+            let menu = gio::Menu::new();
+            menu.append(Some("_Export Graph…"), Some("app.export-graph"));
+            menu.append(Some("_Import Graph…"), Some("app.import-graph"));
+            menu.append(Some("Export _Image…"), Some("app.export-image"));
+            menu.append(Some("_Connect Ports…"), Some("app.command-palette"));
+            menu.append(Some("New _Virtual Node…"), Some("app.new-virtual-node"));
+            menu.append(Some("Connect _To Remote…"), Some("app.connect-remote"));
+            menu.append(Some("C_lose Tab"), Some("app.close-tab"));
+            menu.append(Some("P_references"), Some("app.preferences"));
+
+            let port_names_menu = gio::Menu::new();
+            port_names_menu.append(Some("_Short Names"), Some("app.port-names::name"));
+            port_names_menu.append(Some("_Aliases"), Some("app.port-names::alias"));
+            port_names_menu.append(Some("_Full Names"), Some("app.port-names::full"));
+            port_names_menu.append(Some("_Channel Positions"), Some("app.port-names::channel"));
+            menu.append_submenu(Some("_Port Names"), &port_names_menu);
+
+            let arrange_menu = gio::Menu::new();
+            arrange_menu.append(Some("_Snap to Grid"), Some("app.snap-to-grid"));
+            arrange_menu.append(Some("Show _Hidden Nodes"), Some("app.show-hidden-nodes"));
+            let align_section = gio::Menu::new();
+            align_section.append(Some("Align _Left"), Some("app.align::left"));
+            align_section.append(Some("Align _Right"), Some("app.align::right"));
+            align_section.append(Some("Align _Top"), Some("app.align::top"));
+            align_section.append(
+                Some("_Distribute Vertically"),
+                Some("app.distribute-vertically"),
+            );
+            align_section.append(Some("Tidy _Column"), Some("app.tidy-column"));
+            arrange_menu.append_section(None, &align_section);
+            let zoom_section = gio::Menu::new();
+            zoom_section.append(Some("Zoom _In"), Some("app.zoom-in"));
+            zoom_section.append(Some("Zoom _Out"), Some("app.zoom-out"));
+            zoom_section.append(Some("_Reset Zoom"), Some("app.zoom-reset"));
+            arrange_menu.append_section(None, &zoom_section);
+            menu.append_submenu(Some("_Arrange"), &arrange_menu);
+            let menu_button = gtk::MenuButtonBuilder::new()
+                .icon_name("open-menu-symbolic")
+                .menu_model(&menu)
+                .build();
+            let presets_button = gtk::MenuButtonBuilder::new()
+                .label("Presets")
+                .tooltip_text("Save and Apply Presets")
                 .build();
+            if let Some(presets_menu) = self.presets_menu.get() {
+                presets_button.set_menu_model(Some(presets_menu));
+            }
+            let headerbar = gtk::HeaderBar::new();
+            headerbar.pack_start(&presets_button);
+            headerbar.pack_end(&menu_button);
+
+            // The status role makes screen readers announce changes of the label.
+            let status_label = gtk::LabelBuilder::new()
+                .xalign(0.0)
+                .margin_start(6)
+                .margin_end(6)
+                .margin_top(3)
+                .margin_bottom(3)
+                .accessible_role(gtk::AccessibleRole::Status)
+                .build();
+            let vbox = gtk::Box::new(gtk::Orientation::Vertical, 0);
+            let notebook = app.notebook();
+            notebook.set_vexpand(true);
+            vbox.append(&notebook);
+            vbox.append(&status_label);
+            let _ = self.status_label.set(status_label);
+
             let window = gtk::ApplicationWindowBuilder::new()
                 .application(app)
                 .default_width(1280)
                 .default_height(720)
-                .title("Helvum - Pipewire Patchbay")
-                .child(&scrollwindow)
+                .titlebar(&headerbar)
+                .child(&vbox)
                 .build();
-            window
-                .settings()
-                .set_gtk_application_prefer_dark_theme(true);
+            if let Some(settings) = self.settings.get() {
+                window
+                    .set_default_size(settings.int("window-width"), settings.int("window-height"));
+                if settings.boolean("window-maximized") {
+                    window.maximize();
+                }
+            }
+            let _ = self.window.set(window.clone());
+            app.update_title();
             window.show();
         }
 
+        fn shutdown(&self, app: &Self::Type) {
+            app.save_view_state();
+            for tab in self.tabs.borrow().iter() {
+                app.remember_layout(tab);
+                tab.disconnect();
+            }
+            app.save_layouts();
+            if let Some(dbus_service) = self.dbus_service.borrow_mut().take() {
+                dbus_service.unregister();
+            }
+            self.parent_shutdown(app);
+        }
+
         fn startup(&self, app: &Self::Type) {
             self.parent_startup(app);
 
-            // Load CSS from the STYLE variable.
-            let provider = gtk::CssProvider::new();
-            provider.load_from_data(STYLE.as_bytes());
-            gtk::StyleContext::add_provider_for_display(
-                &gtk::gdk::Display::default().expect("Error initializing gtk css provider."),
-                &provider,
-                gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
-            );
+            let display =
+                gtk::gdk::Display::default().expect("Error initializing gtk css provider.");
+            let theme = theme::setup(&display);
+            theme.set_color_scheme(app.color_scheme());
+            let _ = self.theme.set(theme);
+
+            app.register_dbus_service();
         }
     }
     impl GtkApplicationImpl for Application {}
@@ -97,20 +326,32 @@ glib::wrapper! {
 impl Application {
     /// Create the view.
     /// This will set up the entire user interface and prepare it for being run.
-    pub(super) fn new(
-        gtk_receiver: Receiver<PipewireMessage>,
-        pw_sender: Sender<GtkMessage>,
-    ) -> Self {
-        let app: Application =
-            glib::Object::new(&[("application-id", &"org.freedesktop.ryuukyu.Helvum")])
-                .expect("Failed to create new Application");
+    ///
+    /// A tab is opened for each of the sources, or for the default remote if there are none.
+    /// If `record` is set, the events of the remote of the first tab are recorded into the file at that path.
+    pub(super) fn new(sources: Vec<Source>, mut record: Option<PathBuf>) -> Self {
+        let app: Application = glib::Object::new(&[("application-id", &APP_ID)])
+            .expect("Failed to create new Application");
 
         let imp = imp::Application::from_instance(&app);
-        imp.pw_sender
-            .set(RefCell::new(pw_sender))
-            // Discard the returned sender, as it does not implement `Debug`.
-            .map_err(|_| ())
-            .expect("pw_sender field was already set");
+        *imp.node_labels.borrow_mut() = NodeLabels::load();
+        *imp.layouts.borrow_mut() = Layouts::load();
+        *imp.presets.borrow_mut() = Presets::load();
+        *imp.virtual_nodes.borrow_mut() = VirtualNodes::load();
+        if let Some(settings) = load_settings() {
+            let _ = imp.settings.set(settings);
+        }
+
+        let notebook = gtk::NotebookBuilder::new()
+            .scrollable(true)
+            .show_border(false)
+            .build();
+        notebook.connect_page_notify(clone!(@weak app => move |_| {
+            app.update_title();
+            app.update_presets();
+        }));
+        let _ = imp.notebook.set(notebook);
+        let _ = imp.presets_menu.set(gio::Menu::new());
 
         // Add <Control-Q> shortcut for quitting the application.
         let quit = gtk::gio::SimpleAction::new("quit", None);
@@ -120,145 +361,2461 @@ impl Application {
         app.set_accels_for_action("app.quit", &["<Control>Q"]);
         app.add_action(&quit);
 
-        // React to messages received from the pipewire thread.
-        gtk_receiver.attach(
+        let export_graph = gio::SimpleAction::new("export-graph", None);
+        export_graph.connect_activate(clone!(@weak app => move |_, _| {
+            app.export_graph();
+        }));
+        app.set_accels_for_action("app.export-graph", &["<Control>S"]);
+        app.add_action(&export_graph);
+
+        let import_graph = gio::SimpleAction::new("import-graph", None);
+        import_graph.connect_activate(clone!(@weak app => move |_, _| {
+            app.import_graph();
+        }));
+        app.set_accels_for_action("app.import-graph", &["<Control>O"]);
+        app.add_action(&import_graph);
+
+        let export_image = gio::SimpleAction::new("export-image", None);
+        export_image.connect_activate(clone!(@weak app => move |_, _| {
+            app.export_image();
+        }));
+        app.add_action(&export_image);
+
+        // Choose which name ports are labeled with.
+        let port_name_style = imp
+            .settings
+            .get()
+            .and_then(|settings| view::PortNameStyle::from_name(&settings.string("port-names")))
+            .unwrap_or_default();
+        let port_names = gio::SimpleAction::new_stateful(
+            "port-names",
+            Some(glib::VariantTy::STRING),
+            &port_name_style.name().to_variant(),
+        );
+        port_names.connect_change_state(clone!(@weak app => move |action, value| {
+            let style = value
+                .and_then(|value| value.str())
+                .and_then(view::PortNameStyle::from_name);
+            if let (Some(value), Some(style)) = (value, style) {
+                action.set_state(value);
+                app.set_port_name_style(style);
+                app.store_setting("port-names", style.name());
+            }
+        }));
+        app.add_action(&port_names);
+
+        // Arrange the nodes selected in the current tab.
+        let snap_to_grid =
+            gio::SimpleAction::new_stateful("snap-to-grid", None, &false.to_variant());
+        snap_to_grid.connect_change_state(clone!(@weak app => move |action, value| {
+            if let Some(snap) = value.and_then(|value| value.get::<bool>()) {
+                action.set_state(&snap.to_variant());
+                for tab in app.tabs() {
+                    tab.graphview.set_snap_to_grid(snap);
+                }
+            }
+        }));
+        app.add_action(&snap_to_grid);
+
+        let align = gio::SimpleAction::new("align", Some(glib::VariantTy::STRING));
+        align.connect_activate(clone!(@weak app => move |_, value| {
+            let alignment = match value.and_then(|value| value.str()) {
+                Some("left") => view::NodeAlignment::Left,
+                Some("right") => view::NodeAlignment::Right,
+                Some("top") => view::NodeAlignment::Top,
+                _ => return,
+            };
+            if let Some(tab) = app.current_tab() {
+                tab.graphview.align_selected_nodes(alignment);
+            }
+        }));
+        app.add_action(&align);
+
+        let distribute_vertically = gio::SimpleAction::new("distribute-vertically", None);
+        distribute_vertically.connect_activate(clone!(@weak app => move |_, _| {
+            if let Some(tab) = app.current_tab() {
+                tab.graphview.distribute_selected_nodes_vertically();
+            }
+        }));
+        app.add_action(&distribute_vertically);
+
+        let tidy_column = gio::SimpleAction::new("tidy-column", None);
+        tidy_column.connect_activate(clone!(@weak app => move |_, _| {
+            if let Some(tab) = app.current_tab() {
+                tab.graphview.tidy_selected_nodes();
+            }
+        }));
+        app.add_action(&tidy_column);
+
+        let show_hidden_nodes = gio::SimpleAction::new("show-hidden-nodes", None);
+        show_hidden_nodes.connect_activate(clone!(@weak app => move |_, _| {
+            for graphview in app.graphviews() {
+                graphview.show_hidden_nodes();
+            }
+        }));
+        app.add_action(&show_hidden_nodes);
+
+        let command_palette = gio::SimpleAction::new("command-palette", None);
+        command_palette.connect_activate(clone!(@weak app => move |_, _| {
+            app.show_command_palette();
+        }));
+        app.set_accels_for_action("app.command-palette", &["<Control>K"]);
+        app.add_action(&command_palette);
+
+        let connect_remote = gio::SimpleAction::new("connect-remote", None);
+        connect_remote.connect_activate(clone!(@weak app => move |_, _| {
+            app.ask_for_remote();
+        }));
+        app.add_action(&connect_remote);
+
+        let new_virtual_node = gio::SimpleAction::new("new-virtual-node", None);
+        new_virtual_node.connect_activate(clone!(@weak app => move |_, _| {
+            if let Some(tab) = app.current_tab() {
+                app.ask_for_virtual_node(&tab);
+            }
+        }));
+        app.add_action(&new_virtual_node);
+
+        let close_tab = gio::SimpleAction::new("close-tab", None);
+        close_tab.connect_activate(clone!(@weak app => move |_, _| {
+            if let Some(tab) = app.current_tab() {
+                app.close_tab(&tab);
+            }
+        }));
+        app.set_accels_for_action("app.close-tab", &["<Control>W"]);
+        app.add_action(&close_tab);
+
+        let preferences = gio::SimpleAction::new("preferences", None);
+        preferences.connect_activate(clone!(@weak app => move |_, _| {
+            app.show_preferences();
+        }));
+        app.set_accels_for_action("app.preferences", &["<Control>comma"]);
+        app.add_action(&preferences);
+
+        // Zoom the graphs of all tabs.
+        let zoom_in = gio::SimpleAction::new("zoom-in", None);
+        zoom_in.connect_activate(clone!(@weak app => move |_, _| {
+            app.set_zoom(app.zoom() * ZOOM_STEP);
+        }));
+        app.set_accels_for_action("app.zoom-in", &["<Control>plus", "<Control>equal"]);
+        app.add_action(&zoom_in);
+
+        let zoom_out = gio::SimpleAction::new("zoom-out", None);
+        zoom_out.connect_activate(clone!(@weak app => move |_, _| {
+            app.set_zoom(app.zoom() / ZOOM_STEP);
+        }));
+        app.set_accels_for_action("app.zoom-out", &["<Control>minus"]);
+        app.add_action(&zoom_out);
+
+        let zoom_reset = gio::SimpleAction::new("zoom-reset", None);
+        zoom_reset.connect_activate(clone!(@weak app => move |_, _| {
+            app.set_zoom(1.0);
+        }));
+        app.set_accels_for_action("app.zoom-reset", &["<Control>0"]);
+        app.add_action(&zoom_reset);
+
+        // Follow changes of the settings, no matter if they are made in the preferences dialog or elsewhere.
+        if let Some(settings) = imp.settings.get() {
+            settings.connect_changed(
+                Some("port-names"),
+                clone!(@weak app => move |settings, key| {
+                    app.change_action_state("port-names", &settings.string(key).to_variant());
+                }),
+            );
+            settings.connect_changed(
+                Some("zoom"),
+                clone!(@weak app => move |settings, key| {
+                    app.set_zoom(settings.double(key));
+                }),
+            );
+            settings.connect_changed(
+                Some("color-scheme"),
+                clone!(@weak app => move |_, _| {
+                    let imp = imp::Application::from_instance(&app);
+                    if let Some(theme) = imp.theme.get() {
+                        theme.set_color_scheme(app.color_scheme());
+                    }
+                }),
+            );
+            for key in ["hide-monitor-ports", "hide-midi-ports"] {
+                settings.connect_changed(
+                    Some(key),
+                    clone!(@weak app => move |_, _| {
+                        app.rebuild_views();
+                    }),
+                );
+            }
+        }
+
+        // Manage the presets of the current tab's remote, which are selected by their name.
+        let save_preset = gio::SimpleAction::new("save-preset", None);
+        save_preset.connect_activate(clone!(@weak app => move |_, _| {
+            if let Some(tab) = app.current_tab() {
+                app.ask_for_preset_name(&tab, None);
+            }
+        }));
+        app.add_action(&save_preset);
+
+        let preset_actions: [(&str, fn(&Application, &Rc<RemoteTab>, &str)); 8] = [
+            ("apply-preset", |app, tab, name| {
+                if let Err(e) = app.apply_preset(tab, name, false) {
+                    app.show_error(&e);
+                }
+            }),
+            ("apply-preset-exclusive", |app, tab, name| {
+                if let Err(e) = app.apply_preset(tab, name, true) {
+                    app.show_error(&e);
+                }
+            }),
+            ("preview-preset", |app, tab, name| {
+                app.preview_preset(tab, name)
+            }),
+            ("compare-preset", |app, tab, name| {
+                app.compare_with_preset(tab, Some(name))
+            }),
+            ("update-preset", |app, tab, name| {
+                if let Err(e) = app.save_preset(tab, name) {
+                    app.show_error(&e);
+                }
+            }),
+            ("rename-preset", |app, tab, name| {
+                app.ask_for_preset_name(tab, Some(name))
+            }),
+            ("preset-shortcut", |app, tab, name| {
+                app.ask_for_preset_shortcut(tab, name)
+            }),
+            ("delete-preset", |app, tab, name| {
+                let remote = tab.remote_name();
+                let result = app.change_presets(|presets| {
+                    presets.remove(&remote, name);
+                    Ok(())
+                });
+                if let Err(e) = result {
+                    app.show_error(&e);
+                }
+            }),
+        ];
+        for (action_name, activate) in preset_actions {
+            let action = gio::SimpleAction::new(action_name, Some(glib::VariantTy::STRING));
+            action.connect_activate(clone!(@weak app => move |_, value| {
+                if let (Some(tab), Some(name)) = (app.current_tab(), value.and_then(|value| value.str())) {
+                    activate(&app, &tab, name);
+                }
+            }));
+            app.add_action(&action);
+        }
+
+        if sources.is_empty() {
+            app.open_tab(Source::Remote(None), record.take());
+        }
+        for source in sources {
+            app.open_tab(source, record.take());
+        }
+
+        app
+    }
+
+    fn notebook(&self) -> gtk::Notebook {
+        let imp = imp::Application::from_instance(self);
+        imp.notebook.get().expect("Notebook not created").clone()
+    }
+
+    /// Get all open tabs, in the order they were opened in.
+    fn tabs(&self) -> Vec<Rc<RemoteTab>> {
+        let imp = imp::Application::from_instance(self);
+        imp.tabs.borrow().clone()
+    }
+
+    /// Get the tab currently shown in the main window.
+    fn current_tab(&self) -> Option<Rc<RemoteTab>> {
+        let imp = imp::Application::from_instance(self);
+        let notebook = imp.notebook.get()?;
+        let page = notebook.nth_page(notebook.current_page())?;
+        self.tabs().into_iter().find(|tab| tab.page == page)
+    }
+
+    /// Get the tab showing the graphview.
+    fn tab_of(&self, graphview: &view::GraphView) -> Option<Rc<RemoteTab>> {
+        self.tabs()
+            .into_iter()
+            .find(|tab| tab.graphview == *graphview)
+    }
+
+    /// Get the graphviews of all tabs and offline graph windows.
+    fn graphviews(&self) -> Vec<view::GraphView> {
+        let imp = imp::Application::from_instance(self);
+        imp.tabs
+            .borrow()
+            .iter()
+            .map(|tab| tab.graphview.clone())
+            .chain(
+                imp.offline_graphs
+                    .borrow()
+                    .iter()
+                    .map(|(graphview, _)| graphview.clone()),
+            )
+            .collect()
+    }
+
+    /// Get the current state of the node with the id in the graph shown by the graphview.
+    fn node_of(&self, graphview: &view::GraphView, id: u32) -> Option<model::Node> {
+        if let Some(tab) = self.tab_of(graphview) {
+            return tab.graph.borrow().node(id).cloned();
+        }
+
+        let imp = imp::Application::from_instance(self);
+        let offline_graphs = imp.offline_graphs.borrow();
+        let (_, graph) = offline_graphs
+            .iter()
+            .find(|(other, _)| other == graphview)?;
+        graph.node(id).cloned()
+    }
+
+    /// Open a new tab showing the graph of the source, recording its events into the file at `record` if set.
+    ///
+    /// If a tab for the source is already open, that tab is shown instead.
+    fn open_tab(&self, source: Source, record: Option<PathBuf>) {
+        let imp = imp::Application::from_instance(self);
+        let notebook = self.notebook();
+
+        let remote_name = source.display_name();
+        let existing = self
+            .tabs()
+            .into_iter()
+            .find(|tab| tab.remote_name() == remote_name);
+        if let Some(tab) = existing {
+            notebook.set_current_page(notebook.page_num(&tab.page));
+            return;
+        }
+        info!("Connecting to remote {}", remote_name);
+
+        let graphview = view::GraphView::new();
+        graphview.set_port_name_style(self.port_name_style());
+        graphview.set_snap_to_grid(self.snap_to_grid());
+        graphview.set_zoom(self.zoom());
+        let scrollwindow = gtk::ScrolledWindowBuilder::new().child(&graphview).build();
+        // The first tab is scrolled to where it was the last time helvum was closed.
+        if imp.tabs.borrow().is_empty() {
+            self.restore_scroll_position(&scrollwindow);
+        }
+
+        // Zoom with the scroll wheel while control is held.
+        let scroll_controller =
+            gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::VERTICAL);
+        scroll_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+        scroll_controller.connect_scroll(
+            clone!(@weak self as app => @default-return gtk::Inhibit(false), move |controller, _, dy| {
+                if !controller
+                    .current_event_state()
+                    .contains(gtk::gdk::ModifierType::CONTROL_MASK)
+                {
+                    return gtk::Inhibit(false);
+                }
+                app.set_zoom(app.zoom() * ZOOM_STEP.powf(-dy));
+                gtk::Inhibit(true)
+            }),
+        );
+        scrollwindow.add_controller(&scroll_controller);
+
+        let overlay = overview_overlay(&graphview, &scrollwindow);
+        overlay.set_hexpand(true);
+        let comparison_panel = view::ComparisonPanel::new();
+        comparison_panel.set_visible(false);
+        let page = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        page.append(&overlay);
+        page.append(&comparison_panel);
+
+        let (gtk_receiver, backend) = backend::start(&source, record);
+        let tab = Rc::new(RemoteTab {
+            source,
+            graphview,
+            graph: RefCell::new(Graph::new()),
+            connection: RefCell::new(None),
+            scrollwindow,
+            page: page.clone().upcast(),
+            comparison_panel,
+            comparison: RefCell::new(None),
+            synced: Cell::new(false),
+        });
+
+        if let Err(e) = tab.comparison_panel.connect_local(
+            "fix",
+            false,
+            clone!(@weak self as app, @weak tab => @default-return None, move |args| {
+                let index = args[1].get::<u32>().expect("fix signal has no index");
+                app.fix_difference(&tab, index as usize);
+                None
+            }),
+        ) {
+            warn!("Failed to connect to \"fix\" signal: {}", e);
+        }
+        if let Err(e) = tab.comparison_panel.connect_local(
+            "closed",
+            false,
+            clone!(@weak self as app, @weak tab => @default-return None, move |_| {
+                app.compare_with_preset(&tab, None);
+                None
+            }),
+        ) {
+            warn!("Failed to connect to \"closed\" signal: {}", e);
+        }
+
+        // React to messages received from the backend.
+        let receiver_source = gtk_receiver.attach(
             None,
             clone!(
-                @weak app => @default-return Continue(true),
+                @weak self as app, @weak tab => @default-return Continue(true),
                 move |msg| {
-                    match msg {
-                        PipewireMessage::NodeAdded{ id, name, node_type } => app.add_node(id, name.as_str(), node_type),
-                        PipewireMessage::PortAdded{ id, node_id, name, direction, media_type } => app.add_port(id, name.as_str(), node_id, direction, media_type),
-                        PipewireMessage::LinkAdded{ id, node_from, port_from, node_to, port_to, active} => app.add_link(id, node_from, port_from, node_to, port_to, active),
-                        PipewireMessage::LinkStateChanged { id, active } => app.link_state_changed(id, active), // TODO
-                        PipewireMessage::NodeRemoved { id } => app.remove_node(id),
-                        PipewireMessage::PortRemoved { id, node_id } => app.remove_port(id, node_id),
-                        PipewireMessage::LinkRemoved { id } => app.remove_link(id)
-                    };
+                    app.handle_message(&tab, msg);
                     Continue(true)
                 }
             ),
         );
+        *tab.connection.borrow_mut() = Some(Connection {
+            backend,
+            receiver_source,
+        });
+        // Virtual nodes that are kept are only created once it is known which of them already exist.
+        tab.send(GtkMessage::Sync);
 
-        app
+        let close_button = gtk::ButtonBuilder::new()
+            .icon_name("window-close-symbolic")
+            .has_frame(false)
+            .tooltip_text("Close Tab")
+            .build();
+        close_button.connect_clicked(clone!(@weak self as app, @weak tab => move |_| {
+            app.close_tab(&tab);
+        }));
+        let tab_label = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        tab_label.append(&gtk::Label::new(Some(&remote_name)));
+        tab_label.append(&close_button);
+
+        imp.tabs.borrow_mut().push(tab);
+        let index = notebook.append_page(&page, Some(&tab_label));
+        notebook.set_tab_reorderable(&page, true);
+        // Tabs are only worth their space if there is more than one.
+        notebook.set_show_tabs(imp.tabs.borrow().len() > 1);
+        notebook.set_current_page(Some(index));
+        self.update_title();
+        self.update_presets();
     }
 
-    /// Add a new node to the view.
-    fn add_node(&self, id: u32, name: &str, node_type: Option<NodeType>) {
-        info!("Adding node to graph: id {}", id);
+    /// Close the tab, disconnecting from its remote and storing the layout of its graph.
+    fn close_tab(&self, tab: &RemoteTab) {
+        let imp = imp::Application::from_instance(self);
+        info!("Disconnecting from remote {}", tab.remote_name());
 
-        imp::Application::from_instance(self).graphview.add_node(
-            id,
-            view::Node::new(name),
-            node_type,
-        );
+        self.remember_layout(tab);
+        self.save_layouts();
+        tab.disconnect();
+
+        imp.tabs.borrow_mut().retain(|other| other.page != tab.page);
+        let notebook = self.notebook();
+        if let Some(index) = notebook.page_num(&tab.page) {
+            notebook.remove_page(Some(index));
+        }
+        notebook.set_show_tabs(imp.tabs.borrow().len() > 1);
+        self.update_title();
+        self.update_presets();
     }
 
-    /// Add a new port to the view.
-    fn add_port(
-        &self,
-        id: u32,
-        name: &str,
-        node_id: u32,
-        direction: Direction,
-        media_type: Option<MediaType>,
-    ) {
-        info!("Adding port to graph: id {}", id);
+    /// Remember the positions of the nodes in the graph of the tab, so that they can be restored later.
+    ///
+    /// Only nodes with a key that stays the same when they reappear are remembered.
+    fn remember_layout(&self, tab: &RemoteTab) {
+        let imp = imp::Application::from_instance(self);
+        let graph = tab.graph.borrow();
+        let positions = graph.nodes().filter_map(|node| {
+            let key = NodeLabels::key(&node.properties)?;
+            let (x, y) = tab.graphview.node_position(node.id)?;
+            Some((key, Position { x, y }))
+        });
+        imp.layouts
+            .borrow_mut()
+            .set_positions(&tab.remote_name(), positions);
+    }
 
+    fn save_layouts(&self) {
         let imp = imp::Application::from_instance(self);
+        if let Err(e) = imp.layouts.borrow().save() {
+            warn!("Failed to save layouts: {}", e);
+        }
+    }
 
-        let port = view::Port::new(id, name, direction, media_type);
+    /// Show the remote of the current tab in the title of the main window.
+    fn update_title(&self) {
+        let imp = imp::Application::from_instance(self);
 
-        // Create or delete a link if the widget emits the "port-toggled" signal.
-        if let Err(e) = port.connect_local(
-            "port_toggled",
-            false,
-            clone!(@weak self as app => @default-return None, move |args| {
-                // Args always look like this: &[widget, id_port_from, id_port_to]
-                let port_from = args[1].get::<u32>().unwrap();
-                let port_to = args[2].get::<u32>().unwrap();
+        if let Some(window) = imp.window.get() {
+            let title = match self.current_tab() {
+                Some(tab) => format!("Helvum - Pipewire Patchbay - {}", tab.remote_name()),
+                None => "Helvum - Pipewire Patchbay".to_string(),
+            };
+            window.set_title(Some(&title));
+        }
+    }
 
-                app.toggle_link(port_from, port_to);
+    /// Ask the user for the name or socket path of a remote and show its graph in a new tab.
+    fn ask_for_remote(&self) {
+        let dialog = view::new_dialog(
+            "Connect to Remote",
+            self.active_window().as_ref(),
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                ("C_onnect", gtk::ResponseType::Accept),
+            ],
+        );
 
-                None
+        let entry = gtk::EntryBuilder::new()
+            .placeholder_text(&pipewire_connection::remote_display_name(None))
+            .activates_default(true)
+            .hexpand(true)
+            .build();
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        vbox.append(&view::mnemonic_label("_Remote name or socket path", &entry));
+        vbox.append(&entry);
+        view::set_dialog_content(&dialog, &vbox);
+
+        dialog.connect_response(
+            clone!(@weak self as app, @weak entry => move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    // An empty name connects to the default remote.
+                    let remote = Some(entry.text().trim().to_string()).filter(|remote| !remote.is_empty());
+                    app.open_tab(Source::Remote(remote), None);
+                }
+                dialog.destroy();
             }),
-        ) {
-            warn!("Failed to connect to \"port-toggled\" signal: {}", e);
+        );
+        dialog.show();
+    }
+
+    /// Handle a message from the backend of the tab.
+    fn handle_message(&self, tab: &RemoteTab, message: PipewireMessage) {
+        match message {
+            PipewireMessage::GraphEvent(event) => {
+                // Backends may only announce the removal of an object itself,
+                // so the removal of the objects belonging to it is handled first.
+                let events = match event {
+                    GraphEvent::DeviceRemoved { id }
+                    | GraphEvent::NodeRemoved { id }
+                    | GraphEvent::PortRemoved { id } => tab.graph.borrow().removal_events(id),
+                    event => vec![event],
+                };
+                for event in events {
+                    self.handle_graph_event(tab, event);
+                }
+            }
+            PipewireMessage::Synced => self.handle_synced(tab),
+            PipewireMessage::ConnectionFailed(e) => self.show_error(&format!(
+                "Failed to connect to {}: {}",
+                tab.remote_name(),
+                e
+            )),
+        }
+    }
+
+    /// Apply a change of the graph of the tab's remote to our copy of it and update the view to match.
+    fn handle_graph_event(&self, tab: &RemoteTab, event: GraphEvent) {
+        let imp = imp::Application::from_instance(self);
+
+        self.emit_dbus_signal(tab, &event);
+
+        // The status label is shared by all tabs, so only changes to the visible graph are announced.
+        if self
+            .current_tab()
+            .map_or(false, |current| current.page == tab.page)
+        {
+            self.announce_link_change(&tab.graph.borrow(), &event);
+        }
+        self.update_view(&tab.graphview, &tab.graph.borrow(), &event, true);
+
+        // Put nodes back where they were the last time the remote was shown.
+        if let GraphEvent::NodeAdded(node) = &event {
+            let position = NodeLabels::key(&node.properties)
+                .and_then(|key| imp.layouts.borrow().position(&tab.remote_name(), &key));
+            if let Some(position) = position {
+                tab.graphview
+                    .set_node_position(node.id, position.x, position.y);
+            }
         }
 
-        imp.graphview.add_port(node_id, id, port);
+        tab.graph.borrow_mut().apply(&event);
+
+        if tab.comparison.borrow().is_some() {
+            self.update_comparison(tab);
+        }
     }
 
-    /// Add a new link to the view.
-    fn add_link(
-        &self,
-        id: u32,
-        node_from: u32,
-        port_from: u32,
-        node_to: u32,
-        port_to: u32,
-        active: bool,
-    ) {
-        info!("Adding link to graph: id {}", id);
+    /// Export our D-Bus interface on the object path of the application,
+    /// so that other programs can inspect and change the links of the first tab's remote.
+    fn register_dbus_service(&self) {
+        let imp = imp::Application::from_instance(self);
 
-        // FIXME: Links should be colored depending on the data they carry (video, audio, midi) like ports are.
+        let (connection, object_path) = match (self.dbus_connection(), self.dbus_object_path()) {
+            (Some(connection), Some(object_path)) => (connection, object_path),
+            _ => {
+                info!("Not registered on the session bus, not providing the D-Bus interface");
+                return;
+            }
+        };
 
-        // Update graph to contain the new link.
-        imp::Application::from_instance(self).graphview.add_link(
-            id,
-            PipewireLink {
-                node_from,
-                port_from,
-                node_to,
-                port_to,
-            },
-            active,
+        let handler = clone!(@weak self as app => @default-return Err("The application is shutting down".to_string()),
+            move |method: &str, parameters: &glib::Variant| app.handle_dbus_call(method, parameters)
         );
+        match DBusService::register(&connection, &object_path, handler) {
+            Ok(dbus_service) => *imp.dbus_service.borrow_mut() = Some(dbus_service),
+            Err(e) => warn!("Failed to export the D-Bus interface: {}", e),
+        }
     }
 
-    fn link_state_changed(&self, id: u32, active: bool) {
-        info!(
-            "Link state changed: Link (id={}) is now {}",
-            id,
-            if active { "active" } else { "inactive" }
-        );
+    /// Handle a call of a method of our D-Bus interface, returning its return value.
+    ///
+    /// All methods operate on the first tab, which shows the first remote given on the command line.
+    fn handle_dbus_call(
+        &self,
+        method: &str,
+        parameters: &glib::Variant,
+    ) -> Result<Option<glib::Variant>, String> {
+        let tab = self
+            .tabs()
+            .into_iter()
+            .next()
+            .ok_or("Not connected to a remote")?;
+        // The arguments were already checked against the signature of the method.
+        let string_arg = |index: usize| {
+            parameters
+                .child_value(index)
+                .get::<String>()
+                .unwrap_or_default()
+        };
 
-        imp::Application::from_instance(self)
-            .graphview
-            .set_link_state(id, active);
+        match method {
+            "ListNodes" => {
+                let graph = tab.graph.borrow();
+                let mut nodes: Vec<(u32, String)> = graph
+                    .nodes()
+                    .map(|node| (node.id, node.name.clone()))
+                    .collect();
+                nodes.sort_unstable();
+                Ok(Some((nodes,).to_variant()))
+            }
+            "ListLinks" => {
+                let graph = tab.graph.borrow();
+                let mut links: Vec<(u32, String, String)> = graph
+                    .links()
+                    .filter_map(|link| {
+                        Some((
+                            link.id,
+                            graph.port_full_name(link.port_from)?,
+                            graph.port_full_name(link.port_to)?,
+                        ))
+                    })
+                    .collect();
+                links.sort_unstable();
+                Ok(Some((links,).to_variant()))
+            }
+            "Link" => {
+                let graph = tab.graph.borrow();
+                let pairs = graph.resolve_pairs(&string_arg(0), &string_arg(1))?;
+                for (port_from, port_to) in pairs {
+                    if graph.link_between(port_from, port_to).is_none() {
+                        tab.toggle_link(port_from, port_to)?;
+                    }
+                }
+                Ok(None)
+            }
+            "Unlink" => {
+                let (output, input) = (string_arg(0), string_arg(1));
+                let graph = tab.graph.borrow();
+                let links = graph.links_matching(&output, &input);
+                if links.is_empty() {
+                    return Err(format!("No links between \"{}\" and \"{}\"", output, input));
+                }
+                for link in links {
+                    tab.toggle_link(link.port_from, link.port_to)?;
+                }
+                Ok(None)
+            }
+            "ApplyPreset" => self.apply_preset(&tab, &string_arg(0), false).map(|_| None),
+            "SavePreset" => self.save_preset(&tab, &string_arg(0)).map(|_| None),
+            _ => Err(format!("Unknown method {}", method)),
+        }
     }
 
-    // Toggle a link between the two specified ports on the remote pipewire server.
-    fn toggle_link(&self, port_from: u32, port_to: u32) {
+    /// Notify listeners of our D-Bus interface about a change of the graph shown in the tab.
+    ///
+    /// Only changes of the first tab are signaled, as the methods of the interface operate on it as well.
+    /// The event must not have been applied to the graph of the tab yet.
+    fn emit_dbus_signal(&self, tab: &RemoteTab, event: &GraphEvent) {
         let imp = imp::Application::from_instance(self);
-        let sender = imp.pw_sender.get().expect("pw_sender not set").borrow_mut();
-        sender
-            .send(GtkMessage::ToggleLink { port_from, port_to })
-            .expect("Failed to send message");
-    }
 
-    /// Remove the node with the specified id from the view.
-    fn remove_node(&self, id: u32) {
-        info!("Removing node from graph: id {}", id);
+        let dbus_service = imp.dbus_service.borrow();
+        let dbus_service = match dbus_service.as_ref() {
+            Some(dbus_service) => dbus_service,
+            None => return,
+        };
+        if !self
+            .tabs()
+            .first()
+            .map_or(false, |first| first.page == tab.page)
+        {
+            return;
+        }
+
+        let graph = tab.graph.borrow();
+        match event {
+            GraphEvent::NodeAdded(node) => {
+                dbus_service.emit_signal("NodeAdded", &(node.id, node.name.clone()).to_variant())
+            }
+            GraphEvent::NodeRemoved { id } => {
+                dbus_service.emit_signal("NodeRemoved", &(*id,).to_variant())
+            }
+            GraphEvent::LinkAdded(link) => {
+                if let (Some(output), Some(input)) = (
+                    graph.port_full_name(link.port_from),
+                    graph.port_full_name(link.port_to),
+                ) {
+                    dbus_service.emit_signal("LinkAdded", &(link.id, output, input).to_variant());
+                }
+            }
+            GraphEvent::LinkRemoved { id } => {
+                dbus_service.emit_signal("LinkRemoved", &(*id,).to_variant())
+            }
+            _ => {}
+        }
+    }
 
+    /// Create all links of the preset with the specified name that do not exist on the tab's remote yet.
+    ///
+    /// If the preset is applied `exclusive`ly, all other links are removed.
+    /// Links that can be resolved are changed even if others can not, which is reported as an error afterwards.
+    fn apply_preset(&self, tab: &RemoteTab, name: &str, exclusive: bool) -> Result<(), String> {
         let imp = imp::Application::from_instance(self);
-        imp.graphview.remove_node(id);
+
+        let preset = imp
+            .presets
+            .borrow()
+            .get(&tab.remote_name(), name)
+            .cloned()
+            .ok_or_else(|| format!("No preset named \"{}\"", name))?;
+
+        let graph = tab.graph.borrow();
+        let diff = preset.diff(&graph);
+        for (port_from, port_to) in diff.added {
+            tab.toggle_link(port_from, port_to)?;
+        }
+        if exclusive {
+            for link in diff.removed.iter().filter_map(|id| graph.link(*id)) {
+                tab.toggle_link(link.port_from, link.port_to)?;
+            }
+        }
+
+        if diff.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(diff.errors.join("\n"))
+        }
     }
 
-    /// Remove the port with the id `id` from the node with the id `node_id`
-    /// from the view.
-    fn remove_port(&self, id: u32, node_id: u32) {
-        info!("Removing port from graph: id {}, node_id: {}", id, node_id);
+    /// Store the current links of the tab's remote as a preset with the specified name,
+    /// replacing the links of any preset with the same name.
+    fn save_preset(&self, tab: &RemoteTab, name: &str) -> Result<(), String> {
+        if name.is_empty() {
+            return Err("Presets need a name".to_string());
+        }
 
-        let imp = imp::Application::from_instance(self);
-        imp.graphview.remove_port(id, node_id);
+        let remote = tab.remote_name();
+        let mut preset = Preset::from_graph(&tab.graph.borrow());
+        self.change_presets(|presets| {
+            // Updating a preset keeps its shortcut.
+            preset.accelerator = presets
+                .get(&remote, name)
+                .and_then(|existing| existing.accelerator.clone());
+            presets.set(&remote, name, preset);
+            Ok(())
+        })
     }
 
-    /// Remove the link with the specified id from the view.
-    fn remove_link(&self, id: u32) {
-        info!("Removing link from graph: id {}", id);
+    /// Change the stored presets, then save them and update the presets menu.
+    fn change_presets(
+        &self,
+        change: impl FnOnce(&mut Presets) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let imp = imp::Application::from_instance(self);
+
+        change(&mut imp.presets.borrow_mut())?;
+        let saved = imp
+            .presets
+            .borrow()
+            .save()
+            .map_err(|e| format!("Failed to save presets: {}", e));
+        self.update_presets();
+        for tab in self.tabs() {
+            if tab.comparison.borrow().is_some() {
+                self.update_comparison(&tab);
+            }
+        }
+
+        saved
+    }
 
+    /// Fill the presets menu with the presets of the current tab's remote and set up their keyboard shortcuts.
+    fn update_presets(&self) {
         let imp = imp::Application::from_instance(self);
-        imp.graphview.remove_link(id);
+        let menu = match imp.presets_menu.get() {
+            Some(menu) => menu,
+            None => return,
+        };
+
+        // Shortcuts of the presets of the previously shown remote must not apply to this one.
+        for detailed_action in imp.preset_accels.borrow_mut().drain(..) {
+            self.set_accels_for_action(&detailed_action, &[]);
+        }
+        menu.remove_all();
+
+        let save_section = gio::Menu::new();
+        save_section.append(Some("_Save Current Links…"), Some("app.save-preset"));
+        menu.append_section(None, &save_section);
+
+        let tab = match self.current_tab() {
+            Some(tab) => tab,
+            None => return,
+        };
+        let remote = tab.remote_name();
+        let presets = imp.presets.borrow();
+        let presets_section = gio::Menu::new();
+        for name in presets.names(&remote) {
+            let target = name.to_variant();
+            let submenu = gio::Menu::new();
+            for (label, action) in [
+                ("_Apply", "app.apply-preset"),
+                ("Apply _Exclusively", "app.apply-preset-exclusive"),
+                ("_Preview…", "app.preview-preset"),
+                ("_Compare With Graph", "app.compare-preset"),
+                ("_Update From Graph", "app.update-preset"),
+                ("_Rename…", "app.rename-preset"),
+                ("Set _Shortcut…", "app.preset-shortcut"),
+                ("_Delete", "app.delete-preset"),
+            ] {
+                let item = gio::MenuItem::new(Some(label), None);
+                item.set_action_and_target_value(Some(action), Some(&target));
+                submenu.append_item(&item);
+            }
+            // Underscores in the name would otherwise be taken as mnemonics.
+            presets_section.append_submenu(Some(&name.replace('_', "__")), &submenu);
+
+            if let Some(accelerator) = presets
+                .get(&remote, &name)
+                .and_then(|preset| preset.accelerator.as_deref())
+            {
+                let detailed_action = format!("app.apply-preset({})", target.print(true));
+                self.set_accels_for_action(&detailed_action, &[accelerator]);
+                imp.preset_accels.borrow_mut().push(detailed_action);
+            }
+        }
+        menu.append_section(None, &presets_section);
+    }
+
+    /// Ask the user for a name to save the current links of the tab as a preset under,
+    /// or for a new name of the preset named `current` if it is set.
+    fn ask_for_preset_name(&self, tab: &Rc<RemoteTab>, current: Option<&str>) {
+        let (title, accept_label) = match current {
+            Some(_) => ("Rename Preset", "_Rename"),
+            None => ("Save Preset", "_Save"),
+        };
+        let dialog = view::new_dialog(
+            title,
+            self.active_window().as_ref(),
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                (accept_label, gtk::ResponseType::Accept),
+            ],
+        );
+
+        let entry = gtk::EntryBuilder::new()
+            .text(current.unwrap_or_default())
+            .activates_default(true)
+            .hexpand(true)
+            .build();
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        vbox.append(&view::mnemonic_label("_Name", &entry));
+        vbox.append(&entry);
+        view::set_dialog_content(&dialog, &vbox);
+
+        let current = current.map(str::to_string);
+        dialog.connect_response(
+            clone!(@weak self as app, @weak tab, @weak entry => move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    let name = entry.text().trim().to_string();
+                    let result = match &current {
+                        Some(current) => app.change_presets(|presets| {
+                            presets.rename(&tab.remote_name(), current, &name)
+                        }),
+                        None => app.save_preset(&tab, &name),
+                    };
+                    if let Err(e) = result {
+                        app.show_error(&e);
+                    }
+                }
+                dialog.destroy();
+            }),
+        );
+        dialog.show();
+    }
+
+    /// Ask the user to press the keyboard shortcut that applies the preset.
+    fn ask_for_preset_shortcut(&self, tab: &Rc<RemoteTab>, name: &str) {
+        let dialog = view::new_dialog(
+            &format!("Shortcut for {}", name),
+            self.active_window().as_ref(),
+            &[
+                ("_Remove Shortcut", gtk::ResponseType::Reject),
+                ("_Cancel", gtk::ResponseType::Cancel),
+            ],
+        );
+        view::set_dialog_content(
+            &dialog,
+            &gtk::Label::new(Some("Press the keys that should apply the preset.")),
+        );
+
+        let remote = tab.remote_name();
+        let name = name.to_string();
+        let set_accelerator = clone!(@weak self as app => move |accelerator: Option<String>| {
+            let result = app.change_presets(|presets| {
+                presets
+                    .get_mut(&remote, &name)
+                    .ok_or_else(|| format!("No preset named \"{}\"", name))?
+                    .accelerator = accelerator;
+                Ok(())
+            });
+            if let Err(e) = result {
+                app.show_error(&e);
+            }
+        });
+
+        let key_controller = gtk::EventControllerKey::new();
+        key_controller.connect_key_pressed(
+            clone!(@weak dialog, @strong set_accelerator => @default-return gtk::Inhibit(false), move |_, key, _, modifiers| {
+                let modifiers = modifiers & gtk::accelerator_get_default_mod_mask();
+                // Escape closes the dialog, and keys that are only modifiers wait for the rest of the shortcut.
+                if (key == gtk::gdk::keys::constants::Escape && modifiers.is_empty())
+                    || !gtk::accelerator_valid(key, modifiers)
+                {
+                    return gtk::Inhibit(false);
+                }
+                if let Some(accelerator) = gtk::accelerator_name(key, modifiers) {
+                    set_accelerator(Some(accelerator.to_string()));
+                }
+                dialog.destroy();
+                gtk::Inhibit(true)
+            }),
+        );
+        dialog.add_controller(&key_controller);
+
+        dialog.connect_response(move |dialog, response| {
+            if response == gtk::ResponseType::Reject {
+                set_accelerator(None);
+            }
+            dialog.destroy();
+        });
+        dialog.show();
+    }
+
+    /// Compare the links of the tab with the preset with the specified name,
+    /// or stop comparing them if `name` is `None`.
+    fn compare_with_preset(&self, tab: &RemoteTab, name: Option<&str>) {
+        *tab.comparison.borrow_mut() = name.map(|name| (name.to_string(), PresetDiff::default()));
+        self.update_comparison(tab);
+    }
+
+    /// Compare the links of the tab with its preset again, after either of them changed.
+    fn update_comparison(&self, tab: &RemoteTab) {
+        let imp = imp::Application::from_instance(self);
+
+        let name = tab
+            .comparison
+            .borrow()
+            .as_ref()
+            .map(|(name, _)| name.clone());
+        // Presets that were deleted or renamed can no longer be compared with.
+        let preset = name
+            .as_ref()
+            .and_then(|name| imp.presets.borrow().get(&tab.remote_name(), name).cloned());
+        let (name, preset) = match (name, preset) {
+            (Some(name), Some(preset)) => (name, preset),
+            _ => {
+                tab.comparison.borrow_mut().take();
+                tab.graphview.set_comparison(None);
+                tab.comparison_panel.set_visible(false);
+                return;
+            }
+        };
+
+        let graph = tab.graph.borrow();
+        let diff = preset.diff(&graph);
+
+        let differences: Vec<(view::DifferenceKind, String)> = diff
+            .added
+            .iter()
+            .map(|(port_from, port_to)| {
+                (
+                    view::DifferenceKind::Missing,
+                    link_name(&graph, *port_from, *port_to),
+                )
+            })
+            .chain(diff.removed.iter().filter_map(|id| {
+                let link = graph.link(*id)?;
+                Some((
+                    view::DifferenceKind::Extra,
+                    link_name(&graph, link.port_from, link.port_to),
+                ))
+            }))
+            .chain(
+                diff.errors
+                    .iter()
+                    .map(|e| (view::DifferenceKind::Unresolved, e.clone())),
+            )
+            .collect();
+        let missing = diff
+            .added
+            .iter()
+            .filter_map(|(port_from, port_to)| {
+                Some(PipewireLink {
+                    node_from: graph.port(*port_from)?.node_id,
+                    port_from: *port_from,
+                    node_to: graph.port(*port_to)?.node_id,
+                    port_to: *port_to,
+                })
+            })
+            .collect();
+
+        tab.graphview.set_comparison(Some(view::LinkComparison {
+            missing,
+            extra: diff.removed.iter().copied().collect(),
+        }));
+        tab.comparison_panel.set_differences(&name, &differences);
+        tab.comparison_panel.set_visible(true);
+
+        drop(graph);
+        *tab.comparison.borrow_mut() = Some((name, diff));
+    }
+
+    /// Fix the difference with the specified index in the comparison panel of the tab,
+    /// by creating a missing link or removing an extra one.
+    fn fix_difference(&self, tab: &RemoteTab, index: usize) {
+        let comparison = tab.comparison.borrow();
+        let diff = match comparison.as_ref() {
+            Some((_, diff)) => diff,
+            None => return,
+        };
+
+        // Differences are listed in the order missing, extra and unresolved links.
+        let pair = match diff.added.get(index) {
+            Some(pair) => Some(*pair),
+            None => diff.removed.get(index - diff.added.len()).and_then(|id| {
+                let graph = tab.graph.borrow();
+                let link = graph.link(*id)?;
+                Some((link.port_from, link.port_to))
+            }),
+        };
+        if let Some((port_from, port_to)) = pair {
+            if let Err(e) = tab.toggle_link(port_from, port_to) {
+                self.show_error(&e);
+            }
+        }
+    }
+
+    /// Show which links applying the preset would create and remove, and let the user apply it from there.
+    fn preview_preset(&self, tab: &Rc<RemoteTab>, name: &str) {
+        let imp = imp::Application::from_instance(self);
+
+        let preset = match imp.presets.borrow().get(&tab.remote_name(), name) {
+            Some(preset) => preset.clone(),
+            None => {
+                self.show_error(&format!("No preset named \"{}\"", name));
+                return;
+            }
+        };
+        let graph = tab.graph.borrow();
+        let diff = preset.diff(&graph);
+        let dialog = view::new_dialog(
+            &format!("Apply {}", name),
+            self.active_window().as_ref(),
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                ("Apply _Exclusively", gtk::ResponseType::Other(0)),
+                ("_Apply", gtk::ResponseType::Accept),
+            ],
+        );
+
+        let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let sections = [
+            (
+                "Links that will be created",
+                diff.added
+                    .iter()
+                    .map(|(port_from, port_to)| link_name(&graph, *port_from, *port_to))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "Links that will be removed when applying exclusively",
+                diff.removed
+                    .iter()
+                    .filter_map(|id| graph.link(*id))
+                    .map(|link| link_name(&graph, link.port_from, link.port_to))
+                    .collect(),
+            ),
+            ("Entries that can not be applied", diff.errors.clone()),
+        ];
+        for (heading, lines) in sections.iter().filter(|(_, lines)| !lines.is_empty()) {
+            let heading = gtk::LabelBuilder::new().label(heading).xalign(0.0).build();
+            heading.add_css_class("heading");
+            vbox.append(&heading);
+            let lines = gtk::LabelBuilder::new()
+                .label(&lines.join("\n"))
+                .selectable(true)
+                .xalign(0.0)
+                .build();
+            vbox.append(&lines);
+        }
+        if sections.iter().all(|(_, lines)| lines.is_empty()) {
+            vbox.append(&gtk::Label::new(Some(
+                "The links already match the preset.",
+            )));
+        }
+        let scrolled_window = gtk::ScrolledWindowBuilder::new()
+            .child(&vbox)
+            .propagate_natural_height(true)
+            .max_content_height(400)
+            .build();
+        view::set_dialog_content(&dialog, &scrolled_window);
+
+        let name = name.to_string();
+        dialog.connect_response(
+            clone!(@weak self as app, @weak tab => move |dialog, response| {
+                let exclusive = match response {
+                    gtk::ResponseType::Accept => false,
+                    gtk::ResponseType::Other(0) => true,
+                    _ => {
+                        dialog.destroy();
+                        return;
+                    }
+                };
+                dialog.destroy();
+                if let Err(e) = app.apply_preset(&tab, &name, exclusive) {
+                    app.show_error(&e);
+                }
+            }),
+        );
+        dialog.show();
+    }
+
+    /// Show added and removed links in the status label, so that screen readers announce them.
+    ///
+    /// The event must not have been applied to `graph` yet.
+    fn announce_link_change(&self, graph: &Graph, event: &GraphEvent) {
+        let imp = imp::Application::from_instance(self);
+
+        let (verb, link) = match event {
+            GraphEvent::LinkAdded(link) => ("Linked", link),
+            GraphEvent::LinkRemoved { id } => match graph.link(*id) {
+                Some(link) => ("Unlinked", link),
+                None => return,
+            },
+            _ => return,
+        };
+
+        if let (Some(status_label), Some(from), Some(to)) = (
+            imp.status_label.get(),
+            graph.port_full_name(link.port_from),
+            graph.port_full_name(link.port_to),
+        ) {
+            status_label.set_text(&format!("{} {} to {}", verb, from, to));
+        }
+    }
+
+    /// Update the view to reflect the event, which must not have been applied to `graph` yet.
+    ///
+    /// If the view is not `editable`, its ports can not be used to create or remove links.
+    fn update_view(
+        &self,
+        graphview: &view::GraphView,
+        graph: &Graph,
+        event: &GraphEvent,
+        editable: bool,
+    ) {
+        // Hidden ports and their links are left out of the view entirely.
+        if self.is_hidden(graph, event) {
+            return;
+        }
+
+        match event {
+            GraphEvent::DeviceAdded(_) | GraphEvent::DeviceRemoved { .. } => {
+                // Devices are not shown in the view.
+            }
+            GraphEvent::NodeAdded(node) => self.add_node(graphview, node, editable),
+            GraphEvent::PortAdded(port) => self.add_port(graphview, graph, port, editable),
+            GraphEvent::LinkAdded(link) => self.add_link(graphview, link),
+            GraphEvent::LinkStateChanged { id, state } => {
+                self.link_state_changed(graphview, *id, state.clone())
+            }
+            GraphEvent::LinkFormatChanged { id, format } => {
+                graphview.set_link_format(*id, format.clone())
+            }
+            GraphEvent::LinkPropertiesChanged { id, properties } => {
+                if let Some(link) = graph.link(*id) {
+                    let link = model::Link {
+                        properties: properties.clone(),
+                        ..link.clone()
+                    };
+                    graphview.set_link_properties(*id, link.passive(), link.linger());
+                }
+            }
+            GraphEvent::NodeRemoved { id } => self.remove_node(graphview, *id),
+            GraphEvent::PortRemoved { id } => {
+                if let Some(port) = graph.port(*id) {
+                    self.remove_port(graphview, *id, port.node_id);
+                }
+            }
+            GraphEvent::LinkRemoved { id } => self.remove_link(graphview, *id),
+        }
+    }
+
+    /// Check whether the event concerns a port that is hidden in the preferences, or a link from or to one.
+    ///
+    /// The event must not have been applied to `graph` yet.
+    fn is_hidden(&self, graph: &Graph, event: &GraphEvent) -> bool {
+        let port_hidden = |id: u32| {
+            graph
+                .port(id)
+                .map_or(false, |port| self.is_port_hidden(port))
+        };
+        let link_hidden = |id: u32| {
+            graph.link(id).map_or(false, |link| {
+                port_hidden(link.port_from) || port_hidden(link.port_to)
+            })
+        };
+
+        match event {
+            GraphEvent::PortAdded(port) => self.is_port_hidden(port),
+            GraphEvent::LinkAdded(link) => port_hidden(link.port_from) || port_hidden(link.port_to),
+            GraphEvent::LinkStateChanged { id, .. }
+            | GraphEvent::LinkFormatChanged { id, .. }
+            | GraphEvent::LinkPropertiesChanged { id, .. } => link_hidden(*id),
+            _ => false,
+        }
+    }
+
+    fn is_port_hidden(&self, port: &model::Port) -> bool {
+        let imp = imp::Application::from_instance(self);
+        let settings = match imp.settings.get() {
+            Some(settings) => settings,
+            None => return false,
+        };
+
+        (settings.boolean("hide-monitor-ports")
+            && port.properties.get("port.monitor").map(String::as_str) == Some("true"))
+            || (settings.boolean("hide-midi-ports") && port.media_type == Some(MediaType::Midi))
+    }
+
+    /// Show the graphs of all tabs again from scratch, after the ports that are hidden changed.
+    fn rebuild_views(&self) {
+        for tab in self.tabs() {
+            let dump = GraphDump::from_graph(&tab.graph.borrow(), |id| {
+                tab.graphview
+                    .node_position(id)
+                    .map(|(x, y)| Position { x, y })
+            });
+
+            tab.graphview.clear();
+            let mut graph = Graph::new();
+            for event in dump.events() {
+                self.update_view(&tab.graphview, &graph, &event, true);
+                graph.apply(&event);
+            }
+            for node in &dump.nodes {
+                if let Some(position) = node.position {
+                    tab.graphview
+                        .set_node_position(node.id, position.x, position.y);
+                }
+            }
+
+            if tab.comparison.borrow().is_some() {
+                self.update_comparison(&tab);
+            }
+        }
+    }
+
+    /// Add a new node to the view.
+    ///
+    /// If the view is not `editable`, the node can only be changed in the view itself.
+    fn add_node(&self, graphview: &view::GraphView, node: &model::Node, editable: bool) {
+        info!("Adding node to graph: id {}", node.id);
+
+        let imp = imp::Application::from_instance(self);
+        let label_key = NodeLabels::key(&node.properties);
+        let widget = view::Node::new(
+            &node.name,
+            label_key.clone(),
+            label_key
+                .as_deref()
+                .and_then(|key| imp.node_labels.borrow().get(key).cloned())
+                .as_ref(),
+            node.node_type,
+        );
+
+        // Show all names of the node in the tooltip.
+        let names: Vec<String> = [
+            ("Name", "node.name"),
+            ("Description", "node.description"),
+            ("Nick", "node.nick"),
+            ("Path", "object.path"),
+        ]
+        .iter()
+        .filter_map(|(title, key)| {
+            node.properties
+                .get(*key)
+                .map(|value| format!("{}: {}", title, value))
+        })
+        .collect();
+        if !names.is_empty() {
+            widget.set_label_tooltip(&names.join("\n"));
+        }
+
+        // Custom labels are stored locally, so they can be edited in offline graphs as well.
+        if let Some(label_key) = label_key {
+            let name = node.name.clone();
+            if let Err(e) = widget.connect_local(
+                "edit-label",
+                false,
+                clone!(@weak self as app => @default-return None, move |_| {
+                    app.edit_node_label(&label_key, &name);
+                    None
+                }),
+            ) {
+                warn!("Failed to connect to \"edit-label\" signal: {}", e);
+            }
+        }
+
+        // Pipewire refuses to suspend or destroy nodes that we are not permitted to change.
+        widget.set_action_enabled("disconnect", editable);
+        widget.set_action_enabled("connect-default", editable);
+        widget.set_action_enabled("suspend", editable && node.permissions.execute);
+        widget.set_action_enabled("destroy", editable && node.permissions.write);
+        if node.properties.contains_key(VIRTUAL_NODE_PROPERTY) {
+            widget.add_menu_action("Remove _Virtual Node", "remove-virtual-node");
+            widget.set_action_enabled("remove-virtual-node", editable);
+        }
+        let node_id = node.id;
+        if let Err(e) = widget.connect_local(
+            "menu-action",
+            false,
+            clone!(@weak self as app, @weak graphview => @default-return None, move |args| {
+                match args[1].get::<String>() {
+                    Ok(action) => app.node_menu_action(&graphview, node_id, &action),
+                    Err(e) => warn!("Invalid node menu action: {}", e),
+                }
+                None
+            }),
+        ) {
+            warn!("Failed to connect to \"menu-action\" signal: {}", e);
+        }
+
+        graphview.add_node(node.id, widget);
+    }
+
+    /// Handle an action chosen in the context menu of the node with the id in the graphview.
+    fn node_menu_action(&self, graphview: &view::GraphView, node_id: u32, action: &str) {
+        // The node is looked up again, as it may have changed since its menu was set up.
+        let node = match self.node_of(graphview, node_id) {
+            Some(node) => node,
+            None => {
+                warn!("Node {} is no longer in the graph", node_id);
+                return;
+            }
+        };
+
+        let message = match action {
+            "hide" => {
+                graphview.hide_node(node.id);
+                return;
+            }
+            "properties" => {
+                self.show_node_properties(&node);
+                return;
+            }
+            "destroy" => {
+                self.confirm_destroy_node(graphview, &node);
+                return;
+            }
+            "disconnect" => GtkMessage::DisconnectNode { node_id: node.id },
+            "connect-default" => GtkMessage::ConnectNodeToDefault {
+                node_id: node.id,
+                options: LinkOptions::default(),
+            },
+            "suspend" => GtkMessage::SuspendNode { node_id: node.id },
+            "remove-virtual-node" => {
+                if let (Some(tab), Some(name)) = (
+                    self.tab_of(graphview),
+                    node.properties.get(VIRTUAL_NODE_PROPERTY),
+                ) {
+                    self.remove_virtual_node(&tab, name);
+                }
+                return;
+            }
+            _ => {
+                warn!("Unknown node menu action {}", action);
+                return;
+            }
+        };
+
+        let sent = self
+            .tab_of(graphview)
+            .map_or(false, |tab| tab.send(message));
+        if !sent {
+            warn!("Not connected to a remote, can not change node {}", node.id);
+        }
+    }
+
+    /// Handle the backend having processed all earlier requests,
+    /// which the first time means that it has sent the graph it started with.
+    fn handle_synced(&self, tab: &RemoteTab) {
+        if tab.synced.replace(true) {
+            return;
+        }
+
+        // Virtual nodes that still exist from an earlier run are not created again by the backend.
+        let imp = imp::Application::from_instance(self);
+        let nodes = imp.virtual_nodes.borrow().get(&tab.remote_name()).to_vec();
+        for node in nodes {
+            info!("Creating kept virtual node {}", node.name);
+            tab.send(GtkMessage::CreateVirtualNode(node));
+        }
+    }
+
+    /// Ask the user for the configuration of a new virtual node and create it on the remote of the tab.
+    fn ask_for_virtual_node(&self, tab: &Rc<RemoteTab>) {
+        let dialog = view::new_dialog(
+            "New Virtual Node",
+            self.active_window().as_ref(),
+            &[
+                ("_Cancel", gtk::ResponseType::Cancel),
+                ("C_reate", gtk::ResponseType::Accept),
+            ],
+        );
+
+        let kind = gtk::ComboBoxText::new();
+        kind.append(Some(VirtualNodeKind::NullSink.name()), "Null sink");
+        kind.append(
+            Some(VirtualNodeKind::Loopback.name()),
+            "Loopback to the default sink",
+        );
+        kind.set_active_id(Some(VirtualNodeKind::NullSink.name()));
+        let name = gtk::EntryBuilder::new()
+            .text("virtual-sink")
+            .activates_default(true)
+            .hexpand(true)
+            .build();
+        let description = gtk::EntryBuilder::new()
+            .placeholder_text("Same as the name")
+            .activates_default(true)
+            .build();
+        let channels = gtk::SpinButton::with_range(1.0, 64.0, 1.0);
+        channels.set_value(2.0);
+        let positions = gtk::EntryBuilder::new()
+            .text(&VirtualNode::default_positions(2).join(","))
+            .activates_default(true)
+            .build();
+        // Changing the number of channels suggests the usual positions for them.
+        channels.connect_value_changed(clone!(@weak positions => move |channels| {
+            let count = channels.value_as_int() as usize;
+            positions.set_text(&VirtualNode::default_positions(count).join(","));
+        }));
+        let persistent =
+            gtk::CheckButton::with_mnemonic("_Keep it and create it again when Helvum connects");
+
+        let grid = view::form_grid(&[
+            ("_Type", kind.upcast_ref::<gtk::Widget>()),
+            ("_Name", name.upcast_ref()),
+            ("_Description", description.upcast_ref()),
+            ("C_hannels", channels.upcast_ref()),
+            ("_Positions", positions.upcast_ref()),
+        ]);
+        grid.attach(&persistent, 0, 5, 2, 1);
+        view::set_dialog_content(&dialog, &grid);
+
+        dialog.connect_response(
+            clone!(@weak self as app, @weak tab, @weak kind, @weak name, @weak description, @weak channels, @weak positions, @weak persistent => move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    let node = VirtualNode {
+                        kind: kind
+                            .active_id()
+                            .and_then(|id| VirtualNodeKind::from_name(&id))
+                            .unwrap_or(VirtualNodeKind::NullSink),
+                        name: name.text().trim().to_string(),
+                        description: Some(description.text().trim().to_string())
+                            .filter(|description| !description.is_empty()),
+                        positions: VirtualNode::parse_positions(&positions.text()),
+                        persistent: persistent.is_active(),
+                    };
+                    let count = channels.value_as_int() as usize;
+                    let result = node.validate().and_then(|_| {
+                        if node.positions.len() == count {
+                            Ok(())
+                        } else {
+                            Err(format!(
+                                "{} channel positions are given for {} channels",
+                                node.positions.len(),
+                                count
+                            ))
+                        }
+                    });
+                    // Keep the dialog open, so that the mistake can be corrected.
+                    if let Err(e) = result {
+                        app.show_error(&e);
+                        return;
+                    }
+                    app.create_virtual_node(&tab, node);
+                }
+                dialog.destroy();
+            }),
+        );
+        dialog.show();
+    }
+
+    /// Create the virtual node on the remote of the tab, keeping it if it is persistent.
+    fn create_virtual_node(&self, tab: &RemoteTab, node: VirtualNode) {
+        if node.persistent {
+            let imp = imp::Application::from_instance(self);
+            imp.virtual_nodes
+                .borrow_mut()
+                .add(&tab.remote_name(), node.clone());
+            self.save_virtual_nodes();
+        }
+
+        if !tab.send(GtkMessage::CreateVirtualNode(node)) {
+            warn!("Not connected to a remote, can not create virtual node");
+        }
+    }
+
+    /// Remove the virtual node with the specified name from the remote of the tab, and stop keeping it.
+    fn remove_virtual_node(&self, tab: &RemoteTab, name: &str) {
+        let imp = imp::Application::from_instance(self);
+        let kept = imp
+            .virtual_nodes
+            .borrow_mut()
+            .remove(&tab.remote_name(), name);
+        if kept {
+            self.save_virtual_nodes();
+        }
+
+        if !tab.send(GtkMessage::RemoveVirtualNode {
+            name: name.to_string(),
+        }) {
+            warn!(
+                "Not connected to a remote, can not remove virtual node {}",
+                name
+            );
+        }
+    }
+
+    fn save_virtual_nodes(&self) {
+        let imp = imp::Application::from_instance(self);
+        let saved = imp.virtual_nodes.borrow().save();
+        if let Err(e) = saved {
+            warn!("Failed to save virtual nodes: {}", e);
+            self.show_error(&format!("Failed to save virtual nodes: {}", e));
+        }
+    }
+
+    /// Show all properties of the node, ordered by their key.
+    fn show_node_properties(&self, node: &model::Node) {
+        let dialog = gtk::Dialog::with_buttons(
+            Some(&format!("Properties of {}", node.name)),
+            self.active_window().as_ref(),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[("_Close", gtk::ResponseType::Close)],
+        );
+        dialog.set_default_size(480, 400);
+
+        let grid = gtk::GridBuilder::new()
+            .row_spacing(6)
+            .column_spacing(12)
+            .build();
+        let mut properties: Vec<(&String, &String)> = node.properties.iter().collect();
+        properties.sort();
+        for (row, (key, value)) in properties.into_iter().enumerate() {
+            let key_label = gtk::LabelBuilder::new()
+                .label(key)
+                .selectable(true)
+                .xalign(0.0)
+                .yalign(0.0)
+                .build();
+            key_label.add_css_class("dim-label");
+            let value_label = gtk::LabelBuilder::new()
+                .label(value)
+                .selectable(true)
+                .wrap(true)
+                .xalign(0.0)
+                .hexpand(true)
+                .build();
+            grid.attach(&key_label, 0, row as i32, 1, 1);
+            grid.attach(&value_label, 1, row as i32, 1, 1);
+        }
+
+        let scrolled_window = gtk::ScrolledWindowBuilder::new()
+            .child(&grid)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .vexpand(true)
+            .build();
+        view::set_dialog_content(&dialog, &scrolled_window);
+        dialog.connect_response(|dialog, _| dialog.destroy());
+        dialog.show();
+    }
+
+    /// Ask the user whether the node should really be destroyed, and destroy it if so.
+    fn confirm_destroy_node(&self, graphview: &view::GraphView, node: &model::Node) {
+        let dialog = gtk::MessageDialog::new(
+            self.active_window().as_ref(),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            &format!(
+                "Destroy {}? The application or device providing it may stop working until it is restarted.",
+                node.name
+            ),
+        );
+        dialog.add_buttons(&[
+            ("_Cancel", gtk::ResponseType::Cancel),
+            ("_Destroy", gtk::ResponseType::Accept),
+        ]);
+        if let Some(button) = dialog.widget_for_response(gtk::ResponseType::Accept) {
+            button.add_css_class("destructive-action");
+        }
+
+        let node_id = node.id;
+        dialog.connect_response(
+            clone!(@weak self as app, @weak graphview => move |dialog, response| {
+                if response == gtk::ResponseType::Accept {
+                    let sent = app
+                        .tab_of(&graphview)
+                        .map_or(false, |tab| tab.send(GtkMessage::DestroyNode { node_id }));
+                    if !sent {
+                        warn!("Not connected to a remote, can not destroy node {}", node_id);
+                    }
+                }
+                dialog.destroy();
+            }),
+        );
+        dialog.show();
+    }
+
+    /// Add a new port to the view.
+    fn add_port(
+        &self,
+        graphview: &view::GraphView,
+        graph: &Graph,
+        port: &model::Port,
+        editable: bool,
+    ) {
+        info!("Adding port to graph: id {}", port.id);
+
+        let node_name = graph
+            .node(port.node_id)
+            .map_or("", |node| node.name.as_str());
+        let names = view::PortNames {
+            name: port.name.clone(),
+            alias: port.properties.get("port.alias").cloned(),
+            full_name: format!("{}:{}", node_name, port.name),
+            channel: port.properties.get("audio.channel").cloned(),
+            path: port.properties.get("object.path").cloned(),
+        };
+
+        let widget = view::Port::new(
+            port.id,
+            names,
+            port.direction,
+            port.media_type,
+            port.sort_key(),
+        );
+
+        if editable {
+            // Create or delete a link if the widget emits the "port-toggled" signal.
+            if let Err(e) = widget.connect_local(
+                "port_toggled",
+                false,
+                clone!(@weak self as app, @weak graphview => @default-return None, move |args| {
+                    // Args always look like this: &[widget, id_port_from, id_port_to, passive, linger]
+                    let port_from = args[1].get::<u32>().unwrap();
+                    let port_to = args[2].get::<u32>().unwrap();
+                    let options = LinkOptions {
+                        passive: args[3].get::<bool>().unwrap(),
+                        linger: args[4].get::<bool>().unwrap(),
+                    };
+
+                    app.toggle_link(&graphview, port_from, port_to, options);
+
+                    None
+                }),
+            ) {
+                warn!("Failed to connect to \"port-toggled\" signal: {}", e);
+            }
+        } else {
+            // Ports can not be targeted, so dragging them moves their node instead of starting a link.
+            widget.set_can_target(false);
+        }
+
+        graphview.add_port(port.node_id, port.id, widget);
+    }
+
+    /// Add a new link to the view.
+    fn add_link(&self, graphview: &view::GraphView, link: &model::Link) {
+        info!("Adding link to graph: id {}", link.id);
+
+        // FIXME: Links should be colored depending on the data they carry (video, audio, midi) like ports are.
+
+        // Update graph to contain the new link.
+        graphview.add_link(
+            link.id,
+            PipewireLink {
+                node_from: link.node_from,
+                port_from: link.port_from,
+                node_to: link.node_to,
+                port_to: link.port_to,
+            },
+            link.state.clone(),
+        );
+        graphview.set_link_format(link.id, link.format.clone());
+        graphview.set_link_properties(link.id, link.passive(), link.linger());
+    }
+
+    fn link_state_changed(&self, graphview: &view::GraphView, id: u32, state: model::LinkState) {
+        if let model::LinkState::Error(message) = &state {
+            warn!("Link (id={}) failed: {}", id, message);
+        } else {
+            info!("Link state changed: Link (id={}) is now {}", id, state);
+        }
+
+        graphview.set_link_state(id, state);
+    }
+
+    // Toggle a link between the two specified ports on the remote whose graph is shown in the graphview.
+    fn toggle_link(
+        &self,
+        graphview: &view::GraphView,
+        port_from: u32,
+        port_to: u32,
+        options: LinkOptions,
+    ) {
+        let sent = self.tab_of(graphview).map_or(false, |tab| {
+            tab.send(GtkMessage::ToggleLink {
+                port_from,
+                port_to,
+                options,
+            })
+        });
+        if !sent {
+            warn!("Not connected to a remote, can not toggle link");
+        }
+    }
+
+    /// Zoom the graphs of all tabs and offline graphs by the specified factor, storing it in the settings.
+    fn set_zoom(&self, zoom: f64) {
+        let zoom = zoom.max(view::MIN_ZOOM).min(view::MAX_ZOOM);
+        for graphview in self.graphviews() {
+            graphview.set_zoom(zoom);
+        }
+
+        let imp = imp::Application::from_instance(self);
+        if let Some(settings) = imp.settings.get() {
+            if (settings.double("zoom") - zoom).abs() > f64::EPSILON {
+                if let Err(e) = settings.set_double("zoom", zoom) {
+                    warn!("Failed to store zoom: {}", e);
+                }
+            }
+        }
+    }
+
+    fn zoom(&self) -> f64 {
+        let imp = imp::Application::from_instance(self);
+        match imp.settings.get() {
+            Some(settings) => settings.double("zoom"),
+            None => self.tabs().first().map_or(1.0, |tab| tab.graphview.zoom()),
+        }
+    }
+
+    fn color_scheme(&self) -> ColorScheme {
+        let imp = imp::Application::from_instance(self);
+        imp.settings
+            .get()
+            .and_then(|settings| ColorScheme::from_name(&settings.string("color-scheme")))
+            .unwrap_or(ColorScheme::System)
+    }
+
+    /// Store a string setting, unless it already has the value.
+    fn store_setting(&self, key: &str, value: &str) {
+        let imp = imp::Application::from_instance(self);
+        if let Some(settings) = imp.settings.get() {
+            if settings.string(key).as_str() != value {
+                if let Err(e) = settings.set_string(key, value) {
+                    warn!("Failed to store setting {}: {}", key, e);
+                }
+            }
+        }
+    }
+
+    /// Store the size of the main window and the scroll position of the first tab,
+    /// so that they are restored the next time helvum is started.
+    fn save_view_state(&self) {
+        let imp = imp::Application::from_instance(self);
+        let settings = match imp.settings.get() {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        let mut result = Ok(());
+        if let Some(window) = imp.window.get() {
+            // The default size is the size of the window before it was maximized.
+            let (width, height) = window.default_size();
+            result = result
+                .and_then(|_| settings.set_int("window-width", width))
+                .and_then(|_| settings.set_int("window-height", height))
+                .and_then(|_| settings.set_boolean("window-maximized", window.is_maximized()));
+        }
+        if let Some(tab) = self.tabs().first() {
+            result = result
+                .and_then(|_| {
+                    settings.set_double("scroll-x", tab.scrollwindow.hadjustment().value())
+                })
+                .and_then(|_| {
+                    settings.set_double("scroll-y", tab.scrollwindow.vadjustment().value())
+                });
+        }
+        if let Err(e) = result {
+            warn!("Failed to store window state: {}", e);
+        }
+    }
+
+    /// Scroll the scrolled window to the position stored in the settings.
+    fn restore_scroll_position(&self, scrollwindow: &gtk::ScrolledWindow) {
+        let imp = imp::Application::from_instance(self);
+        let settings = match imp.settings.get() {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        for (adjustment, key) in [
+            (scrollwindow.hadjustment(), "scroll-x"),
+            (scrollwindow.vadjustment(), "scroll-y"),
+        ] {
+            let position = settings.double(key);
+            if position <= 0.0 {
+                continue;
+            }
+
+            // The graph starts out empty, so the position can only be reached once enough nodes were added.
+            let handler: Rc<RefCell<Option<glib::SignalHandlerId>>> = Rc::default();
+            let handler_id =
+                adjustment.connect_changed(clone!(@strong handler => move |adjustment| {
+                    adjustment.set_value(position);
+                    if adjustment.upper() - adjustment.page_size() >= position {
+                        if let Some(handler_id) = handler.borrow_mut().take() {
+                            adjustment.disconnect(handler_id);
+                        }
+                    }
+                }));
+            *handler.borrow_mut() = Some(handler_id);
+        }
+    }
+
+    /// Show a dialog for changing the settings, which take effect immediately.
+    fn show_preferences(&self) {
+        let imp = imp::Application::from_instance(self);
+        let settings = match imp.settings.get() {
+            Some(settings) => settings,
+            None => {
+                self.show_error(&format!(
+                    "Preferences are not available, as the settings schema {} is not installed.",
+                    APP_ID
+                ));
+                return;
+            }
+        };
+
+        let dialog = view::new_dialog(
+            "Preferences",
+            self.active_window().as_ref(),
+            &[("_Close", gtk::ResponseType::Close)],
+        );
+
+        let color_scheme = gtk::ComboBoxText::new();
+        color_scheme.append(Some("default"), "System");
+        color_scheme.append(Some("light"), "Light");
+        color_scheme.append(Some("dark"), "Dark");
+        settings
+            .bind("color-scheme", &color_scheme, "active-id")
+            .build();
+
+        let port_names = gtk::ComboBoxText::new();
+        port_names.append(Some("name"), "Short names");
+        port_names.append(Some("alias"), "Aliases");
+        port_names.append(Some("full"), "Full names");
+        port_names.append(Some("channel"), "Channel positions");
+        settings
+            .bind("port-names", &port_names, "active-id")
+            .build();
+
+        let zoom = gtk::SpinButton::with_range(view::MIN_ZOOM, view::MAX_ZOOM, 0.25);
+        zoom.set_digits(2);
+        settings.bind("zoom", &zoom, "value").build();
+
+        let hide_monitor_ports = gtk::CheckButton::with_mnemonic("Hide _monitor ports");
+        settings
+            .bind("hide-monitor-ports", &hide_monitor_ports, "active")
+            .build();
+        let hide_midi_ports = gtk::CheckButton::with_mnemonic("Hide M_IDI ports");
+        settings
+            .bind("hide-midi-ports", &hide_midi_ports, "active")
+            .build();
+
+        let grid = view::form_grid(&[
+            ("_Color scheme", color_scheme.upcast_ref::<gtk::Widget>()),
+            ("_Port names", port_names.upcast_ref()),
+            ("_Zoom", zoom.upcast_ref()),
+        ]);
+        grid.attach(&hide_monitor_ports, 0, 3, 2, 1);
+        grid.attach(&hide_midi_ports, 0, 4, 2, 1);
+        view::set_dialog_content(&dialog, &grid);
+
+        dialog.connect_response(|dialog, _| dialog.destroy());
+        dialog.show();
+    }
+
+    /// Label the ports in all windows with their name of the specified style.
+    fn set_port_name_style(&self, style: view::PortNameStyle) {
+        for graphview in self.graphviews() {
+            graphview.set_port_name_style(style);
+        }
+    }
+
+    /// Ask the user for a custom label and color for all nodes with the specified label key.
+    fn edit_node_label(&self, label_key: &str, name: &str) {
+        let imp = imp::Application::from_instance(self);
+        let current = imp
+            .node_labels
+            .borrow()
+            .get(label_key)
+            .cloned()
+            .unwrap_or_default();
+
+        let dialog = view::new_dialog(
+            &format!("Label {}", name),
+            self.active_window().as_ref(),
+            &[
+                ("_Reset", gtk::ResponseType::Reject),
+                ("_Cancel", gtk::ResponseType::Cancel),
+                ("_Save", gtk::ResponseType::Accept),
+            ],
+        );
+
+        let entry = gtk::EntryBuilder::new()
+            .placeholder_text(name)
+            .text(current.label.as_deref().unwrap_or_default())
+            .activates_default(true)
+            .hexpand(true)
+            .build();
+        let color_check = gtk::CheckButton::with_mnemonic("Custom _color");
+        let color_button = gtk::ColorButton::new();
+        if let Some(rgba) = current
+            .color
+            .as_deref()
+            .and_then(|color| gtk::gdk::RGBA::parse(color).ok())
+        {
+            color_button.set_rgba(&rgba);
+            color_check.set_active(true);
+        }
+        color_check
+            .bind_property("active", &color_button, "sensitive")
+            .flags(glib::BindingFlags::SYNC_CREATE)
+            .build();
+
+        let grid = view::form_grid(&[("_Label", entry.upcast_ref())]);
+        grid.attach(&color_check, 0, 1, 1, 1);
+        grid.attach(&color_button, 1, 1, 1, 1);
+        view::set_dialog_content(&dialog, &grid);
+
+        let label_key = label_key.to_string();
+        dialog.connect_response(
+            clone!(@weak self as app, @weak entry, @weak color_check, @weak color_button => move |dialog, response| {
+                let label = match response {
+                    gtk::ResponseType::Accept => Some(NodeLabel {
+                        label: Some(entry.text().to_string()).filter(|label| !label.is_empty()),
+                        color: Some(color_button.rgba().to_string()).filter(|_| color_check.is_active()),
+                    }),
+                    gtk::ResponseType::Reject => Some(NodeLabel::default()),
+                    _ => None,
+                };
+                if let Some(label) = label {
+                    app.set_node_label(&label_key, label);
+                }
+                dialog.destroy();
+            }),
+        );
+        dialog.show();
+    }
+
+    /// Store the custom label and color for all nodes with the specified label key and show them in all windows.
+    fn set_node_label(&self, label_key: &str, label: NodeLabel) {
+        let imp = imp::Application::from_instance(self);
+
+        let custom_label = Some(&label).filter(|label| **label != NodeLabel::default());
+        for graphview in self.graphviews() {
+            graphview.set_custom_node_label(label_key, custom_label);
+        }
+
+        let mut node_labels = imp.node_labels.borrow_mut();
+        node_labels.set(label_key, label);
+        if let Err(e) = node_labels.save() {
+            warn!("Failed to save node labels: {}", e);
+            drop(node_labels);
+            self.show_error(&format!("Failed to save node labels: {}", e));
+        }
+    }
+
+    /// Get the style of the names ports are currently labeled with.
+    fn port_name_style(&self) -> view::PortNameStyle {
+        self.lookup_action("port-names")
+            .and_then(|action| action.state())
+            .and_then(|state| state.str().and_then(view::PortNameStyle::from_name))
+            .unwrap_or_default()
+    }
+
+    /// Check whether nodes are currently snapped to the grid when they are moved.
+    fn snap_to_grid(&self) -> bool {
+        self.lookup_action("snap-to-grid")
+            .and_then(|action| action.state())
+            .and_then(|state| state.get::<bool>())
+            .unwrap_or(false)
+    }
+
+    /// Show the command palette for connecting ports of the graph in the current tab by typing their names.
+    fn show_command_palette(&self) {
+        let tab = match self.current_tab() {
+            Some(tab) => tab,
+            None => return,
+        };
+        let graphview = tab.graphview.clone();
+
+        view::show_command_palette(
+            self.active_window().as_ref(),
+            tab.graph.borrow().clone(),
+            clone!(@weak self as app, @weak graphview => move |port_from, port_to| {
+                app.toggle_link(&graphview, port_from, port_to, LinkOptions::default());
+            }),
+        );
+    }
+
+    /// Remove the node with the specified id from the view.
+    fn remove_node(&self, graphview: &view::GraphView, id: u32) {
+        info!("Removing node from graph: id {}", id);
+
+        graphview.remove_node(id);
+    }
+
+    /// Remove the port with the id `id` from the node with the id `node_id`
+    /// from the view.
+    fn remove_port(&self, graphview: &view::GraphView, id: u32, node_id: u32) {
+        info!("Removing port from graph: id {}, node_id: {}", id, node_id);
+
+        graphview.remove_port(id, node_id);
+    }
+
+    /// Remove the link with the specified id from the view.
+    fn remove_link(&self, graphview: &view::GraphView, id: u32) {
+        info!("Removing link from graph: id {}", id);
+
+        graphview.remove_link(id);
+    }
+
+    /// Show a modal dialog informing the user about an error.
+    fn show_error(&self, message: &str) {
+        let dialog = gtk::MessageDialog::new(
+            self.active_window().as_ref(),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Error,
+            gtk::ButtonsType::Close,
+            message,
+        );
+        dialog.connect_response(|dialog, _| dialog.destroy());
+        dialog.show();
+    }
+
+    /// Ask the user for a file and save the graph of the current tab to it.
+    fn export_graph(&self) {
+        let tab = match self.current_tab() {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let dialog = gtk::FileChooserNative::new(
+            Some("Export Graph"),
+            self.active_window().as_ref(),
+            gtk::FileChooserAction::Save,
+            Some("_Export"),
+            Some("_Cancel"),
+        );
+        dialog.set_current_name("graph.json");
+
+        dialog.connect_response(
+            clone!(@weak self as app, @strong dialog, @strong tab => move |_, response| {
+                if response == gtk::ResponseType::Accept {
+                    if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                        let dump = GraphDump::from_graph(&tab.graph.borrow(), |id| {
+                            tab.graphview.node_position(id).map(|(x, y)| Position { x, y })
+                        });
+                        let result = dump
+                            .to_json()
+                            .map_err(|e| e.to_string())
+                            .and_then(|json| std::fs::write(&path, json).map_err(|e| e.to_string()));
+
+                        if let Err(e) = result {
+                            warn!("Failed to export graph to {}: {}", path.display(), e);
+                            app.show_error(&format!("Failed to export graph: {}", e));
+                        }
+                    }
+                }
+                dialog.destroy();
+            }),
+        );
+        dialog.show();
+    }
+
+    /// Ask the user for a file and render the whole graph of the current tab into it as an image.
+    fn export_image(&self) {
+        let tab = match self.current_tab() {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let dialog = gtk::FileChooserNative::new(
+            Some("Export Image"),
+            self.active_window().as_ref(),
+            gtk::FileChooserAction::Save,
+            Some("_Export"),
+            Some("_Cancel"),
+        );
+        dialog.set_current_name("graph.png");
+        dialog.add_choice(
+            "format",
+            "Format",
+            &["png", "svg", "pdf"],
+            &["PNG", "SVG", "PDF"],
+        );
+        dialog.set_choice("format", "png");
+        dialog.add_choice("theme", "Theme", &["dark", "light"], &["Dark", "Light"]);
+        dialog.set_choice("theme", "dark");
+        // A choice without options is shown as a checkbox.
+        dialog.add_choice("transparent", "Transparent background", &[], &[]);
+        dialog.set_choice("transparent", "false");
+
+        dialog.connect_response(
+            clone!(@weak self as app, @strong dialog, @strong tab => move |_, response| {
+                if response == gtk::ResponseType::Accept {
+                    if let Some(mut path) = dialog.file().and_then(|file| file.path()) {
+                        let format = dialog
+                            .choice("format")
+                            .and_then(|format| view::ImageFormat::from_extension(&format))
+                            .unwrap_or(view::ImageFormat::Png);
+                        let light_theme = dialog.choice("theme").map_or(false, |theme| theme == "light");
+                        let transparent = dialog.choice("transparent").map_or(false, |t| t == "true");
+
+                        // Make sure the file extension matches the chosen format.
+                        let has_extension = path
+                            .extension()
+                            .and_then(|extension| extension.to_str())
+                            .and_then(view::ImageFormat::from_extension)
+                            == Some(format);
+                        if !has_extension {
+                            path.set_extension(format.extension());
+                        }
+
+                        if let Err(e) = view::export_image(&tab.graphview, &path, format, transparent, light_theme) {
+                            warn!("Failed to export image to {}: {}", path.display(), e);
+                            app.show_error(&format!("Failed to export image: {}", e));
+                        }
+                    }
+                }
+                dialog.destroy();
+            }),
+        );
+        dialog.show();
+    }
+
+    /// Ask the user for a graph file or `pw-dump` output and show it in a new window.
+    fn import_graph(&self) {
+        let dialog = gtk::FileChooserNative::new(
+            Some("Import Graph"),
+            self.active_window().as_ref(),
+            gtk::FileChooserAction::Open,
+            Some("_Import"),
+            Some("_Cancel"),
+        );
+
+        dialog.connect_response(
+            clone!(@weak self as app, @strong dialog => move |_, response| {
+                if response == gtk::ResponseType::Accept {
+                    if let Some(path) = dialog.file().and_then(|file| file.path()) {
+                        let result = std::fs::read_to_string(&path)
+                            .map_err(|e| e.to_string())
+                            .and_then(|json| GraphDump::from_json(&json));
+
+                        match result {
+                            Ok(dump) => app.show_offline_graph(dump, &path.display().to_string()),
+                            Err(e) => {
+                                warn!("Failed to import graph from {}: {}", path.display(), e);
+                                app.show_error(&format!("Failed to import graph: {}", e));
+                            }
+                        }
+                    }
+                }
+                dialog.destroy();
+            }),
+        );
+        dialog.show();
+    }
+
+    /// Show a graph that is not connected to the pipewire server in a new window.
+    ///
+    /// The graph is read-only, so no links can be created or removed in it.
+    fn show_offline_graph(&self, dump: GraphDump, source: &str) {
+        info!("Showing offline graph from {}", source);
+
+        let imp = imp::Application::from_instance(self);
+        let graphview = view::GraphView::new();
+        graphview.set_port_name_style(self.port_name_style());
+        let mut graph = Graph::new();
+
+        for event in dump.events() {
+            self.update_view(&graphview, &graph, &event, false);
+            graph.apply(&event);
+        }
+
+        for node in &dump.nodes {
+            if let Some(position) = node.position {
+                graphview.set_node_position(node.id, position.x, position.y);
+            }
+        }
+
+        let scrollwindow = gtk::ScrolledWindowBuilder::new().child(&graphview).build();
+        let window = gtk::ApplicationWindowBuilder::new()
+            .application(self)
+            .default_width(1280)
+            .default_height(720)
+            .title(&format!("Offline Graph - {}", source))
+            .child(&overview_overlay(&graphview, &scrollwindow))
+            .build();
+
+        // Keep track of the graphview while its window is open, so that the port name style can be changed
+        // and the nodes of its menus can be looked up.
+        imp.offline_graphs
+            .borrow_mut()
+            .push((graphview.clone(), graph));
+        window.connect_destroy(clone!(@weak self as app, @weak graphview => move |_| {
+            let imp = imp::Application::from_instance(&app);
+            imp.offline_graphs
+                .borrow_mut()
+                .retain(|(other, _)| *other != graphview);
+        }));
+
+        window.show();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    use crate::{
+        model::LinkState,
+        view::testing::{self, with_gtk},
+    };
+
+    /// A player, speakers and headphones.
+    const GRAPH: &str = r#"{
+        "version": 1,
+        "nodes": [
+            { "id": 1, "name": "Player", "node_type": "output", "media_type": "audio",
+              "properties": { "media.class": "Stream/Output/Audio" } },
+            { "id": 2, "name": "Speakers", "node_type": "input", "media_type": "audio",
+              "properties": { "media.class": "Audio/Sink" } },
+            { "id": 3, "name": "Headphones", "node_type": "input", "media_type": "audio",
+              "properties": { "media.class": "Audio/Sink" } }
+        ],
+        "ports": [
+            { "id": 10, "node_id": 1, "name": "output_FL", "direction": "output", "media_type": "audio",
+              "properties": { "audio.channel": "FL" } },
+            { "id": 11, "node_id": 1, "name": "output_FR", "direction": "output", "media_type": "audio",
+              "properties": { "audio.channel": "FR" } },
+            { "id": 20, "node_id": 2, "name": "playback_FL", "direction": "input", "media_type": "audio",
+              "properties": { "audio.channel": "FL" } },
+            { "id": 21, "node_id": 2, "name": "playback_FR", "direction": "input", "media_type": "audio",
+              "properties": { "audio.channel": "FR" } },
+            { "id": 30, "node_id": 3, "name": "playback_FL", "direction": "input", "media_type": "audio",
+              "properties": { "audio.channel": "FL" } },
+            { "id": 31, "node_id": 3, "name": "playback_FR", "direction": "input", "media_type": "audio",
+              "properties": { "audio.channel": "FR" } }
+        ],
+        "links": []
+    }"#;
+
+    /// Create an application showing the simulated graph stored in the file,
+    /// once it has handled the messages its backend sent while starting.
+    pub(crate) fn open_fake_graph(path: &Path) -> Application {
+        let app = Application::new(vec![Source::Fake(path.to_owned())], None);
+        handle_messages();
+        app
+    }
+
+    /// Get the graphview of the tab shown in the main window.
+    pub(crate) fn current_graphview(app: &Application) -> view::GraphView {
+        app.current_tab()
+            .expect("No tab is shown")
+            .graphview
+            .clone()
+    }
+
+    /// Let the application handle all messages its backends have sent.
+    fn handle_messages() {
+        let context = glib::MainContext::default();
+        while context.iteration(false) {}
+    }
+
+    /// Run the test with an application showing the test graph, if GTK is available.
+    fn run(test: fn(&Application, &RemoteTab)) {
+        with_gtk(move || {
+            let path = testing::temp_file(Some(GRAPH));
+            let app = open_fake_graph(&path);
+            let _ = std::fs::remove_file(&path);
+
+            let tab = app.current_tab().expect("No tab is shown");
+            test(&app, &tab);
+            app.close_tab(&tab);
+        });
+    }
+
+    /// Check that the view of the tab shows the ports and links of its graph.
+    fn check_view(tab: &RemoteTab) {
+        let graph = tab.graph.borrow();
+        let mut port_ids: Vec<u32> = graph.ports().map(|port| port.id).collect();
+        port_ids.sort_unstable();
+        assert_eq!(testing::port_ids(&tab.graphview), port_ids);
+        assert_eq!(testing::links(&tab.graphview), testing::graph_links(&graph));
+    }
+
+    #[test]
+    fn graph_is_shown_once_loaded() {
+        run(|_, tab| {
+            assert!(tab.synced.get());
+            assert_eq!(tab.graph.borrow().nodes().count(), 3);
+            assert_eq!(testing::port_ids(&tab.graphview), [10, 11, 20, 21, 30, 31]);
+            check_view(tab);
+        });
+    }
+
+    #[test]
+    fn toggled_links_are_shown() {
+        run(|_, tab| {
+            tab.toggle_link(10, 20).unwrap();
+            handle_messages();
+            assert_eq!(
+                testing::links(&tab.graphview),
+                [(10, 20, LinkState::Active)]
+            );
+            check_view(tab);
+
+            tab.toggle_link(10, 20).unwrap();
+            handle_messages();
+            assert!(testing::links(&tab.graphview).is_empty());
+            check_view(tab);
+        });
+    }
+
+    #[test]
+    fn destroying_a_linked_node_removes_its_ports_and_links() {
+        run(|_, tab| {
+            tab.toggle_link(10, 20).unwrap();
+            tab.toggle_link(11, 21).unwrap();
+            tab.toggle_link(11, 31).unwrap();
+            handle_messages();
+            assert_eq!(testing::links(&tab.graphview).len(), 3);
+
+            // The backend only announces the removal of the node itself.
+            assert!(tab.send(GtkMessage::DestroyNode { node_id: 1 }));
+            handle_messages();
+
+            let graph = tab.graph.borrow();
+            assert!(graph.node(1).is_none());
+            assert!(graph.port(10).is_none());
+            assert_eq!(graph.links().count(), 0);
+            assert!(testing::links(&tab.graphview).is_empty());
+            assert_eq!(testing::port_ids(&tab.graphview), [20, 21, 30, 31]);
+        });
+    }
+
+    #[test]
+    fn node_menu_acts_on_the_current_node() {
+        run(|app, tab| {
+            tab.toggle_link(10, 20).unwrap();
+            tab.toggle_link(11, 21).unwrap();
+            handle_messages();
+
+            app.node_menu_action(&tab.graphview, 2, "disconnect");
+            handle_messages();
+            assert!(testing::links(&tab.graphview).is_empty());
+
+            // Menus of nodes that are gone do nothing.
+            assert!(tab.send(GtkMessage::DestroyNode { node_id: 2 }));
+            handle_messages();
+            app.node_menu_action(&tab.graphview, 2, "connect-default");
+            handle_messages();
+            assert!(testing::links(&tab.graphview).is_empty());
+            check_view(tab);
+        });
+    }
+
+    #[test]
+    fn presets_restore_their_links() {
+        run(|app, tab| {
+            tab.toggle_link(10, 20).unwrap();
+            tab.toggle_link(11, 21).unwrap();
+            handle_messages();
+            app.save_preset(tab, "Speakers").unwrap();
+
+            tab.toggle_link(10, 20).unwrap();
+            tab.toggle_link(11, 31).unwrap();
+            handle_messages();
+            app.apply_preset(tab, "Speakers", false).unwrap();
+            handle_messages();
+            assert_eq!(
+                testing::links(&tab.graphview),
+                [
+                    (10, 20, LinkState::Active),
+                    (11, 21, LinkState::Active),
+                    (11, 31, LinkState::Active),
+                ]
+            );
+
+            app.apply_preset(tab, "Speakers", true).unwrap();
+            handle_messages();
+            assert_eq!(
+                testing::links(&tab.graphview),
+                [(10, 20, LinkState::Active), (11, 21, LinkState::Active)]
+            );
+            check_view(tab);
+        });
     }
 }
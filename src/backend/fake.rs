@@ -0,0 +1,763 @@
+// fake.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! A simulated graph, for trying out the application without a pipewire server.
+//!
+//! Links are negotiated like pipewire would do it: They appear in the `Init` state and go through negotiation
+//! until they are running, or fail if their ports can not be linked.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    path::Path,
+};
+
+use gtk::glib;
+use log::{info, warn};
+use pipewire::spa::Direction;
+
+use super::GraphBackend;
+use crate::{
+    dump::GraphDump,
+    model::{self, Graph, GraphEvent, LinkState},
+    pipewire_connection,
+    virtual_nodes::{self, VirtualNode, VirtualNodeKind, VIRTUAL_NODE_PROPERTY},
+    GtkMessage, LinkOptions, MediaType, NodeType, PipewireMessage,
+};
+
+/// Property of a port that makes all links to or from it fail, with its value as the error message.
+const LINK_ERROR_PROPERTY: &str = "helvum.fake.link-error";
+
+/// A backend simulating a graph in the GTK thread.
+///
+/// All events caused by a request are sent while the request is handled.
+pub struct FakeBackend {
+    sender: glib::Sender<PipewireMessage>,
+    graph: RefCell<Graph>,
+    /// Id of the next created object. Like in pipewire, ids are not reused.
+    next_id: Cell<u32>,
+}
+
+impl FakeBackend {
+    /// Create a backend simulating the graph stored in the file, which may also contain the output of `pw-dump`.
+    ///
+    /// If the file can not be read, the error is reported like a failed connection and the graph stays empty.
+    pub fn load(sender: glib::Sender<PipewireMessage>, path: &Path) -> Self {
+        let backend = Self {
+            sender,
+            graph: RefCell::new(Graph::new()),
+            next_id: Cell::new(0),
+        };
+
+        let result = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| GraphDump::from_json(&json));
+        match result {
+            Ok(dump) => {
+                let ids = (dump.devices.iter().map(|device| device.id))
+                    .chain(dump.nodes.iter().map(|node| node.id))
+                    .chain(dump.ports.iter().map(|port| port.id))
+                    .chain(dump.links.iter().map(|link| link.id));
+                backend
+                    .next_id
+                    .set(ids.max().map_or(0, |id| id.saturating_add(1)));
+
+                for event in dump.events() {
+                    backend.emit(event);
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to load simulated graph from {}: {}",
+                    path.display(),
+                    e
+                );
+                let _ = backend.sender.send(PipewireMessage::ConnectionFailed(e));
+            }
+        }
+
+        backend
+    }
+
+    /// Apply the event to the simulated graph and report it.
+    fn emit(&self, event: GraphEvent) {
+        self.graph.borrow_mut().apply(&event);
+        // The receiver is only gone if the application is shutting down.
+        let _ = self.sender.send(PipewireMessage::GraphEvent(event));
+    }
+
+    /// Get the id for a new object.
+    fn new_id(&self) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+
+    /// Toggle a link between the two specified ports.
+    ///
+    /// If the link does not exist yet, it is created with the specified options.
+    fn toggle_link(&self, port_from: u32, port_to: u32, options: LinkOptions) {
+        let existing = self
+            .graph
+            .borrow()
+            .link_between(port_from, port_to)
+            .map(|link| link.id);
+        if let Some(id) = existing {
+            info!("Removing simulated link with id {}", id);
+            self.emit(GraphEvent::LinkRemoved { id });
+        } else {
+            self.create_link(port_from, port_to, options);
+        }
+    }
+
+    /// Create a link between the two specified ports, which fails like a real link would
+    /// if the ports do not fit together.
+    fn create_link(&self, port_from: u32, port_to: u32, options: LinkOptions) {
+        let ports = {
+            let graph = self.graph.borrow();
+            (graph.port(port_from).cloned(), graph.port(port_to).cloned())
+        };
+        let (output, input) = match ports {
+            (Some(output), Some(input)) => (output, input),
+            _ => {
+                warn!(
+                    "Can not link port id:{} to port id:{}, as they are not in the graph",
+                    port_from, port_to
+                );
+                return;
+            }
+        };
+
+        let id = self.new_id();
+        info!(
+            "Creating simulated link with id {} from port id:{} to port id:{} with {:?}",
+            id, port_from, port_to, options
+        );
+
+        let mut properties = HashMap::new();
+        properties.insert("link.passive".to_string(), options.passive.to_string());
+        properties.insert("object.linger".to_string(), options.linger.to_string());
+        self.emit(GraphEvent::LinkAdded(model::Link {
+            id,
+            node_from: output.node_id,
+            port_from,
+            node_to: input.node_id,
+            port_to,
+            state: LinkState::Init,
+            format: None,
+            properties,
+        }));
+        self.emit(GraphEvent::LinkStateChanged {
+            id,
+            state: LinkState::Negotiating,
+        });
+
+        let error = if let Some(message) = [&output, &input]
+            .iter()
+            .find_map(|port| port.properties.get(LINK_ERROR_PROPERTY))
+        {
+            Some(message.clone())
+        } else if output.direction != Direction::Output || input.direction != Direction::Input {
+            Some("Ports have the wrong direction".to_string())
+        } else if output.media_type != input.media_type {
+            Some("No common format".to_string())
+        } else {
+            None
+        };
+
+        match error {
+            Some(message) => self.emit(GraphEvent::LinkStateChanged {
+                id,
+                state: LinkState::Error(message),
+            }),
+            None => {
+                if let Some(MediaType::Audio) = output.media_type {
+                    self.emit(GraphEvent::LinkFormatChanged {
+                        id,
+                        format: Some(model::LinkFormat {
+                            sample_format: Some("F32LE".to_string()),
+                            rate: Some(48000),
+                            channels: Some(1),
+                        }),
+                    });
+                }
+                self.emit(GraphEvent::LinkStateChanged {
+                    id,
+                    state: LinkState::Allocating,
+                });
+                // Passive links do not cause their nodes to be scheduled, so they stay paused.
+                self.emit(GraphEvent::LinkStateChanged {
+                    id,
+                    state: if options.passive {
+                        LinkState::Paused
+                    } else {
+                        LinkState::Active
+                    },
+                });
+            }
+        }
+    }
+
+    /// Remove all links from or to the specified node.
+    fn disconnect_node(&self, node_id: u32) {
+        let links = self.graph.borrow().links_of_node(node_id);
+        for id in links {
+            info!("Removing simulated link with id {}", id);
+            self.emit(GraphEvent::LinkRemoved { id });
+        }
+    }
+
+    /// Link the ports of the node to the default sink or from the default source, keeping existing links.
+    fn connect_node_to_default(&self, node_id: u32, options: LinkOptions) {
+        let pairs = {
+            let graph = self.graph.borrow();
+            let default = match graph.default_node_for(node_id) {
+                Ok(default) => default.id,
+                Err(e) => {
+                    warn!("Can not connect node {} to a default node: {}", node_id, e);
+                    return;
+                }
+            };
+            let pairs = match graph.node(node_id).and_then(|node| node.node_type) {
+                Some(NodeType::Input) => graph.channel_pairs(default, node_id),
+                _ => graph.channel_pairs(node_id, default),
+            };
+            pairs
+                .into_iter()
+                .filter(|(port_from, port_to)| graph.link_between(*port_from, *port_to).is_none())
+                .collect::<Vec<_>>()
+        };
+
+        for (port_from, port_to) in pairs {
+            self.create_link(port_from, port_to, options);
+        }
+    }
+
+    /// Destroy the node.
+    ///
+    /// Only the removal of the node is announced, which removes its ports and links along with it,
+    /// like for a node removed from a pipewire server.
+    fn destroy_node(&self, node_id: u32) {
+        if self.graph.borrow().node(node_id).is_none() {
+            warn!(
+                "Can not destroy node {}, as it is not in the graph",
+                node_id
+            );
+            return;
+        }
+
+        info!("Destroying simulated node with id {}", node_id);
+        let removals = self.graph.borrow().removal_events(node_id);
+        for event in &removals {
+            self.graph.borrow_mut().apply(event);
+        }
+        // The receiver is only gone if the application is shutting down.
+        let _ = self
+            .sender
+            .send(PipewireMessage::GraphEvent(GraphEvent::NodeRemoved {
+                id: node_id,
+            }));
+    }
+
+    /// Create the nodes and ports the null sink or loopback would have,
+    /// unless a virtual node with the same name already exists.
+    fn create_virtual_node(&self, node: VirtualNode) {
+        if virtual_nodes::nodes_of(&self.graph.borrow(), &node.name)
+            .next()
+            .is_some()
+        {
+            info!("Virtual node {} already exists", node.name);
+            return;
+        }
+
+        info!("Creating simulated {} {}", node.kind.name(), node.name);
+        let properties = |name: String, media_class: &str| {
+            let mut properties = HashMap::new();
+            properties.insert("node.name".to_string(), name);
+            properties.insert(
+                "node.description".to_string(),
+                node.description().to_string(),
+            );
+            properties.insert("media.class".to_string(), media_class.to_string());
+            properties.insert(VIRTUAL_NODE_PROPERTY.to_string(), node.name.clone());
+            properties
+        };
+
+        let sink = self.add_node(properties(node.name.clone(), "Audio/Sink"));
+        self.add_ports(sink, &node.positions, Direction::Input, "playback", false);
+        match node.kind {
+            VirtualNodeKind::NullSink => {
+                self.add_ports(sink, &node.positions, Direction::Output, "monitor", true)
+            }
+            VirtualNodeKind::Loopback => {
+                let output = self.add_node(properties(
+                    format!("{}.output", node.name),
+                    "Stream/Output/Audio",
+                ));
+                self.add_ports(output, &node.positions, Direction::Output, "output", false);
+            }
+        }
+    }
+
+    /// Add a simulated node with the specified properties, returning its id.
+    fn add_node(&self, properties: HashMap<String, String>) -> u32 {
+        let id = self.new_id();
+        self.emit(GraphEvent::NodeAdded(model::Node {
+            id,
+            name: pipewire_connection::node_name(&properties),
+            node_type: pipewire_connection::node_type(&properties),
+            media_type: pipewire_connection::node_media_type(&properties),
+            device_id: None,
+            properties,
+            permissions: model::Permissions::default(),
+        }));
+        id
+    }
+
+    /// Add an audio port for each of the channel positions to the node, named `<prefix>_<position>`.
+    fn add_ports(
+        &self,
+        node_id: u32,
+        positions: &[String],
+        direction: Direction,
+        prefix: &str,
+        monitor: bool,
+    ) {
+        for (index, position) in positions.iter().enumerate() {
+            let id = self.new_id();
+            let name = format!("{}_{}", prefix, position);
+            let mut properties = HashMap::new();
+            properties.insert("port.name".to_string(), name.clone());
+            properties.insert("port.id".to_string(), index.to_string());
+            properties.insert("node.id".to_string(), node_id.to_string());
+            properties.insert("audio.channel".to_string(), position.clone());
+            if monitor {
+                properties.insert("port.monitor".to_string(), "true".to_string());
+            }
+            self.emit(GraphEvent::PortAdded(model::Port {
+                id,
+                node_id,
+                name,
+                direction,
+                media_type: Some(MediaType::Audio),
+                properties,
+            }));
+        }
+    }
+
+    /// Destroy all nodes of the virtual node with the specified name.
+    fn remove_virtual_node(&self, name: &str) {
+        let ids: Vec<u32> = virtual_nodes::nodes_of(&self.graph.borrow(), name)
+            .map(|node| node.id)
+            .collect();
+        for id in ids {
+            self.destroy_node(id);
+        }
+    }
+}
+
+impl GraphBackend for FakeBackend {
+    fn send(&self, message: GtkMessage) -> bool {
+        match message {
+            GtkMessage::ToggleLink {
+                port_from,
+                port_to,
+                options,
+            } => self.toggle_link(port_from, port_to, options),
+            GtkMessage::DisconnectNode { node_id } => self.disconnect_node(node_id),
+            GtkMessage::ConnectNodeToDefault { node_id, options } => {
+                self.connect_node_to_default(node_id, options)
+            }
+            // Simulated nodes do not process anything, so there is nothing to suspend.
+            GtkMessage::SuspendNode { node_id } => {
+                info!("Suspending simulated node with id {}", node_id)
+            }
+            GtkMessage::DestroyNode { node_id } => self.destroy_node(node_id),
+            GtkMessage::CreateVirtualNode(node) => self.create_virtual_node(node),
+            GtkMessage::RemoveVirtualNode { name } => self.remove_virtual_node(&name),
+            // Events caused by earlier requests have already been sent.
+            GtkMessage::Sync => {
+                let _ = self.sender.send(PipewireMessage::Synced);
+            }
+            GtkMessage::Terminate => {}
+        }
+
+        true
+    }
+
+    fn stop(&self) {
+        // Nothing is running besides the GTK thread.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::rc::Rc;
+
+    use crate::view::testing;
+
+    /// A player, speakers, a camera and a sink whose links always fail.
+    const GRAPH: &str = r#"{
+        "version": 1,
+        "nodes": [
+            { "id": 1, "name": "Player", "node_type": "output", "media_type": "audio",
+              "properties": { "media.class": "Stream/Output/Audio" } },
+            { "id": 2, "name": "Speakers", "node_type": "input", "media_type": "audio",
+              "properties": { "media.class": "Audio/Sink", "priority.session": "1000" } },
+            { "id": 3, "name": "Camera", "node_type": "output", "media_type": "video",
+              "properties": { "media.class": "Video/Source" } },
+            { "id": 4, "name": "Broken", "node_type": "input", "media_type": "audio",
+              "properties": { "media.class": "Audio/Sink" } }
+        ],
+        "ports": [
+            { "id": 10, "node_id": 1, "name": "output_FL", "direction": "output", "media_type": "audio",
+              "properties": { "audio.channel": "FL" } },
+            { "id": 11, "node_id": 1, "name": "output_FR", "direction": "output", "media_type": "audio",
+              "properties": { "audio.channel": "FR" } },
+            { "id": 20, "node_id": 2, "name": "playback_FL", "direction": "input", "media_type": "audio",
+              "properties": { "audio.channel": "FL" } },
+            { "id": 21, "node_id": 2, "name": "playback_FR", "direction": "input", "media_type": "audio",
+              "properties": { "audio.channel": "FR" } },
+            { "id": 30, "node_id": 3, "name": "capture", "direction": "output", "media_type": "video" },
+            { "id": 40, "node_id": 4, "name": "playback_MONO", "direction": "input", "media_type": "audio",
+              "properties": { "audio.channel": "MONO", "helvum.fake.link-error": "Device is gone" } }
+        ],
+        "links": []
+    }"#;
+
+    /// The first id given to objects created by the backend.
+    const FIRST_ID: u32 = 41;
+
+    /// A fake backend along with the graph built from the messages it sent.
+    struct Harness {
+        backend: FakeBackend,
+        context: glib::MainContext,
+        messages: Rc<RefCell<Vec<PipewireMessage>>>,
+        graph: RefCell<Graph>,
+    }
+
+    impl Harness {
+        fn new(path: &Path) -> Self {
+            let context = glib::MainContext::new();
+            let _guard = context.acquire().expect("Failed to acquire main context");
+
+            let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+            let messages = Rc::new(RefCell::new(Vec::new()));
+            receiver.attach(
+                Some(&context),
+                glib::clone!(@strong messages => move |message| {
+                    messages.borrow_mut().push(message);
+                    glib::Continue(true)
+                }),
+            );
+
+            Self {
+                backend: FakeBackend::load(sender, path),
+                context: context.clone(),
+                messages,
+                graph: RefCell::new(Graph::new()),
+            }
+        }
+
+        /// Load the test graph, taking the events that add it.
+        fn with_graph() -> Self {
+            let path = testing::temp_file(Some(GRAPH));
+            let harness = Self::new(&path);
+            let _ = std::fs::remove_file(&path);
+
+            let loaded = harness.take_messages();
+            assert_eq!(loaded.len(), 4 + 6);
+            assert_eq!(harness.graph.borrow().nodes().count(), 4);
+            harness.check_state();
+
+            harness
+        }
+
+        /// Send a request to the backend and take the messages it sent in response.
+        fn send(&self, message: GtkMessage) -> Vec<String> {
+            assert!(self.backend.send(message));
+            self.take_messages()
+        }
+
+        /// Take all messages sent by the backend, applying the events to the graph.
+        /// The messages are returned as descriptions that are easy to compare.
+        fn take_messages(&self) -> Vec<String> {
+            {
+                let _guard = self
+                    .context
+                    .acquire()
+                    .expect("Failed to acquire main context");
+                while self.context.iteration(false) {}
+            }
+
+            let messages: Vec<PipewireMessage> = self.messages.borrow_mut().drain(..).collect();
+            for message in &messages {
+                if let PipewireMessage::GraphEvent(event) = message {
+                    self.graph.borrow_mut().apply(event);
+                }
+            }
+
+            messages.iter().map(describe).collect()
+        }
+
+        /// Check that the messages described all links of the backend.
+        fn check_state(&self) {
+            assert_eq!(
+                testing::graph_links(&self.graph.borrow()),
+                testing::graph_links(&self.backend.graph.borrow())
+            );
+        }
+
+        fn link_state(&self, port_from: u32, port_to: u32) -> Option<LinkState> {
+            self.graph
+                .borrow()
+                .link_between(port_from, port_to)
+                .map(|link| link.state.clone())
+        }
+    }
+
+    fn describe(message: &PipewireMessage) -> String {
+        match message {
+            PipewireMessage::GraphEvent(GraphEvent::LinkAdded(link)) => format!(
+                "link {} added from {} to {}",
+                link.id, link.port_from, link.port_to
+            ),
+            PipewireMessage::GraphEvent(GraphEvent::LinkStateChanged { id, state }) => {
+                format!("link {} {}", id, state)
+            }
+            PipewireMessage::GraphEvent(GraphEvent::LinkFormatChanged { id, format }) => {
+                match format {
+                    Some(format) => format!("link {} format {}", id, format),
+                    None => format!("link {} format unknown", id),
+                }
+            }
+            PipewireMessage::GraphEvent(GraphEvent::LinkRemoved { id }) => {
+                format!("link {} removed", id)
+            }
+            PipewireMessage::GraphEvent(event) => format!("{:?}", event),
+            PipewireMessage::Synced => "synced".to_string(),
+            PipewireMessage::ConnectionFailed(e) => format!("connection failed: {}", e),
+        }
+    }
+
+    /// Get the messages of a successfully created audio link.
+    fn created_link(id: u32, port_from: u32, port_to: u32, state: &str) -> Vec<String> {
+        vec![
+            format!("link {} added from {} to {}", id, port_from, port_to),
+            format!("link {} negotiating", id),
+            format!("link {} format F32LE, 48000 Hz, 1 channel", id),
+            format!("link {} allocating", id),
+            format!("link {} {}", id, state),
+        ]
+    }
+
+    fn toggle_link(port_from: u32, port_to: u32) -> GtkMessage {
+        GtkMessage::ToggleLink {
+            port_from,
+            port_to,
+            options: LinkOptions::default(),
+        }
+    }
+
+    #[test]
+    fn toggle_link_creates_and_removes_link() {
+        let harness = Harness::with_graph();
+        assert_eq!(
+            harness.send(toggle_link(10, 20)),
+            created_link(FIRST_ID, 10, 20, "active")
+        );
+        assert_eq!(harness.link_state(10, 20), Some(LinkState::Active));
+        let graph = harness.graph.borrow();
+        let link = graph.link(FIRST_ID).unwrap();
+        assert!(!link.passive());
+        assert!(link.linger());
+        drop(graph);
+        harness.check_state();
+
+        assert_eq!(
+            harness.send(toggle_link(10, 20)),
+            [format!("link {} removed", FIRST_ID)]
+        );
+        assert_eq!(harness.link_state(10, 20), None);
+        harness.check_state();
+    }
+
+    #[test]
+    fn passive_links_stay_paused() {
+        let harness = Harness::with_graph();
+        let messages = harness.send(GtkMessage::ToggleLink {
+            port_from: 11,
+            port_to: 21,
+            options: LinkOptions {
+                passive: true,
+                linger: false,
+            },
+        });
+
+        assert_eq!(messages, created_link(FIRST_ID, 11, 21, "paused"));
+        let graph = harness.graph.borrow();
+        let link = graph.link(FIRST_ID).unwrap();
+        assert_eq!(link.state, LinkState::Paused);
+        assert!(link.passive());
+        assert!(!link.linger());
+        drop(graph);
+        harness.check_state();
+    }
+
+    #[test]
+    fn link_error_property_makes_links_fail() {
+        let harness = Harness::with_graph();
+        assert_eq!(
+            harness.send(toggle_link(10, 40)),
+            [
+                format!("link {} added from 10 to 40", FIRST_ID),
+                format!("link {} negotiating", FIRST_ID),
+                format!("link {} error: Device is gone", FIRST_ID),
+            ]
+        );
+        assert_eq!(
+            harness.link_state(10, 40),
+            Some(LinkState::Error("Device is gone".to_string()))
+        );
+        harness.check_state();
+
+        // Failed links still exist until they are removed.
+        assert_eq!(
+            harness.send(toggle_link(10, 40)),
+            [format!("link {} removed", FIRST_ID)]
+        );
+        harness.check_state();
+    }
+
+    #[test]
+    fn links_between_unfitting_ports_fail() {
+        let harness = Harness::with_graph();
+        let messages = harness.send(toggle_link(30, 20));
+        assert_eq!(
+            messages.last().map(String::as_str),
+            Some("link 41 error: No common format")
+        );
+
+        let messages = harness.send(toggle_link(20, 11));
+        assert_eq!(
+            messages.last().map(String::as_str),
+            Some("link 42 error: Ports have the wrong direction")
+        );
+
+        // Ports that do not exist can not be linked at all.
+        assert!(harness.send(toggle_link(10, 99)).is_empty());
+        harness.check_state();
+    }
+
+    #[test]
+    fn disconnect_node_removes_all_its_links() {
+        let harness = Harness::with_graph();
+        harness.send(toggle_link(10, 20));
+        harness.send(toggle_link(11, 21));
+        harness.send(toggle_link(11, 40));
+
+        assert_eq!(
+            harness.send(GtkMessage::DisconnectNode { node_id: 2 }),
+            [
+                format!("link {} removed", FIRST_ID),
+                format!("link {} removed", FIRST_ID + 1),
+            ]
+        );
+        assert!(harness.graph.borrow().links_of_node(2).is_empty());
+        assert_eq!(harness.graph.borrow().links_of_node(1), [FIRST_ID + 2]);
+        harness.check_state();
+
+        // Nodes without links are left alone.
+        assert!(harness
+            .send(GtkMessage::DisconnectNode { node_id: 2 })
+            .is_empty());
+    }
+
+    #[test]
+    fn connect_node_to_default_keeps_existing_links() {
+        let harness = Harness::with_graph();
+        harness.send(toggle_link(10, 20));
+
+        // The speakers have a higher priority than the broken sink, so they are the default.
+        assert_eq!(
+            harness.send(GtkMessage::ConnectNodeToDefault {
+                node_id: 1,
+                options: LinkOptions::default(),
+            }),
+            created_link(FIRST_ID + 1, 11, 21, "active")
+        );
+        assert_eq!(harness.link_state(10, 20), Some(LinkState::Active));
+        assert_eq!(harness.link_state(11, 21), Some(LinkState::Active));
+        harness.check_state();
+
+        // Nothing happens once all links exist, or if there is no default node.
+        assert!(harness
+            .send(GtkMessage::ConnectNodeToDefault {
+                node_id: 1,
+                options: LinkOptions::default(),
+            })
+            .is_empty());
+        assert!(harness
+            .send(GtkMessage::ConnectNodeToDefault {
+                node_id: 3,
+                options: LinkOptions::default(),
+            })
+            .is_empty());
+        harness.check_state();
+    }
+
+    #[test]
+    fn sync_follows_events_of_earlier_requests() {
+        let harness = Harness::with_graph();
+        assert_eq!(harness.send(GtkMessage::Sync), ["synced"]);
+
+        assert!(harness.backend.send(toggle_link(10, 20)));
+        let mut expected = created_link(FIRST_ID, 10, 20, "active");
+        expected.push("synced".to_string());
+        assert_eq!(harness.send(GtkMessage::Sync), expected);
+        harness.check_state();
+    }
+
+    #[test]
+    fn destroy_node_only_announces_the_node() {
+        let harness = Harness::with_graph();
+        harness.send(toggle_link(10, 20));
+
+        assert_eq!(
+            harness.send(GtkMessage::DestroyNode { node_id: 2 }),
+            ["NodeRemoved { id: 2 }"]
+        );
+        // Its ports and links are gone from the simulated graph nonetheless.
+        let graph = harness.backend.graph.borrow();
+        assert!(graph.port(20).is_none());
+        assert_eq!(graph.links().count(), 0);
+    }
+
+    #[test]
+    fn missing_file_fails_like_a_connection() {
+        let harness = Harness::new(Path::new("/nonexistent/helvum-graph.json"));
+
+        let messages = harness.take_messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("connection failed: "));
+        assert_eq!(harness.graph.borrow().nodes().count(), 0);
+    }
+}
@@ -0,0 +1,100 @@
+// mod.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Sources of the graphs shown by the application.
+//!
+//! A backend runs independently of the GTK main loop. It reports changes of its graph with
+//! [`PipewireMessage`]s and handles the requests of the application sent as [`GtkMessage`]s,
+//! so the application does not need to know whether a real pipewire server is behind it.
+
+mod fake;
+mod replay;
+
+use std::path::PathBuf;
+
+use gtk::glib;
+use log::warn;
+
+use crate::{pipewire_connection, GtkMessage, PipewireMessage};
+
+/// Where a graph comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// The pipewire remote with the specified name or socket path, or the default remote if `None`.
+    Remote(Option<String>),
+    /// A simulated graph, initially containing the graph stored in the specified file.
+    Fake(PathBuf),
+    /// A recording of the events received from a remote, replayed at `speed` times its original speed.
+    Replay { path: PathBuf, speed: f64 },
+}
+
+impl Source {
+    /// Get the name of the source, for showing it to the user.
+    pub fn display_name(&self) -> String {
+        match self {
+            Self::Remote(remote) => pipewire_connection::remote_display_name(remote.as_deref()),
+            Self::Fake(path) => format!("{} (simulated)", path.display()),
+            Self::Replay { path, .. } => format!("{} (replay)", path.display()),
+        }
+    }
+}
+
+/// A running source of a graph.
+pub trait GraphBackend {
+    /// Send a request to the backend, returning `false` if it is no longer running.
+    fn send(&self, message: GtkMessage) -> bool;
+
+    /// Stop the backend and wait until it has finished, if it is still running.
+    ///
+    /// Messages sent by the backend before it stopped may still be waiting in the receiver.
+    fn stop(&self);
+}
+
+/// Start a backend for the source.
+///
+/// If `record` is set, the events received from a remote are recorded into the file at that path.
+///
+/// Returns the receiver of the messages sent by the backend,
+/// which must be attached to the main context of the calling thread.
+pub fn start(
+    source: &Source,
+    record: Option<PathBuf>,
+) -> (glib::Receiver<PipewireMessage>, Box<dyn GraphBackend>) {
+    if record.is_some() && !matches!(source, Source::Remote(_)) {
+        warn!(
+            "Only remotes can be recorded, not recording {}",
+            source.display_name()
+        );
+    }
+
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let backend: Box<dyn GraphBackend> = match source {
+        Source::Remote(remote) => Box::new(pipewire_connection::PipewireBackend::spawn(
+            sender,
+            remote.clone(),
+            record,
+        )),
+        Source::Fake(path) => Box::new(fake::FakeBackend::load(sender, path)),
+        Source::Replay { path, speed } => {
+            Box::new(replay::ReplayBackend::load(sender, path, *speed))
+        }
+    };
+
+    (receiver, backend)
+}
@@ -0,0 +1,184 @@
+// replay.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Replaying a recording of the events received from a remote, see [`crate::recording`].
+//!
+//! The recorded events are handled just like the pipewire thread handles events arriving from the server,
+//! but in the GTK thread and with the timing of the recording, scaled by the replay speed.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    path::Path,
+    rc::Rc,
+    time::Duration,
+};
+
+use gtk::glib;
+use log::{info, warn};
+
+use super::GraphBackend;
+use crate::{
+    model::Graph,
+    pipewire_connection,
+    recording::{self, RecordedEvent},
+    GtkMessage, PipewireMessage,
+};
+
+/// A backend replaying a recording.
+///
+/// The graph of a recording can not be changed, so requests to change it are ignored.
+pub struct ReplayBackend {
+    state: Rc<ReplayState>,
+}
+
+struct ReplayState {
+    sender: glib::Sender<PipewireMessage>,
+    graph: RefCell<Graph>,
+    /// The events that have not been replayed yet.
+    events: RefCell<VecDeque<RecordedEvent>>,
+    /// Factor the time between events is divided by.
+    speed: f64,
+    /// Timeout replaying the next event, while there is one.
+    timeout: Cell<Option<glib::SourceId>>,
+    /// Number of `GtkMessage::Sync` requests waiting for the replay to finish.
+    pending_syncs: Cell<usize>,
+}
+
+impl ReplayBackend {
+    /// Start replaying the recording in the file at `speed` times its original speed.
+    ///
+    /// If the file can not be read, the error is reported like a failed connection.
+    pub fn load(sender: glib::Sender<PipewireMessage>, path: &Path, speed: f64) -> Self {
+        let events = match recording::load(path) {
+            Ok(events) => {
+                info!(
+                    "Replaying {} events from {} at {}x speed",
+                    events.len(),
+                    path.display(),
+                    speed
+                );
+                events
+            }
+            Err(e) => {
+                warn!("Failed to load recording from {}: {}", path.display(), e);
+                let _ = sender.send(PipewireMessage::ConnectionFailed(e));
+                Vec::new()
+            }
+        };
+
+        let state = Rc::new(ReplayState {
+            sender,
+            graph: RefCell::new(Graph::new()),
+            events: RefCell::new(events.into()),
+            speed,
+            timeout: Cell::new(None),
+            pending_syncs: Cell::new(0),
+        });
+        schedule_next(&state, 0.0);
+
+        Self { state }
+    }
+}
+
+/// Wait until the next event is due, `previous` being the time of the last replayed event.
+fn schedule_next(state: &Rc<ReplayState>, previous: f64) {
+    let next = match state.events.borrow().front() {
+        Some(event) => event.time,
+        None => {
+            info!("Replay finished");
+            for _ in 0..state.pending_syncs.replace(0) {
+                let _ = state.sender.send(PipewireMessage::Synced);
+            }
+            return;
+        }
+    };
+
+    let delay = Duration::from_secs_f64(((next - previous) / state.speed).max(0.0));
+    let weak_state = Rc::downgrade(state);
+    let timeout = glib::timeout_add_local_once(delay, move || {
+        if let Some(state) = weak_state.upgrade() {
+            // The timeout is removed once it ran.
+            state.timeout.set(None);
+            replay_next(&state);
+        }
+    });
+    state.timeout.set(Some(timeout));
+}
+
+/// Replay all events recorded at the time of the next event, then wait for the events after them.
+fn replay_next(state: &Rc<ReplayState>) {
+    let time = match state.events.borrow().front() {
+        Some(event) => event.time,
+        None => return,
+    };
+
+    loop {
+        let event = {
+            let mut events = state.events.borrow_mut();
+            match events.front() {
+                Some(event) if event.time <= time => events.pop_front(),
+                _ => break,
+            }
+        };
+        if let Some(event) = event {
+            pipewire_connection::apply_registry_event(&event.event, &state.graph, &state.sender);
+        }
+    }
+
+    schedule_next(state, time);
+}
+
+impl GraphBackend for ReplayBackend {
+    fn send(&self, message: GtkMessage) -> bool {
+        match message {
+            GtkMessage::ToggleLink { .. }
+            | GtkMessage::DisconnectNode { .. }
+            | GtkMessage::ConnectNodeToDefault { .. } => {
+                warn!("Links can not be changed in a replayed recording");
+            }
+            GtkMessage::SuspendNode { .. }
+            | GtkMessage::DestroyNode { .. }
+            | GtkMessage::CreateVirtualNode(_)
+            | GtkMessage::RemoveVirtualNode { .. } => {
+                warn!("Nodes can not be changed in a replayed recording");
+            }
+            // The recording is only in sync with its remote once all of it has been replayed.
+            GtkMessage::Sync => {
+                if self.state.events.borrow().is_empty() {
+                    let _ = self.state.sender.send(PipewireMessage::Synced);
+                } else {
+                    self.state
+                        .pending_syncs
+                        .set(self.state.pending_syncs.get() + 1);
+                }
+            }
+            GtkMessage::Terminate => {}
+        }
+
+        true
+    }
+
+    fn stop(&self) {
+        if let Some(timeout) = self.state.timeout.take() {
+            glib::source_remove(timeout);
+        }
+        self.state.events.borrow_mut().clear();
+    }
+}
@@ -0,0 +1,398 @@
+// cli.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Command line interface for scripting link operations.
+//!
+//! The subcommands use the same backends and messages as the graphical application,
+//! but receive the messages in a plain glib main loop, so GTK is never initialized.
+
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
+};
+
+use glib::{clone, Continue};
+use pipewire::spa::Direction;
+
+use crate::{
+    backend::{self, GraphBackend, Source},
+    dump::GraphDump,
+    model::{self, Graph},
+    presets::Preset,
+    GtkMessage, LinkOptions, MediaType, PipewireMessage,
+};
+
+const USAGE: &str = "Usage:
+  helvum                              Start the graphical patchbay
+  helvum list                         List all ports and links
+  helvum link <output> <input>        Link all output ports matching <output> to input ports matching <input>
+  helvum unlink <output> <input>      Remove all links between ports matching <output> and <input>
+  helvum apply-preset <file>          Create all links listed in <file>
+  helvum dump [--json]                Print all nodes, ports and links including their ids,
+                                      optionally in the format described in docs/graph_format.md
+  helvum help                         Show this help
+
+Options:
+  --remote <name>                     Connect to the pipewire remote with the specified name or socket path
+                                      instead of the default one.
+                                      The patchbay accepts this option several times and shows each remote in a tab
+  --fake <file>                       Simulate the graph stored in <file> instead of connecting to a remote
+  --record <file>                     Record all events received from the remote into <file>
+  --replay <file>                     Replay the events recorded in <file> instead of connecting to a remote
+  --replay-speed <factor>             Replay recordings <factor> times faster than they were recorded
+
+Ports are addressed by their full name \"<node name>:<port name>\".
+Patterns may contain the wildcards '*' (any number of characters) and '?' (exactly one character).
+
+A preset file contains one link per line in the form \"<output> -> <input>\",
+empty lines and lines starting with '#' are ignored.";
+
+/// How long to wait for the pipewire server before giving up.
+///
+/// Simulated and replayed graphs are not waited for, as they do not depend on a server.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A subcommand given on the command line.
+#[derive(Debug, Clone)]
+pub(super) enum Command {
+    List,
+    Link { output: String, input: String },
+    Unlink { output: String, input: String },
+    ApplyPreset { path: PathBuf },
+    Dump { json: bool },
+    Help,
+}
+
+impl Command {
+    /// Parse the command line arguments, not including the program name.
+    ///
+    /// Returns `Ok(None)` if no subcommand was given and the graphical application should be started.
+    pub fn parse(args: &[String]) -> Result<Option<Self>, Box<dyn Error>> {
+        let (subcommand, args) = match args.split_first() {
+            // Options are left for GTK to handle.
+            Some((first, rest)) if !first.starts_with('-') => (first.as_str(), rest),
+            _ => return Ok(None),
+        };
+
+        let command = match (subcommand, args) {
+            ("list", []) => Self::List,
+            ("link", [output, input]) => Self::Link {
+                output: output.clone(),
+                input: input.clone(),
+            },
+            ("unlink", [output, input]) => Self::Unlink {
+                output: output.clone(),
+                input: input.clone(),
+            },
+            ("apply-preset", [path]) => Self::ApplyPreset {
+                path: PathBuf::from(path),
+            },
+            ("dump", []) => Self::Dump { json: false },
+            ("dump", [flag]) if flag == "--json" => Self::Dump { json: true },
+            ("help", _) => Self::Help,
+            _ => return Err(format!("Invalid arguments\n\n{}", USAGE).into()),
+        };
+
+        Ok(Some(command))
+    }
+}
+
+fn media_type_name(media_type: Option<MediaType>) -> &'static str {
+    match media_type {
+        Some(MediaType::Audio) => "audio",
+        Some(MediaType::Video) => "video",
+        Some(MediaType::Midi) => "midi",
+        None => "unknown",
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Input => "in",
+        Direction::Output => "out",
+    }
+}
+
+fn print_list(graph: &Graph) {
+    let mut ports: Vec<(String, Direction)> = graph
+        .ports()
+        .filter_map(|port| Some((graph.port_full_name(port.id)?, port.direction)))
+        .collect();
+    ports.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    println!("Ports:");
+    for (name, direction) in ports {
+        println!("  {:<3} {}", direction_name(direction), name);
+    }
+
+    let mut links: Vec<(String, String)> = graph
+        .links()
+        .map(|link| {
+            (
+                graph.port_full_name(link.port_from).unwrap_or_default(),
+                graph.port_full_name(link.port_to).unwrap_or_default(),
+            )
+        })
+        .collect();
+    links.sort();
+
+    println!("Links:");
+    for (from, to) in links {
+        println!("  {} -> {}", from, to);
+    }
+}
+
+fn print_dump(graph: &Graph) {
+    let mut devices: Vec<&model::Device> = graph.devices().collect();
+    devices.sort_by_key(|device| device.id);
+    for device in devices {
+        println!("device {} \"{}\"", device.id, device.name);
+    }
+
+    let mut nodes: Vec<&model::Node> = graph.nodes().collect();
+    nodes.sort_by_key(|node| node.id);
+    for node in nodes {
+        match node.device_id {
+            Some(device_id) => println!("node {} \"{}\" device {}", node.id, node.name, device_id),
+            None => println!("node {} \"{}\"", node.id, node.name),
+        }
+
+        for port in graph.ports_of_node(node.id) {
+            println!(
+                "  port {} {} {} \"{}\"",
+                port.id,
+                direction_name(port.direction),
+                media_type_name(port.media_type),
+                port.name
+            );
+        }
+    }
+
+    let mut links: Vec<&model::Link> = graph.links().collect();
+    links.sort_by_key(|link| link.id);
+    for link in links {
+        println!(
+            "link {} {} -> {} {}",
+            link.id, link.port_from, link.port_to, link.state
+        );
+    }
+}
+
+/// The result of a command that did not fail entirely.
+#[derive(Debug, Default)]
+struct Outcome {
+    /// Whether requests were sent to the pipewire thread that still need to be processed.
+    pending: bool,
+    /// Errors of a partially failed command, which should only be reported once the requests have been processed.
+    errors: Vec<String>,
+}
+
+/// Execute the command on the fully enumerated graph.
+fn execute(
+    command: &Command,
+    graph: &Graph,
+    backend: &dyn GraphBackend,
+) -> Result<Outcome, Box<dyn Error>> {
+    let toggle = |port_from: u32, port_to: u32| {
+        if backend.send(GtkMessage::ToggleLink {
+            port_from,
+            port_to,
+            options: LinkOptions::default(),
+        }) {
+            Ok(())
+        } else {
+            Err("Failed to send message to the pipewire thread")
+        }
+    };
+
+    match command {
+        Command::List => {
+            print_list(graph);
+            Ok(Outcome::default())
+        }
+        Command::Dump { json: false } => {
+            print_dump(graph);
+            Ok(Outcome::default())
+        }
+        Command::Dump { json: true } => {
+            println!("{}", GraphDump::from_graph(graph, |_| None).to_json()?);
+            Ok(Outcome::default())
+        }
+        Command::Link { output, input } => {
+            let mut requested = false;
+            for (port_from, port_to) in graph.resolve_pairs(output, input)? {
+                if graph.link_between(port_from, port_to).is_none() {
+                    toggle(port_from, port_to)?;
+                    requested = true;
+                }
+            }
+            Ok(Outcome {
+                pending: requested,
+                errors: Vec::new(),
+            })
+        }
+        Command::Unlink { output, input } => {
+            let mut requested = false;
+            for link in graph.links_matching(output, input) {
+                toggle(link.port_from, link.port_to)?;
+                requested = true;
+            }
+            if !requested {
+                return Err(format!("No links between \"{}\" and \"{}\"", output, input).into());
+            }
+            Ok(Outcome {
+                pending: requested,
+                errors: Vec::new(),
+            })
+        }
+        Command::ApplyPreset { path } => {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+            // The links that could be resolved are still created, the errors are reported afterwards.
+            let (pairs, errors) = Preset::parse(&content)?.resolve(graph);
+            let mut requested = false;
+            for (port_from, port_to) in pairs {
+                if graph.link_between(port_from, port_to).is_none() {
+                    toggle(port_from, port_to)?;
+                    requested = true;
+                }
+            }
+            Ok(Outcome {
+                pending: requested,
+                errors,
+            })
+        }
+        Command::Help => Ok(Outcome::default()),
+    }
+}
+
+/// Print the errors of the preset entries that could not be applied and make the command fail.
+fn report_errors(result: &RefCell<Result<(), String>>, errors: &[String]) {
+    if errors.is_empty() {
+        return;
+    }
+
+    for e in errors {
+        eprintln!("{}", e);
+    }
+    *result.borrow_mut() = Err(format!(
+        "{} preset entries could not be applied",
+        errors.len()
+    ));
+}
+
+/// Run the subcommand to completion.
+///
+/// The subcommand operates on the graph of the specified source.
+/// If `record` is set, the events received from the remote are recorded into the file at that path.
+pub(super) fn run(
+    command: Command,
+    source: Source,
+    record: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    if let Command::Help = command {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let ctx = glib::MainContext::default();
+    let _guard = ctx.acquire().unwrap();
+    let mainloop = glib::MainLoop::new(Some(&ctx), false);
+
+    let (gtk_receiver, backend) = backend::start(&source, record);
+    let backend: Rc<dyn GraphBackend> = Rc::from(backend);
+
+    let graph = Rc::new(RefCell::new(Graph::new()));
+    let result: Rc<RefCell<Result<(), String>>> = Rc::new(RefCell::new(Ok(())));
+    let syncs_received = Rc::new(Cell::new(0));
+    let pending_errors: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(Vec::new()));
+
+    // The first roundtrip makes sure all globals have been announced,
+    // the second one that the info of all links bound in the meantime has arrived.
+    // After the command has been executed, a last roundtrip makes sure the server has processed its requests.
+    // If the thread failed to connect to the server, it reports the error with a message instead.
+    backend.send(GtkMessage::Sync);
+
+    gtk_receiver.attach(
+        None,
+        clone!(@strong mainloop, @strong graph, @strong result, @strong backend => move |msg| {
+            match msg {
+                PipewireMessage::GraphEvent(event) => graph.borrow_mut().apply(&event),
+                PipewireMessage::ConnectionFailed(e) => {
+                    *result.borrow_mut() = Err(format!("Failed to connect to the pipewire server: {}", e));
+                    mainloop.quit();
+                }
+                PipewireMessage::Synced => {
+                    syncs_received.set(syncs_received.get() + 1);
+                    match syncs_received.get() {
+                        1 => {
+                            if !backend.send(GtkMessage::Sync) {
+                                mainloop.quit();
+                            }
+                        }
+                        2 => match execute(&command, &graph.borrow(), backend.as_ref()) {
+                            Ok(Outcome { pending: true, errors }) => {
+                                *pending_errors.borrow_mut() = errors;
+                                if !backend.send(GtkMessage::Sync) {
+                                    report_errors(&result, &pending_errors.borrow());
+                                    mainloop.quit();
+                                }
+                            }
+                            Ok(Outcome { pending: false, errors }) => {
+                                report_errors(&result, &errors);
+                                mainloop.quit();
+                            }
+                            Err(e) => {
+                                *result.borrow_mut() = Err(e.to_string());
+                                mainloop.quit();
+                            }
+                        },
+                        _ => {
+                            report_errors(&result, &pending_errors.borrow());
+                            mainloop.quit();
+                        }
+                    }
+                }
+            }
+
+            Continue(true)
+        }),
+    );
+
+    if let Source::Remote(_) = source {
+        glib::timeout_add_local_once(
+            TIMEOUT,
+            clone!(@strong mainloop, @strong result => move || {
+                *result.borrow_mut() = Err("Timed out waiting for the pipewire server".to_string());
+                mainloop.quit();
+            }),
+        );
+    }
+
+    mainloop.run();
+
+    backend.stop();
+
+    let result = result.borrow().clone();
+    result.map_err(Into::into)
+}
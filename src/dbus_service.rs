@@ -0,0 +1,204 @@
+// dbus_service.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! The D-Bus interface for controlling helvum from other applications.
+//!
+//! The interface is exported on the object path of the application on the session bus,
+//! so it is available as soon as helvum is running. The methods are handled by the application,
+//! which operates on the graph of its first tab.
+
+use std::{
+    ffi::CString,
+    os::raw::{c_char, c_uint},
+    ptr,
+};
+
+use gtk::{
+    gio::{self, ffi as gio_ffi},
+    glib::{self, ffi as glib_ffi, translate::*},
+};
+use log::warn;
+
+/// Name of the interface.
+pub const INTERFACE_NAME: &str = "org.freedesktop.ryuukyu.Helvum.Patchbay";
+
+/// Name of the D-Bus error returned by failed method calls.
+const ERROR_NAME: &str = "org.freedesktop.ryuukyu.Helvum.Error.Failed";
+
+const INTERFACE_XML: &str = r#"
+<node>
+  <interface name="org.freedesktop.ryuukyu.Helvum.Patchbay">
+    <method name="ListNodes">
+      <arg name="nodes" type="a(us)" direction="out"/>
+    </method>
+    <method name="ListLinks">
+      <arg name="links" type="a(uss)" direction="out"/>
+    </method>
+    <method name="Link">
+      <arg name="output" type="s" direction="in"/>
+      <arg name="input" type="s" direction="in"/>
+    </method>
+    <method name="Unlink">
+      <arg name="output" type="s" direction="in"/>
+      <arg name="input" type="s" direction="in"/>
+    </method>
+    <method name="ApplyPreset">
+      <arg name="name" type="s" direction="in"/>
+    </method>
+    <method name="SavePreset">
+      <arg name="name" type="s" direction="in"/>
+    </method>
+    <signal name="NodeAdded">
+      <arg name="id" type="u"/>
+      <arg name="name" type="s"/>
+    </signal>
+    <signal name="NodeRemoved">
+      <arg name="id" type="u"/>
+    </signal>
+    <signal name="LinkAdded">
+      <arg name="id" type="u"/>
+      <arg name="output" type="s"/>
+      <arg name="input" type="s"/>
+    </signal>
+    <signal name="LinkRemoved">
+      <arg name="id" type="u"/>
+    </signal>
+  </interface>
+</node>
+"#;
+
+/// Handles a method call with the name of the method and its parameters,
+/// returning the return value of the method or an error message.
+type MethodHandler = Box<dyn Fn(&str, &glib::Variant) -> Result<Option<glib::Variant>, String>>;
+
+/// Our interface, exported on a D-Bus connection until it is unregistered.
+pub struct DBusService {
+    connection: gio::DBusConnection,
+    object_path: String,
+    registration_id: c_uint,
+}
+
+impl DBusService {
+    /// Export the interface on the object path, with method calls being handled by `handler`.
+    ///
+    /// The handler is called in the main context of the calling thread.
+    pub fn register(
+        connection: &gio::DBusConnection,
+        object_path: &str,
+        handler: impl Fn(&str, &glib::Variant) -> Result<Option<glib::Variant>, String> + 'static,
+    ) -> Result<Self, glib::Error> {
+        let node_info = gio::DBusNodeInfo::for_xml(INTERFACE_XML)?;
+        let interface_info = node_info
+            .lookup_interface(INTERFACE_NAME)
+            .expect("Interface missing from its description");
+
+        // gio 0.14 has no binding for registering objects, so the C function is called directly.
+        let handler: Box<MethodHandler> = Box::new(Box::new(handler));
+        let vtable = gio_ffi::GDBusInterfaceVTable {
+            method_call: Some(method_call_trampoline),
+            get_property: None,
+            set_property: None,
+            padding: [ptr::null_mut(); 8],
+        };
+        let registration_id = unsafe {
+            let mut error = ptr::null_mut();
+            // The vtable is copied, while the handler is owned by the registration until it is dropped by `free_handler`.
+            let registration_id = gio_ffi::g_dbus_connection_register_object(
+                connection.to_glib_none().0,
+                object_path.to_glib_none().0,
+                interface_info.to_glib_none().0,
+                &vtable,
+                Box::into_raw(handler) as glib_ffi::gpointer,
+                Some(free_handler),
+                &mut error,
+            );
+            if !error.is_null() {
+                return Err(from_glib_full(error));
+            }
+            registration_id
+        };
+
+        Ok(Self {
+            connection: connection.clone(),
+            object_path: object_path.to_string(),
+            registration_id,
+        })
+    }
+
+    /// Emit a signal of the interface to all listeners.
+    pub fn emit_signal(&self, name: &str, parameters: &glib::Variant) {
+        if let Err(e) = self.connection.emit_signal(
+            None,
+            &self.object_path,
+            INTERFACE_NAME,
+            name,
+            Some(parameters),
+        ) {
+            warn!("Failed to emit D-Bus signal {}: {}", name, e);
+        }
+    }
+
+    /// Stop exporting the interface.
+    pub fn unregister(self) {
+        unsafe {
+            gio_ffi::g_dbus_connection_unregister_object(
+                self.connection.to_glib_none().0,
+                self.registration_id,
+            );
+        }
+    }
+}
+
+unsafe extern "C" fn method_call_trampoline(
+    _connection: *mut gio_ffi::GDBusConnection,
+    _sender: *const c_char,
+    _object_path: *const c_char,
+    _interface_name: *const c_char,
+    method_name: *const c_char,
+    parameters: *mut glib_ffi::GVariant,
+    invocation: *mut gio_ffi::GDBusMethodInvocation,
+    user_data: glib_ffi::gpointer,
+) {
+    let handler = &*(user_data as *const MethodHandler);
+    let method_name: glib::GString = from_glib_none(method_name);
+    let parameters: glib::Variant = from_glib_none(parameters);
+
+    // Both functions take over the reference to the invocation we were given.
+    match handler(&method_name, &parameters) {
+        Ok(value) => gio_ffi::g_dbus_method_invocation_return_value(
+            invocation,
+            value.as_ref().map_or(ptr::null_mut(), |value| {
+                value.to_glib_none().0 as *mut glib_ffi::GVariant
+            }),
+        ),
+        Err(message) => {
+            let error_name = CString::new(ERROR_NAME).unwrap();
+            let message = CString::new(message.replace('\0', "")).unwrap();
+            gio_ffi::g_dbus_method_invocation_return_dbus_error(
+                invocation,
+                error_name.as_ptr(),
+                message.as_ptr(),
+            );
+        }
+    }
+}
+
+unsafe extern "C" fn free_handler(user_data: glib_ffi::gpointer) {
+    drop(Box::from_raw(user_data as *mut MethodHandler));
+}
@@ -0,0 +1,417 @@
+// dump.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Serialization of the graph to JSON and import of `pw-dump` output.
+//!
+//! The format written by helvum is documented in `docs/graph_format.md`.
+
+use std::collections::HashMap;
+
+use pipewire::spa::Direction;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    model::{self, Graph, GraphEvent, LinkFormat, LinkState},
+    pipewire_connection, MediaType, NodeType,
+};
+
+/// The version of the format written by [`GraphDump::to_json`].
+pub const FORMAT_VERSION: u32 = 1;
+
+/// A complete graph, as written to and read from a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphDump {
+    pub version: u32,
+    #[serde(default)]
+    pub devices: Vec<DeviceDump>,
+    pub nodes: Vec<NodeDump>,
+    pub ports: Vec<PortDump>,
+    pub links: Vec<LinkDump>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceDump {
+    pub id: u32,
+    pub name: String,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeDump {
+    pub id: u32,
+    pub name: String,
+    pub node_type: Option<NodeType>,
+    #[serde(default)]
+    pub media_type: Option<MediaType>,
+    #[serde(default)]
+    pub device_id: Option<u32>,
+    /// Position of the node in the graph view, if it is known.
+    #[serde(default)]
+    pub position: Option<Position>,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortDump {
+    pub id: u32,
+    pub node_id: u32,
+    pub name: String,
+    #[serde(with = "direction")]
+    pub direction: Direction,
+    pub media_type: Option<MediaType>,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkDump {
+    pub id: u32,
+    pub node_from: u32,
+    pub port_from: u32,
+    pub node_to: u32,
+    pub port_to: u32,
+    pub active: bool,
+    /// Name of the state of the link, like `"active"` or `"error"`.
+    #[serde(default)]
+    pub state: Option<String>,
+    /// Message of the error, if the link is in the error state.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// The format negotiated for the link, if negotiation has finished.
+    #[serde(default)]
+    pub format: Option<LinkFormat>,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+impl Default for GraphDump {
+    fn default() -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            devices: Vec::new(),
+            nodes: Vec::new(),
+            ports: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+}
+
+impl GraphDump {
+    /// Describe the graph, using `position_of` to find the position of each node in the graph view.
+    pub fn from_graph(graph: &Graph, position_of: impl Fn(u32) -> Option<Position>) -> Self {
+        let mut dump = Self {
+            devices: graph
+                .devices()
+                .map(|device| DeviceDump {
+                    id: device.id,
+                    name: device.name.clone(),
+                    properties: device.properties.clone(),
+                })
+                .collect(),
+            nodes: graph
+                .nodes()
+                .map(|node| NodeDump {
+                    id: node.id,
+                    name: node.name.clone(),
+                    node_type: node.node_type,
+                    media_type: node.media_type,
+                    device_id: node.device_id,
+                    position: position_of(node.id),
+                    properties: node.properties.clone(),
+                })
+                .collect(),
+            ports: graph
+                .ports()
+                .map(|port| PortDump {
+                    id: port.id,
+                    node_id: port.node_id,
+                    name: port.name.clone(),
+                    direction: port.direction,
+                    media_type: port.media_type,
+                    properties: port.properties.clone(),
+                })
+                .collect(),
+            links: graph
+                .links()
+                .map(|link| LinkDump {
+                    id: link.id,
+                    node_from: link.node_from,
+                    port_from: link.port_from,
+                    node_to: link.node_to,
+                    port_to: link.port_to,
+                    active: link.state.is_active(),
+                    state: Some(link.state.name().to_string()),
+                    error: match &link.state {
+                        LinkState::Error(message) => Some(message.clone()),
+                        _ => None,
+                    },
+                    format: link.format.clone(),
+                    properties: link.properties.clone(),
+                })
+                .collect(),
+            ..Self::default()
+        };
+
+        // Sort everything by id, so that dumps of the same graph can easily be compared.
+        dump.devices.sort_by_key(|device| device.id);
+        dump.nodes.sort_by_key(|node| node.id);
+        dump.ports.sort_by_key(|port| port.id);
+        dump.links.sort_by_key(|link| link.id);
+
+        dump
+    }
+
+    /// Get the events that build up the described graph when applied to an empty graph.
+    pub fn events(&self) -> Vec<GraphEvent> {
+        let devices = self.devices.iter().map(|device| {
+            GraphEvent::DeviceAdded(model::Device {
+                id: device.id,
+                name: device.name.clone(),
+                properties: device.properties.clone(),
+            })
+        });
+        let nodes = self.nodes.iter().map(|node| {
+            GraphEvent::NodeAdded(model::Node {
+                id: node.id,
+                name: node.name.clone(),
+                node_type: node.node_type,
+                media_type: node.media_type,
+                device_id: node.device_id,
+                properties: node.properties.clone(),
+                permissions: model::Permissions::default(),
+            })
+        });
+        let ports = self.ports.iter().map(|port| {
+            GraphEvent::PortAdded(model::Port {
+                id: port.id,
+                node_id: port.node_id,
+                name: port.name.clone(),
+                direction: port.direction,
+                media_type: port.media_type,
+                properties: port.properties.clone(),
+            })
+        });
+        let links = self.links.iter().map(|link| {
+            GraphEvent::LinkAdded(model::Link {
+                id: link.id,
+                node_from: link.node_from,
+                port_from: link.port_from,
+                node_to: link.node_to,
+                port_to: link.port_to,
+                // Older files only tell whether the link was active.
+                state: link
+                    .state
+                    .as_deref()
+                    .and_then(|state| LinkState::from_name(state, link.error.as_deref()))
+                    .unwrap_or(if link.active {
+                        LinkState::Active
+                    } else {
+                        LinkState::Paused
+                    }),
+                format: link.format.clone(),
+                properties: link.properties.clone(),
+            })
+        });
+
+        devices.chain(nodes).chain(ports).chain(links).collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Read a graph from either a file written by helvum or from the output of `pw-dump`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+
+        // pw-dump prints a list of objects, while our own format is a single object.
+        if value.is_array() {
+            Ok(Self::from_pw_dump(&value))
+        } else {
+            let dump: Self = serde_json::from_value(value).map_err(|e| e.to_string())?;
+            if dump.version > FORMAT_VERSION {
+                return Err(format!(
+                    "Unsupported format version {} (newest supported version is {})",
+                    dump.version, FORMAT_VERSION
+                ));
+            }
+            Ok(dump)
+        }
+    }
+
+    /// Convert the output of `pw-dump` into a graph.
+    ///
+    /// Objects that are not nodes, ports or links are ignored.
+    fn from_pw_dump(objects: &Value) -> Self {
+        let objects = objects.as_array().map(Vec::as_slice).unwrap_or_default();
+        let mut dump = Self::default();
+
+        let objects_of_type = |type_: &'static str| {
+            objects
+                .iter()
+                .filter(move |object| object["type"].as_str() == Some(type_))
+        };
+
+        for object in objects_of_type("PipeWire:Interface:Device") {
+            let id = match object["id"].as_u64() {
+                Some(id) => id as u32,
+                None => continue,
+            };
+            let properties = pw_dump_props(&object["info"]["props"]);
+
+            dump.devices.push(DeviceDump {
+                id,
+                name: pipewire_connection::device_name(&properties),
+                properties,
+            });
+        }
+
+        let mut node_media_types = HashMap::new();
+        for object in objects_of_type("PipeWire:Interface:Node") {
+            let id = match object["id"].as_u64() {
+                Some(id) => id as u32,
+                None => continue,
+            };
+            let properties = pw_dump_props(&object["info"]["props"]);
+            let media_type = pipewire_connection::node_media_type(&properties);
+
+            node_media_types.insert(id, media_type);
+            dump.nodes.push(NodeDump {
+                id,
+                name: pipewire_connection::node_name(&properties),
+                node_type: pipewire_connection::node_type(&properties),
+                media_type,
+                device_id: properties.get("device.id").and_then(|id| id.parse().ok()),
+                position: None,
+                properties,
+            });
+        }
+
+        for object in objects_of_type("PipeWire:Interface:Port") {
+            let id = match object["id"].as_u64() {
+                Some(id) => id as u32,
+                None => continue,
+            };
+            let properties = pw_dump_props(&object["info"]["props"]);
+            let node_id = match properties.get("node.id").and_then(|id| id.parse().ok()) {
+                Some(node_id) => node_id,
+                None => continue,
+            };
+            let direction = if object["info"]["direction"].as_str() == Some("input") {
+                Direction::Input
+            } else {
+                Direction::Output
+            };
+
+            dump.ports.push(PortDump {
+                id,
+                node_id,
+                name: properties.get("port.name").cloned().unwrap_or_default(),
+                direction,
+                media_type: node_media_types.get(&node_id).copied().flatten(),
+                properties,
+            });
+        }
+
+        for object in objects_of_type("PipeWire:Interface:Link") {
+            let info = &object["info"];
+            let get_id = |key: &str| info[key].as_u64().map(|id| id as u32);
+
+            if let (Some(id), Some(node_from), Some(port_from), Some(node_to), Some(port_to)) = (
+                object["id"].as_u64().map(|id| id as u32),
+                get_id("output-node-id"),
+                get_id("output-port-id"),
+                get_id("input-node-id"),
+                get_id("input-port-id"),
+            ) {
+                dump.links.push(LinkDump {
+                    id,
+                    node_from,
+                    port_from,
+                    node_to,
+                    port_to,
+                    active: info["state"].as_str() == Some("active"),
+                    state: info["state"].as_str().map(str::to_string),
+                    error: info["error"].as_str().map(str::to_string),
+                    format: None,
+                    properties: pw_dump_props(&info["props"]),
+                });
+            }
+        }
+
+        dump
+    }
+}
+
+/// Convert the properties of a `pw-dump` object into a string map.
+///
+/// `pw-dump` prints numbers and booleans as JSON values, while pipewire stores all properties as strings.
+fn pw_dump_props(props: &Value) -> HashMap<String, String> {
+    props
+        .as_object()
+        .map(|props| {
+            props
+                .iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// (De)serialize a port direction as `"input"` or `"output"`, like `pw-dump` does.
+mod direction {
+    use pipewire::spa::Direction;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        direction: &Direction,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match direction {
+            Direction::Input => "input",
+            Direction::Output => "output",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Direction, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "input" => Ok(Direction::Input),
+            "output" => Ok(Direction::Output),
+            other => Err(D::Error::custom(format!(
+                "invalid port direction \"{}\"",
+                other
+            ))),
+        }
+    }
+}
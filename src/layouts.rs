@@ -0,0 +1,89 @@
+// layouts.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Positions of nodes in the graph of each remote, stored in a local file so that they are kept across restarts.
+//!
+//! Nodes are identified by the same key as for their custom labels, see [`NodeLabels::key`](crate::node_labels::NodeLabels::key).
+
+use std::{collections::HashMap, error::Error, path::PathBuf};
+
+use gtk::glib;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::dump::Position;
+
+/// The node positions of all remotes, keyed by the display name of the remote.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Layouts {
+    remotes: HashMap<String, HashMap<String, Position>>,
+}
+
+impl Layouts {
+    /// Get the path of the file the layouts are stored in.
+    fn path() -> PathBuf {
+        glib::user_config_dir().join("helvum").join("layouts.json")
+    }
+
+    /// Load the stored layouts, or start without any layouts if there are none or they can not be read.
+    pub fn load() -> Self {
+        let path = Self::path();
+        if !path.exists() {
+            return Self::default();
+        }
+
+        std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("Failed to load layouts from {}: {}", path.display(), e);
+                Self::default()
+            })
+    }
+
+    /// Store the layouts, so that they are used again the next time helvum is started.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Get the stored position of the node with the specified key in the graph of the remote.
+    pub fn position(&self, remote: &str, node_key: &str) -> Option<Position> {
+        self.remotes.get(remote)?.get(node_key).copied()
+    }
+
+    /// Remember the positions of nodes in the graph of the remote.
+    ///
+    /// Positions of nodes that are not part of `positions` are kept, as those nodes may just not be running right now.
+    pub fn set_positions(
+        &mut self,
+        remote: &str,
+        positions: impl IntoIterator<Item = (String, Position)>,
+    ) {
+        self.remotes
+            .entry(remote.to_string())
+            .or_default()
+            .extend(positions);
+    }
+}
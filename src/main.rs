@@ -18,18 +18,53 @@
 // SPDX-License-Identifier: GPL-3.0-only
 
 mod application;
+mod backend;
+mod cli;
+mod dbus_service;
+mod dump;
+mod layouts;
+mod model;
+mod node_labels;
 mod pipewire_connection;
+mod presets;
+mod recording;
+mod theme;
 mod view;
+mod virtual_nodes;
+
+use std::path::PathBuf;
 
-use glib::PRIORITY_DEFAULT;
 use gtk::prelude::*;
-use pipewire::spa::Direction;
+use serde::{Deserialize, Serialize};
 
 /// Messages sent by the GTK thread to notify the pipewire thread.
 #[derive(Debug, Clone)]
 enum GtkMessage {
     /// Toggle a link between the two specified ports.
-    ToggleLink { port_from: u32, port_to: u32 },
+    ///
+    /// If the link is created, it is created with the specified options.
+    ToggleLink {
+        port_from: u32,
+        port_to: u32,
+        options: LinkOptions,
+    },
+    /// Remove all links from or to the specified node.
+    DisconnectNode { node_id: u32 },
+    /// Link the ports of the specified node to the default sink if it is an output node,
+    /// or from the default source if it is an input node.
+    ///
+    /// Missing links are created with the specified options, existing links are kept.
+    ConnectNodeToDefault { node_id: u32, options: LinkOptions },
+    /// Suspend the specified node, so that it releases its device until it is used again.
+    SuspendNode { node_id: u32 },
+    /// Destroy the specified node along with its ports and links.
+    DestroyNode { node_id: u32 },
+    /// Create the virtual node, unless a virtual node with the same name already exists.
+    CreateVirtualNode(virtual_nodes::VirtualNode),
+    /// Remove all nodes of the virtual node with the specified name.
+    RemoveVirtualNode { name: String },
+    /// Perform a roundtrip with the server and answer with `PipewireMessage::Synced` once done.
+    Sync,
     /// Quit the event loop and let the thread finish.
     Terminate,
 }
@@ -37,55 +72,47 @@ enum GtkMessage {
 /// Messages sent by the pipewire thread to notify the GTK thread.
 #[derive(Debug, Clone)]
 enum PipewireMessage {
-    NodeAdded {
-        id: u32,
-        name: String,
-        node_type: Option<NodeType>,
-    },
-    PortAdded {
-        id: u32,
-        node_id: u32,
-        name: String,
-        direction: Direction,
-        media_type: Option<MediaType>,
-    },
-    LinkAdded {
-        id: u32,
-        node_from: u32,
-        port_from: u32,
-        node_to: u32,
-        port_to: u32,
-        active: bool,
-    },
-    LinkStateChanged {
-        id: u32,
-        active: bool,
-    },
-    NodeRemoved {
-        id: u32,
-    },
-    PortRemoved {
-        id: u32,
-        node_id: u32,
-    },
-    LinkRemoved {
-        id: u32,
-    },
+    /// The graph on the remote changed.
+    GraphEvent(model::GraphEvent),
+    /// Answer to a `GtkMessage::Sync`: All messages caused by earlier requests have been sent.
+    Synced,
+    /// The remote could not be connected to, so the thread has exited.
+    ConnectionFailed(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum NodeType {
     Input,
     Output,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MediaType {
     Audio,
     Video,
     Midi,
 }
 
+/// Properties a new link is created with.
+#[derive(Debug, Clone, Copy)]
+pub struct LinkOptions {
+    /// Passive links do not cause the nodes they connect to be scheduled.
+    pub passive: bool,
+    /// Lingering links are kept after helvum exits.
+    pub linger: bool,
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        Self {
+            passive: false,
+            linger: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PipewireLink {
     pub node_from: u32,
@@ -108,27 +135,126 @@ fn init_glib_logger() {
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     init_glib_logger();
+
+    let mut args: Vec<String> = std::env::args().collect();
+    let program = args.remove(0);
+    let SourceOptions {
+        mut sources,
+        record,
+    } = take_source_options(&mut args)?;
+
+    // Subcommands are handled before GTK is initialized, so that they also work without a display.
+    if let Some(command) = cli::Command::parse(&args)? {
+        if sources.len() > 1 {
+            return Err("Subcommands can only operate on a single remote".into());
+        }
+        return cli::run(
+            command,
+            sources.pop().unwrap_or(backend::Source::Remote(None)),
+            record,
+        );
+    }
+
     gtk::init()?;
 
     // Aquire main context so that we can attach the gtk channel later.
     let ctx = glib::MainContext::default();
     let _guard = ctx.acquire().unwrap();
 
-    // Start the pipewire thread with channels in both directions.
-    let (gtk_sender, gtk_receiver) = glib::MainContext::channel(PRIORITY_DEFAULT);
-    let (pw_sender, pw_receiver) = pipewire::channel::channel();
-    let pw_thread =
-        std::thread::spawn(move || pipewire_connection::thread_main(gtk_sender, pw_receiver));
+    // The application starts a backend for each source and stops them again when it shuts down.
+    let app = application::Application::new(sources, record);
+
+    // Let GTK handle the remaining options.
+    args.insert(0, program);
+    app.run_with_args(&args);
+
+    Ok(())
+}
+
+/// Options selecting where the shown graphs come from, used by both the patchbay and the subcommands.
+#[derive(Debug, Default)]
+struct SourceOptions {
+    /// The sources in the order they were given in.
+    sources: Vec<backend::Source>,
+    /// File to record the events of the remote into.
+    record: Option<PathBuf>,
+}
 
-    let app = application::Application::new(gtk_receiver, pw_sender.clone());
+/// Remove the `--remote`, `--fake`, `--replay`, `--replay-speed` and `--record` options from the arguments
+/// and return what they select.
+fn take_source_options(args: &mut Vec<String>) -> Result<SourceOptions, String> {
+    let mut options = SourceOptions::default();
+    let mut replay_speed = None;
 
-    app.run();
+    while let Some((option, value)) = take_option(
+        args,
+        &[
+            "--remote",
+            "--fake",
+            "--replay",
+            "--replay-speed",
+            "--record",
+        ],
+    )? {
+        match option.as_str() {
+            "--remote" => options.sources.push(backend::Source::Remote(Some(value))),
+            "--fake" => options.sources.push(backend::Source::Fake(value.into())),
+            "--replay" => options.sources.push(backend::Source::Replay {
+                path: value.into(),
+                speed: 1.0,
+            }),
+            "--replay-speed" => {
+                let speed = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|speed| *speed > 0.0)
+                    .ok_or("The --replay-speed option requires a positive number")?;
+                replay_speed = Some(speed);
+            }
+            _ => options.record = Some(value.into()),
+        }
+    }
 
-    pw_sender
-        .send(GtkMessage::Terminate)
-        .expect("Failed to send message");
+    if let Some(replay_speed) = replay_speed {
+        for source in &mut options.sources {
+            if let backend::Source::Replay { speed, .. } = source {
+                *speed = replay_speed;
+            }
+        }
+    }
 
-    pw_thread.join().expect("Pipewire thread panicked");
+    let single_remote = matches!(
+        options.sources.as_slice(),
+        [] | [backend::Source::Remote(_)]
+    );
+    if options.record.is_some() && !single_remote {
+        return Err("The --record option can only be used with a single remote".to_string());
+    }
 
-    Ok(())
+    Ok(options)
+}
+
+/// Remove the first of the options with the specified names from the arguments and return its name and value.
+///
+/// Options are accepted both in the form `--option <value>` and `--option=<value>`.
+fn take_option(args: &mut Vec<String>, names: &[&str]) -> Result<Option<(String, String)>, String> {
+    for index in 0..args.len() {
+        let (option, value) = match args[index].split_once('=') {
+            Some((option, value)) => (option.to_string(), Some(value.to_string())),
+            None => (args[index].clone(), None),
+        };
+        if !names.contains(&option.as_str()) {
+            continue;
+        }
+
+        args.remove(index);
+        let value = match value {
+            Some(value) => value,
+            None if index < args.len() => args.remove(index),
+            None => return Err(format!("The {} option requires a value", option)),
+        };
+        return Ok(Some((option, value)));
+    }
+
+    Ok(None)
 }
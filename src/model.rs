@@ -0,0 +1,1250 @@
+// model.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! The graph of pipewire objects tracked by helvum.
+//!
+//! The pipewire thread owns a [`Graph`] that mirrors the remote server.
+//! Every change to it is described by a [`GraphEvent`], which is applied to that graph and then sent
+//! to the GTK thread, where it is applied to a read-only copy.
+//! As both copies see the same events in the same order, they always hold the same content.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use pipewire::spa::Direction;
+use serde::{Deserialize, Serialize};
+
+use crate::{MediaType, NodeType};
+
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub id: u32,
+    pub name: String,
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: u32,
+    pub name: String,
+    pub node_type: Option<NodeType>,
+    pub media_type: Option<MediaType>,
+    /// The device this node belongs to, if any.
+    pub device_id: Option<u32>,
+    pub properties: HashMap<String, String>,
+    /// What helvum may do with the node.
+    pub permissions: Permissions,
+}
+
+/// What a client may do with an object besides seeing it, see `PW_PERM_*` in `pipewire/permission.h`.
+///
+/// Objects are assumed to be fully accessible unless known otherwise, as that is what pipewire
+/// grants to clients by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permissions {
+    /// The object may be changed and destroyed.
+    pub write: bool,
+    /// Methods of the object may be called, like sending commands to a node.
+    pub execute: bool,
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Self {
+            write: true,
+            execute: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Port {
+    pub id: u32,
+    pub node_id: u32,
+    pub name: String,
+    pub direction: Direction,
+    pub media_type: Option<MediaType>,
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Link {
+    pub id: u32,
+    pub node_from: u32,
+    pub port_from: u32,
+    pub node_to: u32,
+    pub port_to: u32,
+    pub state: LinkState,
+    /// The format negotiated for the link, once negotiation has finished.
+    pub format: Option<LinkFormat>,
+    pub properties: HashMap<String, String>,
+}
+
+/// The state of a link, as reported by pipewire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkState {
+    /// The link could not be set up, with a message describing the error.
+    Error(String),
+    Unlinked,
+    Init,
+    Negotiating,
+    Allocating,
+    Paused,
+    Active,
+}
+
+impl LinkState {
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Active)
+    }
+
+    /// Get the name of the state, as used by pipewire tools like `pw-dump`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Error(_) => "error",
+            Self::Unlinked => "unlinked",
+            Self::Init => "init",
+            Self::Negotiating => "negotiating",
+            Self::Allocating => "allocating",
+            Self::Paused => "paused",
+            Self::Active => "active",
+        }
+    }
+
+    /// Get the state with the specified name, using `error` as the message of the error state.
+    pub fn from_name(name: &str, error: Option<&str>) -> Option<Self> {
+        match name {
+            "error" => Some(Self::Error(error.unwrap_or_default().to_string())),
+            "unlinked" => Some(Self::Unlinked),
+            "init" => Some(Self::Init),
+            "negotiating" => Some(Self::Negotiating),
+            "allocating" => Some(Self::Allocating),
+            "paused" => Some(Self::Paused),
+            "active" => Some(Self::Active),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LinkState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error(message) => write!(f, "error: {}", message),
+            other => f.write_str(other.name()),
+        }
+    }
+}
+
+/// The format of the data carried by a link.
+///
+/// Every part is optional, as not all formats have them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkFormat {
+    /// Name of the sample format, like `F32LE`.
+    pub sample_format: Option<String>,
+    pub rate: Option<u32>,
+    pub channels: Option<u32>,
+}
+
+impl fmt::Display for LinkFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(sample_format) = &self.sample_format {
+            parts.push(sample_format.clone());
+        }
+        if let Some(rate) = self.rate {
+            parts.push(format!("{} Hz", rate));
+        }
+        match self.channels {
+            Some(1) => parts.push("1 channel".to_string()),
+            Some(channels) => parts.push(format!("{} channels", channels)),
+            None => {}
+        }
+
+        if parts.is_empty() {
+            f.write_str("unknown")
+        } else {
+            f.write_str(&parts.join(", "))
+        }
+    }
+}
+
+/// Audio channel positions in the order ports are shown in, see `enum spa_audio_channel` in `spa/param/audio/raw.h`.
+const CHANNEL_ORDER: &[&str] = &[
+    "MONO", "FL", "FR", "FC", "LFE", "SL", "SR", "FLC", "FRC", "RC", "RL", "RR", "TC", "TFL",
+    "TFC", "TFR", "TRL", "TRC", "TRR", "RLC", "RRC", "FLW", "FRW", "LFE2", "FLH", "FCH", "FRH",
+    "TFLC", "TFRC", "TSL", "TSR", "LLFE", "RLFE", "BC", "BLC", "BRC",
+];
+
+impl Port {
+    /// Get the key the ports of a node are ordered by.
+    ///
+    /// Ports are ordered by their audio channel position first, with auxiliary channels
+    /// ordered by their number after all named positions. Ports without a known position follow,
+    /// ordered by their `port.id` index in the node, and finally by their pipewire id.
+    pub fn sort_key(&self) -> (u32, u32, u32) {
+        let channel = self.properties.get("audio.channel").and_then(|channel| {
+            CHANNEL_ORDER
+                .iter()
+                .position(|position| position == channel)
+                .map(|position| position as u32)
+                .or_else(|| {
+                    channel
+                        .strip_prefix("AUX")
+                        .and_then(|n| n.parse::<u32>().ok())
+                        .and_then(|n| n.checked_add(CHANNEL_ORDER.len() as u32))
+                })
+        });
+        let index = self
+            .properties
+            .get("port.id")
+            .and_then(|index| index.parse().ok());
+
+        (
+            channel.unwrap_or(u32::MAX),
+            index.unwrap_or(u32::MAX),
+            self.id,
+        )
+    }
+}
+
+impl Link {
+    /// Whether the link is passive, meaning that it does not cause the nodes it connects to be scheduled.
+    pub fn passive(&self) -> bool {
+        property_is_true(&self.properties, "link.passive")
+    }
+
+    /// Whether the link is kept after the client that created it disconnects.
+    pub fn linger(&self) -> bool {
+        property_is_true(&self.properties, "object.linger")
+    }
+}
+
+/// Check whether a boolean property is set to true, which pipewire accepts as either `true` or `1`.
+fn property_is_true(properties: &HashMap<String, String>, key: &str) -> bool {
+    matches!(
+        properties.get(key).map(String::as_str),
+        Some("true") | Some("1")
+    )
+}
+
+/// A single change to the graph.
+#[derive(Debug, Clone)]
+pub enum GraphEvent {
+    DeviceAdded(Device),
+    DeviceRemoved {
+        id: u32,
+    },
+    NodeAdded(Node),
+    NodeRemoved {
+        id: u32,
+    },
+    PortAdded(Port),
+    PortRemoved {
+        id: u32,
+    },
+    LinkAdded(Link),
+    LinkStateChanged {
+        id: u32,
+        state: LinkState,
+    },
+    LinkFormatChanged {
+        id: u32,
+        format: Option<LinkFormat>,
+    },
+    LinkPropertiesChanged {
+        id: u32,
+        properties: HashMap<String, String>,
+    },
+    LinkRemoved {
+        id: u32,
+    },
+}
+
+/// All devices, nodes, ports and links, along with indices for looking up related objects.
+///
+/// The graph can only be changed by applying [`GraphEvent`]s to it.
+/// Cloning it yields a snapshot that is not affected by later changes.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    devices: HashMap<u32, Device>,
+    nodes: HashMap<u32, Node>,
+    ports: HashMap<u32, Port>,
+    links: HashMap<u32, Link>,
+
+    /// Map device ids to the ids of their nodes.
+    device_nodes: HashMap<u32, BTreeSet<u32>>,
+    /// Map node ids to the ids of their ports.
+    node_ports: HashMap<u32, BTreeSet<u32>>,
+    /// Map port ids to the ids of all links from or to the port.
+    port_links: HashMap<u32, BTreeSet<u32>>,
+    /// Map `(output port id, input port id)` tuples to the id of the link that connects them.
+    links_by_ports: HashMap<(u32, u32), u32>,
+}
+
+/// Add `child` to the children of `parent` in the index.
+fn index_insert(index: &mut HashMap<u32, BTreeSet<u32>>, parent: u32, child: u32) {
+    index.entry(parent).or_default().insert(child);
+}
+
+/// Remove `child` from the children of `parent` in the index, dropping the entry if it becomes empty.
+fn index_remove(index: &mut HashMap<u32, BTreeSet<u32>>, parent: u32, child: u32) {
+    if let Some(children) = index.get_mut(&parent) {
+        children.remove(&child);
+        if children.is_empty() {
+            index.remove(&parent);
+        }
+    }
+}
+
+impl Graph {
+    /// Create a new, empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change the graph as described by the event.
+    ///
+    /// Objects that are added with the id of an existing object replace that object.
+    /// Events referring to unknown objects are ignored.
+    /// Removing an object does not remove the objects belonging to it, see [`Graph::removal_events`].
+    pub fn apply(&mut self, event: &GraphEvent) {
+        match event {
+            GraphEvent::DeviceAdded(device) => {
+                self.devices.insert(device.id, device.clone());
+            }
+            GraphEvent::DeviceRemoved { id } => {
+                self.devices.remove(id);
+            }
+            GraphEvent::NodeAdded(node) => {
+                self.remove_node(node.id);
+                if let Some(device_id) = node.device_id {
+                    index_insert(&mut self.device_nodes, device_id, node.id);
+                }
+                self.nodes.insert(node.id, node.clone());
+            }
+            GraphEvent::NodeRemoved { id } => self.remove_node(*id),
+            GraphEvent::PortAdded(port) => {
+                self.remove_port(port.id);
+                index_insert(&mut self.node_ports, port.node_id, port.id);
+                self.ports.insert(port.id, port.clone());
+            }
+            GraphEvent::PortRemoved { id } => self.remove_port(*id),
+            GraphEvent::LinkAdded(link) => {
+                self.remove_link(link.id);
+                index_insert(&mut self.port_links, link.port_from, link.id);
+                index_insert(&mut self.port_links, link.port_to, link.id);
+                self.links_by_ports
+                    .insert((link.port_from, link.port_to), link.id);
+                self.links.insert(link.id, link.clone());
+            }
+            GraphEvent::LinkStateChanged { id, state } => {
+                if let Some(link) = self.links.get_mut(id) {
+                    link.state = state.clone();
+                }
+            }
+            GraphEvent::LinkFormatChanged { id, format } => {
+                if let Some(link) = self.links.get_mut(id) {
+                    link.format = format.clone();
+                }
+            }
+            GraphEvent::LinkPropertiesChanged { id, properties } => {
+                if let Some(link) = self.links.get_mut(id) {
+                    link.properties = properties.clone();
+                }
+            }
+            GraphEvent::LinkRemoved { id } => self.remove_link(*id),
+        }
+    }
+
+    fn remove_node(&mut self, id: u32) {
+        if let Some(node) = self.nodes.remove(&id) {
+            if let Some(device_id) = node.device_id {
+                index_remove(&mut self.device_nodes, device_id, id);
+            }
+        }
+    }
+
+    fn remove_port(&mut self, id: u32) {
+        if let Some(port) = self.ports.remove(&id) {
+            index_remove(&mut self.node_ports, port.node_id, id);
+        }
+    }
+
+    fn remove_link(&mut self, id: u32) {
+        if let Some(link) = self.links.remove(&id) {
+            index_remove(&mut self.port_links, link.port_from, id);
+            index_remove(&mut self.port_links, link.port_to, id);
+            self.links_by_ports.remove(&(link.port_from, link.port_to));
+        }
+    }
+
+    /// Get the events that remove the object with the specified id along with all objects belonging to it.
+    ///
+    /// Pipewire may announce the removal of an object before that of the objects belonging to it,
+    /// so the links of a port are removed before the port, the ports of a node before the node,
+    /// and the nodes of a device before the device.
+    /// Returns no events if there is no object with the id.
+    pub fn removal_events(&self, id: u32) -> Vec<GraphEvent> {
+        let mut events = Vec::new();
+        if self.devices.contains_key(&id) {
+            for node_id in self.device_nodes.get(&id).into_iter().flatten() {
+                self.push_node_removal(*node_id, &mut events);
+            }
+            events.push(GraphEvent::DeviceRemoved { id });
+        } else if self.nodes.contains_key(&id) {
+            self.push_node_removal(id, &mut events);
+        } else if self.ports.contains_key(&id) {
+            self.push_port_removal(id, &mut events);
+        } else if self.links.contains_key(&id) {
+            events.push(GraphEvent::LinkRemoved { id });
+        }
+
+        events
+    }
+
+    fn push_node_removal(&self, id: u32, events: &mut Vec<GraphEvent>) {
+        for port_id in self.node_ports.get(&id).into_iter().flatten() {
+            self.push_port_removal(*port_id, events);
+        }
+        events.push(GraphEvent::NodeRemoved { id });
+    }
+
+    fn push_port_removal(&self, id: u32, events: &mut Vec<GraphEvent>) {
+        for link_id in self.port_links.get(&id).into_iter().flatten() {
+            // A link between two ports of the same node is only removed once.
+            let removed = events
+                .iter()
+                .any(|event| matches!(event, GraphEvent::LinkRemoved { id } if id == link_id));
+            if !removed {
+                events.push(GraphEvent::LinkRemoved { id: *link_id });
+            }
+        }
+        events.push(GraphEvent::PortRemoved { id });
+    }
+
+    pub fn device(&self, id: u32) -> Option<&Device> {
+        self.devices.get(&id)
+    }
+
+    pub fn node(&self, id: u32) -> Option<&Node> {
+        self.nodes.get(&id)
+    }
+
+    pub fn port(&self, id: u32) -> Option<&Port> {
+        self.ports.get(&id)
+    }
+
+    pub fn link(&self, id: u32) -> Option<&Link> {
+        self.links.get(&id)
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &Device> + '_ {
+        self.devices.values()
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> + '_ {
+        self.nodes.values()
+    }
+
+    pub fn ports(&self) -> impl Iterator<Item = &Port> + '_ {
+        self.ports.values()
+    }
+
+    pub fn links(&self) -> impl Iterator<Item = &Link> + '_ {
+        self.links.values()
+    }
+
+    /// Get all nodes belonging to the specified device, ordered by id.
+    pub fn nodes_of_device(&self, device_id: u32) -> impl Iterator<Item = &Node> + '_ {
+        self.device_nodes
+            .get(&device_id)
+            .into_iter()
+            .flatten()
+            .filter_map(move |id| self.nodes.get(id))
+    }
+
+    /// Get all ports of the specified node, ordered by id.
+    pub fn ports_of_node(&self, node_id: u32) -> impl Iterator<Item = &Port> + '_ {
+        self.node_ports
+            .get(&node_id)
+            .into_iter()
+            .flatten()
+            .filter_map(move |id| self.ports.get(id))
+    }
+
+    /// Get all links from or to the specified port, ordered by id.
+    pub fn links_of_port(&self, port_id: u32) -> impl Iterator<Item = &Link> + '_ {
+        self.port_links
+            .get(&port_id)
+            .into_iter()
+            .flatten()
+            .filter_map(move |id| self.links.get(id))
+    }
+
+    /// Get the ids of all links from or to a port of the specified node, ordered by id.
+    pub fn links_of_node(&self, node_id: u32) -> Vec<u32> {
+        self.ports_of_node(node_id)
+            .flat_map(|port| self.links_of_port(port.id))
+            .map(|link| link.id)
+            .collect::<BTreeSet<u32>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Get the link from the specified output port to the specified input port.
+    pub fn link_between(&self, output_port: u32, input_port: u32) -> Option<&Link> {
+        self.links_by_ports
+            .get(&(output_port, input_port))
+            .and_then(|id| self.links.get(id))
+    }
+
+    /// Find a node by its displayed name or by its `node.name` property.
+    pub fn node_by_name(&self, name: &str) -> Option<&Node> {
+        self.nodes.values().find(|node| {
+            node.name == name || node.properties.get("node.name").map(String::as_str) == Some(name)
+        })
+    }
+
+    /// Get the full `<node name>:<port name>` name of a port.
+    pub fn port_full_name(&self, port_id: u32) -> Option<String> {
+        let port = self.ports.get(&port_id)?;
+        let node_name = self
+            .nodes
+            .get(&port.node_id)
+            .map_or("", |node| node.name.as_str());
+
+        Some(format!("{}:{}", node_name, port.name))
+    }
+
+    /// Get all ports with the specified direction whose full name matches the pattern,
+    /// ordered by their full name.
+    ///
+    /// See [`matches_pattern`] for the syntax of the pattern.
+    pub fn find_ports(&self, pattern: &str, direction: Direction) -> Vec<&Port> {
+        let mut ports: Vec<(String, &Port)> = self
+            .ports
+            .values()
+            .filter(|port| port.direction == direction)
+            .filter_map(|port| Some((self.port_full_name(port.id)?, port)))
+            .filter(|(name, _)| matches_pattern(pattern, name))
+            .collect();
+        ports.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        ports.into_iter().map(|(_, port)| port).collect()
+    }
+
+    /// Resolve the patterns into pairs of `(output port, input port)` that should be linked.
+    ///
+    /// If both patterns match the same number of ports, they are paired up in the order of their names,
+    /// so that e.g. `*:output_FL` gets linked to `*:playback_FL`.
+    /// If one pattern matches a single port, that port is paired with every port matching the other pattern.
+    pub fn resolve_pairs(&self, output: &str, input: &str) -> Result<Vec<(u32, u32)>, String> {
+        let outputs: Vec<u32> = self
+            .find_ports(output, Direction::Output)
+            .iter()
+            .map(|port| port.id)
+            .collect();
+        let inputs: Vec<u32> = self
+            .find_ports(input, Direction::Input)
+            .iter()
+            .map(|port| port.id)
+            .collect();
+
+        if outputs.is_empty() {
+            return Err(format!("No output port matches \"{}\"", output));
+        }
+        if inputs.is_empty() {
+            return Err(format!("No input port matches \"{}\"", input));
+        }
+
+        let pairs = if outputs.len() == inputs.len() {
+            outputs.into_iter().zip(inputs).collect()
+        } else if outputs.len() == 1 {
+            inputs
+                .into_iter()
+                .map(|input| (outputs[0], input))
+                .collect()
+        } else if inputs.len() == 1 {
+            outputs
+                .into_iter()
+                .map(|output| (output, inputs[0]))
+                .collect()
+        } else {
+            return Err(format!(
+                "\"{}\" matches {} output ports, but \"{}\" matches {} input ports",
+                output,
+                outputs.len(),
+                input,
+                inputs.len()
+            ));
+        };
+
+        Ok(pairs)
+    }
+
+    /// Get the node that audio from or to the specified node goes to or comes from by default.
+    ///
+    /// That is the audio sink with the highest `priority.session` for output nodes,
+    /// and the audio source with the highest priority for input nodes.
+    pub fn default_node_for(&self, node_id: u32) -> Result<&Node, String> {
+        let node = self
+            .node(node_id)
+            .ok_or_else(|| format!("Node {} is not in the graph", node_id))?;
+        if node.media_type != Some(MediaType::Audio) {
+            return Err(format!("{} is not an audio node", node.name));
+        }
+        let class = match node.node_type {
+            Some(NodeType::Output) => "Audio/Sink",
+            Some(NodeType::Input) => "Audio/Source",
+            None => return Err(format!("{} is neither a source nor a sink", node.name)),
+        };
+
+        let priority = |node: &Node| -> i64 {
+            node.properties
+                .get("priority.session")
+                .and_then(|priority| priority.parse().ok())
+                .unwrap_or(0)
+        };
+        self.nodes
+            .values()
+            .filter(|other| other.id != node_id)
+            .filter(|other| {
+                other
+                    .properties
+                    .get("media.class")
+                    .map_or(false, |other_class| other_class.starts_with(class))
+            })
+            // Prefer the node that appeared first if several nodes have the same priority.
+            .max_by_key(|other| (priority(other), std::cmp::Reverse(other.id)))
+            .ok_or_else(|| format!("There is no default {}", class))
+    }
+
+    /// Pair up the output ports of the `output` node with the input ports of the `input` node,
+    /// as `(output port, input port)` tuples.
+    ///
+    /// Ports are paired by their audio channel if the input node has all channels of the output node,
+    /// otherwise in the order of their sort key. A single port is paired with all ports of the other node.
+    pub fn channel_pairs(&self, output: u32, input: u32) -> Vec<(u32, u32)> {
+        let sorted_ports = |node_id: u32, direction: Direction| {
+            let mut ports: Vec<&Port> = self
+                .ports_of_node(node_id)
+                .filter(|port| port.direction == direction)
+                .collect();
+            ports.sort_by_key(|port| port.sort_key());
+            ports
+        };
+        let outputs = sorted_ports(output, Direction::Output);
+        let inputs = sorted_ports(input, Direction::Input);
+
+        if outputs.len() == 1 {
+            return inputs
+                .iter()
+                .map(|input| (outputs[0].id, input.id))
+                .collect();
+        }
+        if inputs.len() == 1 {
+            return outputs
+                .iter()
+                .map(|output| (output.id, inputs[0].id))
+                .collect();
+        }
+
+        let channel = |port: &Port| port.properties.get("audio.channel").cloned();
+        let by_channel: Option<Vec<(u32, u32)>> = outputs
+            .iter()
+            .map(|output| {
+                let output_channel = channel(output)?;
+                inputs
+                    .iter()
+                    .find(|input| channel(input).as_ref() == Some(&output_channel))
+                    .map(|input| (output.id, input.id))
+            })
+            .collect();
+
+        by_channel.unwrap_or_else(|| {
+            outputs
+                .iter()
+                .zip(inputs.iter())
+                .map(|(output, input)| (output.id, input.id))
+                .collect()
+        })
+    }
+
+    /// Get all links from an output port matching `output` to an input port matching `input`, ordered by id.
+    pub fn links_matching(&self, output: &str, input: &str) -> Vec<&Link> {
+        let inputs: Vec<u32> = self
+            .find_ports(input, Direction::Input)
+            .iter()
+            .map(|port| port.id)
+            .collect();
+
+        let mut links: Vec<&Link> = self
+            .find_ports(output, Direction::Output)
+            .iter()
+            .flat_map(|port| self.links_of_port(port.id))
+            .filter(|link| inputs.contains(&link.port_to))
+            .collect();
+        links.sort_by_key(|link| link.id);
+
+        links
+    }
+}
+
+/// Check whether `name` matches `pattern`, where `*` matches any number of characters
+/// and `?` matches exactly one character.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // matches[j] is true if the pattern processed so far matches the first j characters of the name.
+    let mut matches = vec![false; name.len() + 1];
+    matches[0] = true;
+
+    for p in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matches[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matches[j - 1],
+                c => j > 0 && matches[j - 1] && name[j - 1] == c,
+            };
+        }
+        matches = next;
+    }
+
+    matches[name.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn node(id: u32, name: &str, media_class: &str, priority: u32) -> Node {
+        let (node_type, media_type) = match media_class {
+            "Audio/Sink" => (Some(NodeType::Input), Some(MediaType::Audio)),
+            "Audio/Source" => (Some(NodeType::Output), Some(MediaType::Audio)),
+            "Stream/Output/Audio" => (Some(NodeType::Output), Some(MediaType::Audio)),
+            "Stream/Input/Audio" => (Some(NodeType::Input), Some(MediaType::Audio)),
+            "Video/Source" => (Some(NodeType::Output), Some(MediaType::Video)),
+            _ => (None, Some(MediaType::Audio)),
+        };
+
+        Node {
+            id,
+            name: name.to_string(),
+            node_type,
+            media_type,
+            device_id: None,
+            properties: properties(&[
+                ("media.class", media_class),
+                ("priority.session", &priority.to_string()),
+            ]),
+            permissions: Permissions::default(),
+        }
+    }
+
+    fn port(id: u32, node_id: u32, name: &str, direction: Direction, channel: &str) -> Port {
+        Port {
+            id,
+            node_id,
+            name: name.to_string(),
+            direction,
+            media_type: Some(MediaType::Audio),
+            properties: properties(&[("audio.channel", channel)]),
+        }
+    }
+
+    fn link(graph: &Graph, id: u32, port_from: u32, port_to: u32) -> Link {
+        Link {
+            id,
+            node_from: graph.port(port_from).unwrap().node_id,
+            port_from,
+            node_to: graph.port(port_to).unwrap().node_id,
+            port_to,
+            state: LinkState::Init,
+            format: None,
+            properties: HashMap::new(),
+        }
+    }
+
+    /// A stereo player, a stereo sink on a device, a mono microphone and a surround sink.
+    fn graph() -> Graph {
+        let mut graph = Graph::new();
+        graph.apply(&GraphEvent::DeviceAdded(Device {
+            id: 1,
+            name: "Soundcard".to_string(),
+            properties: HashMap::new(),
+        }));
+
+        let mut speakers = node(3, "Speakers", "Audio/Sink", 1000);
+        speakers.device_id = Some(1);
+        for node in [
+            node(2, "Player", "Stream/Output/Audio", 0),
+            speakers,
+            node(4, "Microphone", "Audio/Source", 0),
+            node(5, "Surround", "Audio/Sink", 500),
+        ] {
+            graph.apply(&GraphEvent::NodeAdded(node));
+        }
+
+        for port in [
+            port(20, 2, "output_FL", Direction::Output, "FL"),
+            port(21, 2, "output_FR", Direction::Output, "FR"),
+            port(30, 3, "playback_FL", Direction::Input, "FL"),
+            port(31, 3, "playback_FR", Direction::Input, "FR"),
+            port(32, 3, "monitor_FL", Direction::Output, "FL"),
+            port(33, 3, "monitor_FR", Direction::Output, "FR"),
+            port(40, 4, "capture_MONO", Direction::Output, "MONO"),
+            port(50, 5, "playback_FR", Direction::Input, "FR"),
+            port(51, 5, "playback_FL", Direction::Input, "FL"),
+            port(52, 5, "playback_LFE", Direction::Input, "LFE"),
+            port(53, 5, "playback_FC", Direction::Input, "FC"),
+        ] {
+            graph.apply(&GraphEvent::PortAdded(port));
+        }
+
+        graph
+    }
+
+    fn port_ids(graph: &Graph, node_id: u32) -> Vec<u32> {
+        graph.ports_of_node(node_id).map(|port| port.id).collect()
+    }
+
+    fn link_ids(graph: &Graph, port_id: u32) -> Vec<u32> {
+        graph.links_of_port(port_id).map(|link| link.id).collect()
+    }
+
+    #[test]
+    fn apply_adds_objects_to_indices() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+
+        assert_eq!(graph.device(1).unwrap().name, "Soundcard");
+        assert_eq!(
+            graph
+                .nodes_of_device(1)
+                .map(|node| node.id)
+                .collect::<Vec<_>>(),
+            [3]
+        );
+        assert_eq!(port_ids(&graph, 3), [30, 31, 32, 33]);
+        assert_eq!(link_ids(&graph, 20), [100]);
+        assert_eq!(link_ids(&graph, 30), [100]);
+        assert_eq!(graph.link_between(20, 30).unwrap().id, 100);
+
+        // Adding an object with an existing id replaces it, but keeps the objects belonging to it.
+        graph.apply(&GraphEvent::NodeAdded(node(
+            3,
+            "Renamed",
+            "Audio/Sink",
+            1000,
+        )));
+        assert_eq!(graph.node(3).unwrap().name, "Renamed");
+        assert_eq!(graph.nodes_of_device(1).count(), 0);
+        assert_eq!(port_ids(&graph, 3), [30, 31, 32, 33]);
+        assert_eq!(link_ids(&graph, 30), [100]);
+    }
+
+    #[test]
+    fn apply_changes_links() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+
+        graph.apply(&GraphEvent::LinkStateChanged {
+            id: 100,
+            state: LinkState::Active,
+        });
+        let format = LinkFormat {
+            sample_format: Some("F32LE".to_string()),
+            rate: Some(48000),
+            channels: Some(2),
+        };
+        graph.apply(&GraphEvent::LinkFormatChanged {
+            id: 100,
+            format: Some(format.clone()),
+        });
+        graph.apply(&GraphEvent::LinkPropertiesChanged {
+            id: 100,
+            properties: properties(&[("link.passive", "true"), ("object.linger", "1")]),
+        });
+
+        let link = graph.link(100).unwrap();
+        assert_eq!(link.state, LinkState::Active);
+        assert_eq!(link.format, Some(format));
+        assert!(link.passive());
+        assert!(link.linger());
+
+        // Events for unknown links are ignored.
+        graph.apply(&GraphEvent::LinkStateChanged {
+            id: 101,
+            state: LinkState::Active,
+        });
+        assert!(graph.link(101).is_none());
+    }
+
+    #[test]
+    fn apply_removes_links_from_indices() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 101, 20, 50)));
+
+        graph.apply(&GraphEvent::LinkRemoved { id: 100 });
+
+        assert!(graph.link(100).is_none());
+        assert!(graph.link_between(20, 30).is_none());
+        assert_eq!(link_ids(&graph, 20), [101]);
+        assert!(link_ids(&graph, 30).is_empty());
+    }
+
+    /// Remove the object with the specified id like the pipewire thread does,
+    /// returning the removed objects as `(kind, id)` in the order they were removed.
+    fn remove(graph: &mut Graph, id: u32) -> Vec<(&'static str, u32)> {
+        let events = graph.removal_events(id);
+        for event in &events {
+            graph.apply(event);
+        }
+
+        events
+            .iter()
+            .map(|event| match event {
+                GraphEvent::DeviceRemoved { id } => ("device", *id),
+                GraphEvent::NodeRemoved { id } => ("node", *id),
+                GraphEvent::PortRemoved { id } => ("port", *id),
+                GraphEvent::LinkRemoved { id } => ("link", *id),
+                other => panic!("{:?} is not a removal", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn removing_a_port_removes_its_links() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 101, 21, 31)));
+
+        assert_eq!(remove(&mut graph, 30), [("link", 100), ("port", 30)]);
+
+        assert!(graph.port(30).is_none());
+        assert_eq!(port_ids(&graph, 3), [31, 32, 33]);
+        assert!(graph.link(100).is_none());
+        assert!(graph.link_between(20, 30).is_none());
+        assert!(link_ids(&graph, 20).is_empty());
+        assert_eq!(link_ids(&graph, 21), [101]);
+    }
+
+    #[test]
+    fn removing_a_node_removes_its_ports_and_links() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 101, 21, 50)));
+        // A link from a monitor port back into the same node is only removed once.
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 102, 32, 31)));
+
+        assert_eq!(
+            remove(&mut graph, 3),
+            [
+                ("link", 100),
+                ("port", 30),
+                ("link", 102),
+                ("port", 31),
+                ("port", 32),
+                ("port", 33),
+                ("node", 3),
+            ]
+        );
+
+        assert!(graph.node(3).is_none());
+        assert!(port_ids(&graph, 3).is_empty());
+        assert!((30..=33).all(|id| graph.port(id).is_none()));
+        assert!(graph.link(100).is_none());
+        assert!(link_ids(&graph, 20).is_empty());
+        assert_eq!(link_ids(&graph, 21), [101]);
+        assert_eq!(graph.nodes_of_device(1).count(), 0);
+        // Their removal is still announced by pipewire afterwards, which is then ignored.
+        assert!(graph.removal_events(30).is_empty());
+        assert!(graph.removal_events(100).is_empty());
+    }
+
+    #[test]
+    fn removing_a_device_removes_its_nodes() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+
+        assert_eq!(
+            remove(&mut graph, 1),
+            [
+                ("link", 100),
+                ("port", 30),
+                ("port", 31),
+                ("port", 32),
+                ("port", 33),
+                ("node", 3),
+                ("device", 1),
+            ]
+        );
+
+        assert!(graph.device(1).is_none());
+        assert!(graph.node(3).is_none());
+        assert!(port_ids(&graph, 3).is_empty());
+        assert!(graph.link(100).is_none());
+        assert!(link_ids(&graph, 20).is_empty());
+        assert_eq!(port_ids(&graph, 2), [20, 21]);
+    }
+
+    #[test]
+    fn removal_events_of_each_object_kind() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+
+        assert_eq!(
+            remove(&mut graph.clone(), 2),
+            [("link", 100), ("port", 20), ("port", 21), ("node", 2)]
+        );
+        assert_eq!(
+            remove(&mut graph.clone(), 20),
+            [("link", 100), ("port", 20)]
+        );
+        assert_eq!(remove(&mut graph.clone(), 100), [("link", 100)]);
+        assert!(graph.removal_events(999).is_empty());
+    }
+
+    #[test]
+    fn link_between_has_direction() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 102, 21, 31)));
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+
+        assert_eq!(graph.link_between(21, 31).unwrap().id, 102);
+        assert!(graph.link_between(31, 21).is_none());
+        assert!(graph.link_between(20, 31).is_none());
+    }
+
+    #[test]
+    fn links_of_node_are_unique() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 102, 21, 31)));
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 101, 32, 50)));
+
+        assert_eq!(graph.links_of_node(2), [100, 102]);
+        assert_eq!(graph.links_of_node(3), [100, 101, 102]);
+        assert_eq!(graph.links_of_node(5), [101]);
+        assert!(graph.links_of_node(4).is_empty());
+        assert!(graph.links_of_node(999).is_empty());
+    }
+
+    #[test]
+    fn links_matching_patterns() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 101, 21, 31)));
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 100, 20, 30)));
+        graph.apply(&GraphEvent::LinkAdded(link(&graph, 102, 20, 51)));
+
+        let ids = |output: &str, input: &str| -> Vec<u32> {
+            graph
+                .links_matching(output, input)
+                .iter()
+                .map(|link| link.id)
+                .collect()
+        };
+        assert_eq!(ids("Player:*", "Speakers:*"), [100, 101]);
+        assert_eq!(ids("Player:output_FL", "*"), [100, 102]);
+        assert_eq!(ids("*", "*:playback_FL"), [100, 102]);
+        assert!(ids("Microphone:*", "*").is_empty());
+        // Only the unlinked monitor ports of the speakers match as outputs.
+        assert!(ids("Speakers:*", "*").is_empty());
+    }
+
+    #[test]
+    fn resolve_pairs_by_name() {
+        let graph = graph();
+
+        // Equal numbers of ports are paired in the order of their names.
+        assert_eq!(
+            graph.resolve_pairs("Player:*", "Speakers:*"),
+            Ok(vec![(20, 30), (21, 31)])
+        );
+        // A single port is paired with all ports matching the other pattern.
+        assert_eq!(
+            graph.resolve_pairs("Microphone:*", "Speakers:playback_*"),
+            Ok(vec![(40, 30), (40, 31)])
+        );
+        assert_eq!(
+            graph.resolve_pairs("Player:*", "Surround:playback_FC"),
+            Ok(vec![(20, 53), (21, 53)])
+        );
+    }
+
+    #[test]
+    fn resolve_pairs_errors() {
+        let graph = graph();
+
+        assert_eq!(
+            graph.resolve_pairs("Nothing:*", "Speakers:*"),
+            Err("No output port matches \"Nothing:*\"".to_string())
+        );
+        // Patterns only match ports of the right direction.
+        assert_eq!(
+            graph.resolve_pairs("Player:*", "Player:*"),
+            Err("No input port matches \"Player:*\"".to_string())
+        );
+        assert_eq!(
+            graph.resolve_pairs("Player:*", "Surround:*"),
+            Err(
+                "\"Player:*\" matches 2 output ports, but \"Surround:*\" matches 4 input ports"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn pattern_syntax() {
+        assert!(matches_pattern("Player:output_FL", "Player:output_FL"));
+        assert!(!matches_pattern("Player:output_FL", "Player:output_FR"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("*", "Player:output_FL"));
+        assert!(matches_pattern("Player:*", "Player:output_FL"));
+        assert!(matches_pattern("*:output_*", "Player:output_FL"));
+        assert!(matches_pattern("*FL", "Player:output_FL"));
+        assert!(!matches_pattern("*FL", "Player:output_FR"));
+        assert!(matches_pattern("Player:output_F?", "Player:output_FR"));
+        assert!(!matches_pattern("Player:output_?", "Player:output_FR"));
+        assert!(!matches_pattern("?", ""));
+        assert!(matches_pattern("Pl*er:*_F?", "Player:output_FL"));
+        assert!(!matches_pattern("Player", "Player:output_FL"));
+        assert!(matches_pattern("Äpfel:?", "Äpfel:ü"));
+    }
+
+    #[test]
+    fn ports_sort_by_channel() {
+        let mut graph = graph();
+        for port in [
+            port(60, 6, "aux10", Direction::Input, "AUX10"),
+            port(61, 6, "aux2", Direction::Input, "AUX2"),
+            port(62, 6, "rear", Direction::Input, "RR"),
+            port(63, 6, "front", Direction::Input, "FL"),
+            port(64, 6, "unknown", Direction::Input, "XYZ"),
+        ] {
+            graph.apply(&GraphEvent::PortAdded(port));
+        }
+
+        let mut ports: Vec<&Port> = graph.ports_of_node(6).collect();
+        ports.sort_by_key(|port| port.sort_key());
+        let ids: Vec<u32> = ports.iter().map(|port| port.id).collect();
+        assert_eq!(ids, [63, 62, 61, 60, 64]);
+    }
+
+    #[test]
+    fn channel_pairs_by_channel() {
+        let graph = graph();
+
+        // The surround sink has the channels of the player in a different order.
+        assert_eq!(graph.channel_pairs(2, 5), [(20, 51), (21, 50)]);
+        assert_eq!(graph.channel_pairs(2, 3), [(20, 30), (21, 31)]);
+    }
+
+    #[test]
+    fn channel_pairs_single_port() {
+        let graph = graph();
+
+        // A mono output goes to all inputs, in channel order.
+        assert_eq!(
+            graph.channel_pairs(4, 5),
+            [(40, 51), (40, 50), (40, 53), (40, 52)]
+        );
+    }
+
+    #[test]
+    fn channel_pairs_without_common_channels() {
+        let mut graph = graph();
+        for port in [
+            port(60, 6, "aux1", Direction::Input, "AUX1"),
+            port(61, 6, "aux0", Direction::Input, "AUX0"),
+        ] {
+            graph.apply(&GraphEvent::PortAdded(port));
+        }
+
+        // Ports are zipped in their sort order if the channels do not match.
+        assert_eq!(graph.channel_pairs(2, 6), [(20, 61), (21, 60)]);
+    }
+
+    #[test]
+    fn default_node_prefers_priority() {
+        let graph = graph();
+
+        assert_eq!(graph.default_node_for(2).unwrap().id, 3);
+        assert_eq!(graph.default_node_for(3).unwrap().id, 4);
+        assert_eq!(graph.default_node_for(4).unwrap().id, 3);
+    }
+
+    #[test]
+    fn default_node_ties_prefer_lowest_id() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::NodeAdded(node(
+            5,
+            "Surround",
+            "Audio/Sink",
+            1000,
+        )));
+        graph.apply(&GraphEvent::NodeAdded(node(
+            1000,
+            "Later",
+            "Audio/Sink",
+            1000,
+        )));
+
+        assert_eq!(graph.default_node_for(2).unwrap().id, 3);
+    }
+
+    #[test]
+    fn default_node_errors() {
+        let mut graph = graph();
+        graph.apply(&GraphEvent::NodeAdded(node(6, "Camera", "Video/Source", 0)));
+        graph.apply(&GraphEvent::NodeAdded(node(7, "Filter", "Audio/Duplex", 0)));
+
+        assert_eq!(
+            graph.default_node_for(999).unwrap_err(),
+            "Node 999 is not in the graph"
+        );
+        assert_eq!(
+            graph.default_node_for(6).unwrap_err(),
+            "Camera is not an audio node"
+        );
+        assert_eq!(
+            graph.default_node_for(7).unwrap_err(),
+            "Filter is neither a source nor a sink"
+        );
+
+        graph.apply(&GraphEvent::NodeRemoved { id: 4 });
+        assert_eq!(
+            graph.default_node_for(3).unwrap_err(),
+            "There is no default Audio/Source"
+        );
+    }
+}
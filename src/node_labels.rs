@@ -0,0 +1,103 @@
+// node_labels.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Custom labels and colors for nodes, chosen by the user and stored in a local file.
+//!
+//! Nodes get new ids every time they appear, so they are identified by their `node.name` property instead,
+//! or by their `object.path` if they do not have a name.
+
+use std::{collections::HashMap, error::Error, path::PathBuf};
+
+use gtk::glib;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// The customizations of a single node.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeLabel {
+    /// Label shown instead of the name of the node.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Background color of the node, in any format understood by CSS.
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// The customizations of all nodes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeLabels {
+    labels: HashMap<String, NodeLabel>,
+}
+
+impl NodeLabels {
+    /// Get the path of the file the labels are stored in.
+    fn path() -> PathBuf {
+        glib::user_config_dir()
+            .join("helvum")
+            .join("node-labels.json")
+    }
+
+    /// Load the stored labels, or start without any labels if there are none or they can not be read.
+    pub fn load() -> Self {
+        let path = Self::path();
+        if !path.exists() {
+            return Self::default();
+        }
+
+        std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("Failed to load node labels from {}: {}", path.display(), e);
+                Self::default()
+            })
+    }
+
+    /// Store the labels, so that they are used again the next time helvum is started.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Get the key a node is identified by, based on its properties.
+    pub fn key(properties: &HashMap<String, String>) -> Option<String> {
+        properties
+            .get("node.name")
+            .or_else(|| properties.get("object.path"))
+            .cloned()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&NodeLabel> {
+        self.labels.get(key)
+    }
+
+    /// Set the customizations of the node with the specified key, removing them if they are empty.
+    pub fn set(&mut self, key: &str, label: NodeLabel) {
+        if label == NodeLabel::default() {
+            self.labels.remove(key);
+        } else {
+            self.labels.insert(key.to_string(), label);
+        }
+    }
+}
@@ -17,109 +17,373 @@
 //
 // SPDX-License-Identifier: GPL-3.0-only
 
-mod state;
-
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    ffi::CString,
+    path::PathBuf,
+    ptr::NonNull,
+    rc::Rc,
+    thread::JoinHandle,
+};
 
 use gtk::glib::{self, clone};
 use log::{debug, info, warn};
 use pipewire::{
-    link::{Link, LinkChangeMask, LinkListener, LinkState},
+    link::{Link, LinkChangeMask, LinkInfo, LinkListener, LinkState},
+    node::Node,
     prelude::*,
     properties,
-    registry::{GlobalObject, Registry},
-    spa::{Direction, ForeignDict},
+    proxy::ProxyT,
+    registry::{GlobalObject, Permission, Registry},
+    spa::{
+        pod::{deserialize::PodDeserializer, Value},
+        utils::Id,
+        Direction, ForeignDict,
+    },
     types::ObjectType,
-    Context, Core, MainLoop,
+    Context, Core, MainLoop, Properties,
 };
 
-use crate::{GtkMessage, MediaType, NodeType, PipewireMessage};
-use state::{Item, State};
+use crate::{
+    backend::GraphBackend,
+    model::{self, Graph, GraphEvent},
+    recording::{LinkChanges, LinkInfoEvent, Recorder, RegistryEvent},
+    virtual_nodes::{self, VirtualNode, VirtualNodeKind},
+    GtkMessage, LinkOptions, MediaType, NodeType, PipewireMessage,
+};
+
+/// Name of the remote pipewire connects to if no other remote is requested.
+const DEFAULT_REMOTE: &str = "pipewire-0";
 
 enum ProxyItem {
     Link {
         _proxy: Link,
         _listener: LinkListener,
     },
+    /// Nodes are only bound to send commands to them.
+    Node { proxy: Node },
+}
+
+/// A virtual node created by the pipewire thread.
+enum VirtualNodeHandle {
+    /// The proxy of a null sink, which destroys the node when it is dropped unless the node lingers.
+    NullSink(Node),
+    /// The loopback module loaded into our context, which runs the nodes of the loopback.
+    Loopback(NonNull<pipewire::sys::pw_impl_module>),
+}
+
+/// A pipewire thread connected to a remote.
+pub(super) struct PipewireBackend {
+    pw_sender: pipewire::channel::Sender<GtkMessage>,
+    /// Handle of the thread, until it has been stopped.
+    thread: Cell<Option<JoinHandle<()>>>,
+}
+
+impl PipewireBackend {
+    /// Start the pipewire thread, connected to the remote with the specified name or socket path.
+    ///
+    /// If `remote` is `None`, the default remote is used, which can be set with the `PIPEWIRE_REMOTE` environment variable.
+    /// If `record` is set, all events received from the remote are recorded into the file at that path.
+    pub(super) fn spawn(
+        gtk_sender: glib::Sender<PipewireMessage>,
+        remote: Option<String>,
+        record: Option<PathBuf>,
+    ) -> Self {
+        let (pw_sender, pw_receiver) = pipewire::channel::channel();
+        let thread =
+            std::thread::spawn(move || thread_main(gtk_sender, pw_receiver, remote, record));
+
+        Self {
+            pw_sender,
+            thread: Cell::new(Some(thread)),
+        }
+    }
+}
+
+impl GraphBackend for PipewireBackend {
+    fn send(&self, message: GtkMessage) -> bool {
+        self.pw_sender.send(message).is_ok()
+    }
+
+    fn stop(&self) {
+        if let Some(thread) = self.thread.take() {
+            // The thread has already exited if it failed to connect to the remote.
+            let _ = self.pw_sender.send(GtkMessage::Terminate);
+            if thread.join().is_err() {
+                warn!("Pipewire thread panicked");
+            }
+        }
+    }
+}
+
+/// Get the name of the remote that is connected to if the specified remote is requested, for showing it to the user.
+pub(super) fn remote_display_name(remote: Option<&str>) -> String {
+    remote
+        .map(str::to_string)
+        .or_else(|| std::env::var("PIPEWIRE_REMOTE").ok())
+        .unwrap_or_else(|| DEFAULT_REMOTE.to_string())
 }
 
 /// The "main" function of the pipewire thread.
-pub(super) fn thread_main(
+fn thread_main(
     gtk_sender: glib::Sender<PipewireMessage>,
     pw_receiver: pipewire::channel::Receiver<GtkMessage>,
+    remote: Option<String>,
+    record: Option<PathBuf>,
 ) {
     let mainloop = MainLoop::new().expect("Failed to create mainloop");
-    let context = Context::new(&mainloop).expect("Failed to create context");
-    let core = Rc::new(context.connect(None).expect("Failed to connect to remote"));
+    let context = Rc::new(Context::new(&mainloop).expect("Failed to create context"));
+    let core = match context.connect(remote.as_ref().map(|remote| {
+        properties! {
+            *pipewire::keys::REMOTE_NAME => remote.as_str()
+        }
+    })) {
+        Ok(core) => Rc::new(core),
+        Err(e) => {
+            warn!(
+                "Failed to connect to remote {}: {}",
+                remote_display_name(remote.as_deref()),
+                e
+            );
+            gtk_sender
+                .send(PipewireMessage::ConnectionFailed(e.to_string()))
+                .expect("Failed to send message");
+            return;
+        }
+    };
     let registry = Rc::new(core.get_registry().expect("Failed to get registry"));
 
     // Keep proxies and their listeners alive so that we can receive info events.
     let proxies = Rc::new(RefCell::new(HashMap::new()));
 
-    let state = Rc::new(RefCell::new(State::new()));
+    let graph = Rc::new(RefCell::new(Graph::new()));
+
+    // Sequence numbers of `core.sync()` requests whose `done` event has not arrived yet.
+    let pending_syncs: Rc<RefCell<Vec<i32>>> = Rc::new(RefCell::new(Vec::new()));
+
+    // Virtual nodes created on request, keyed by their name.
+    let virtual_nodes: Rc<RefCell<HashMap<String, VirtualNodeHandle>>> =
+        Rc::new(RefCell::new(HashMap::new()));
 
     let _receiver = pw_receiver.attach(&mainloop, {
-        clone!(@strong mainloop, @weak core, @weak registry, @strong state => move |msg| match msg {
-            GtkMessage::ToggleLink { port_from, port_to } => toggle_link(port_from, port_to, &core, &registry, &state),
+        clone!(@strong mainloop, @weak context, @weak core, @weak registry, @strong proxies, @strong graph, @strong pending_syncs, @strong virtual_nodes => move |msg| match msg {
+            GtkMessage::ToggleLink { port_from, port_to, options } => toggle_link(port_from, port_to, options, &core, &registry, &graph),
+            GtkMessage::DisconnectNode { node_id } => disconnect_node(node_id, &registry, &graph),
+            GtkMessage::ConnectNodeToDefault { node_id, options } => connect_node_to_default(node_id, options, &core, &graph),
+            GtkMessage::SuspendNode { node_id } => suspend_node(node_id, &proxies),
+            GtkMessage::DestroyNode { node_id } => destroy_node(node_id, &registry, &graph),
+            GtkMessage::CreateVirtualNode(node) => create_virtual_node(node, &context, &core, &graph, &virtual_nodes),
+            GtkMessage::RemoveVirtualNode { name } => remove_virtual_node(&name, &registry, &graph, &virtual_nodes),
+            GtkMessage::Sync => request_sync(&core, &pending_syncs),
             GtkMessage::Terminate => mainloop.quit(),
         })
     });
 
+    let _core_listener = core
+        .add_listener_local()
+        .done(clone!(@strong gtk_sender, @strong pending_syncs => move |id, seq| {
+            if id != pipewire::PW_ID_CORE {
+                return;
+            }
+
+            let mut pending_syncs = pending_syncs.borrow_mut();
+            if let Some(index) = pending_syncs.iter().position(|pending| *pending == seq.seq()) {
+                pending_syncs.remove(index);
+                gtk_sender.send(PipewireMessage::Synced).expect("Failed to send message");
+            }
+        }))
+        .register();
+
+    let recorder = record.and_then(|path| match Recorder::create(&path) {
+        Ok(recorder) => {
+            info!("Recording registry events to {}", path.display());
+            Some(Rc::new(recorder))
+        }
+        Err(e) => {
+            warn!("Failed to start recording to {}: {}", path.display(), e);
+            None
+        }
+    });
+
     let _listener = registry
         .add_listener_local()
-        .global(clone!(@strong gtk_sender, @weak registry, @strong proxies, @strong state =>
-            move |global| match global.type_ {
-                ObjectType::Node => handle_node(global, &gtk_sender, &state),
-                ObjectType::Port => handle_port(global, &gtk_sender, &state),
-                ObjectType::Link => handle_link(global, &gtk_sender, &registry, &proxies, &state),
-                _ => {
-                    // Other objects are not interesting to us
+        .global(clone!(@strong gtk_sender, @weak registry, @strong proxies, @strong graph, @strong recorder =>
+            move |global| {
+                let event = RegistryEvent::Global {
+                    id: global.id,
+                    object_type: object_type_name(&global.type_),
+                    properties: global.props.as_ref().map(dict_to_map).unwrap_or_default(),
+                    permissions: model::Permissions {
+                        write: global.permissions.contains(Permission::W),
+                        execute: global.permissions.contains(Permission::X),
+                    },
+                };
+                handle_event(event, recorder.as_deref(), &graph, &gtk_sender);
+
+                match global.type_ {
+                    // Links are only added to the graph once their info arrives.
+                    ObjectType::Link => bind_link(global, &gtk_sender, &registry, &proxies, &graph, &recorder),
+                    ObjectType::Node if global.permissions.contains(Permission::X) => bind_node(global, &registry, &proxies),
+                    _ => {}
                 }
             }
         ))
-        .global_remove(clone!(@strong proxies, @strong state => move |id| {
-            if let Some(item) = state.borrow_mut().remove(id) {
-                gtk_sender.send(match item {
-                    Item::Node { .. } => PipewireMessage::NodeRemoved {id},
-                    Item::Port { node_id } => PipewireMessage::PortRemoved {id, node_id},
-                    Item::Link { .. } => PipewireMessage::LinkRemoved {id},
-                }).expect("Failed to send message");
-            } else {
+        .global_remove(clone!(@strong proxies, @strong graph, @strong recorder => move |id| {
+            handle_event(RegistryEvent::GlobalRemove { id }, recorder.as_deref(), &graph, &gtk_sender);
+            proxies.borrow_mut().remove(&id);
+        }))
+        .register();
+
+    mainloop.run();
+}
+
+/// Get the name an object type is recorded with.
+fn object_type_name(object_type: &ObjectType) -> String {
+    match object_type {
+        ObjectType::Device => "device".to_string(),
+        ObjectType::Node => "node".to_string(),
+        ObjectType::Port => "port".to_string(),
+        ObjectType::Link => "link".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Record the event if a recording was requested, then apply it to the graph.
+fn handle_event(
+    event: RegistryEvent,
+    recorder: Option<&Recorder>,
+    graph: &RefCell<Graph>,
+    sender: &glib::Sender<PipewireMessage>,
+) {
+    if let Some(recorder) = recorder {
+        recorder.record(&event);
+    }
+    apply_registry_event(&event, graph, sender);
+}
+
+/// Update the graph to reflect an event of the registry or of a bound proxy,
+/// and forward the resulting changes to the GTK thread.
+///
+/// Replayed recordings are handled by this as well, so only the recorded data may be used.
+pub(crate) fn apply_registry_event(
+    event: &RegistryEvent,
+    graph: &RefCell<Graph>,
+    sender: &glib::Sender<PipewireMessage>,
+) {
+    match event {
+        RegistryEvent::Global {
+            id,
+            object_type,
+            properties,
+            permissions,
+        } => match object_type.as_str() {
+            "device" => handle_device(*id, properties, graph, sender),
+            "node" => handle_node(*id, properties, *permissions, graph, sender),
+            "port" => handle_port(*id, properties, graph, sender),
+            _ => {
+                // Links are added once their info arrives, other objects are not interesting to us
+            }
+        },
+        RegistryEvent::GlobalRemove { id } => {
+            // Objects belonging to the removed object are removed along with it,
+            // their own removal is announced later and ignored then.
+            let removals = graph.borrow().removal_events(*id);
+            if removals.is_empty() {
                 warn!(
-                    "Attempted to remove item with id {} that is not saved in state",
+                    "Attempted to remove item with id {} that is not saved in the graph",
                     id
                 );
             }
+            for removal in removals {
+                emit(removal, graph, sender);
+            }
+        }
+        RegistryEvent::LinkInfo(info) => handle_link_info(info, graph, sender),
+    }
+}
 
-            proxies.borrow_mut().remove(&id);
-        }))
-        .register();
+/// Apply the event to the graph and forward it to the GTK thread.
+fn emit(event: GraphEvent, graph: &RefCell<Graph>, sender: &glib::Sender<PipewireMessage>) {
+    graph.borrow_mut().apply(&event);
+    sender
+        .send(PipewireMessage::GraphEvent(event))
+        .expect("Failed to send message");
+}
 
-    mainloop.run();
+/// Handle a new device being added
+fn handle_device(
+    id: u32,
+    properties: &HashMap<String, String>,
+    graph: &RefCell<Graph>,
+    sender: &glib::Sender<PipewireMessage>,
+) {
+    emit(
+        GraphEvent::DeviceAdded(model::Device {
+            id,
+            name: device_name(properties),
+            properties: properties.clone(),
+        }),
+        graph,
+        sender,
+    );
 }
 
 /// Handle a new node being added
 fn handle_node(
-    node: &GlobalObject<ForeignDict>,
+    id: u32,
+    properties: &HashMap<String, String>,
+    permissions: model::Permissions,
+    graph: &RefCell<Graph>,
     sender: &glib::Sender<PipewireMessage>,
-    state: &Rc<RefCell<State>>,
 ) {
-    let props = node
-        .props
-        .as_ref()
-        .expect("Node object is missing properties");
-
-    // Get the nicest possible name for the node, using a fallback chain of possible name attributes.
-    let name = String::from(
-        props
-            .get("node.nick")
-            .or_else(|| props.get("node.description"))
-            .or_else(|| props.get("node.name"))
-            .unwrap_or_default(),
+    emit(
+        GraphEvent::NodeAdded(model::Node {
+            id,
+            name: node_name(properties),
+            node_type: node_type(properties),
+            media_type: node_media_type(properties),
+            device_id: properties.get("device.id").and_then(|id| id.parse().ok()),
+            properties: properties.clone(),
+            permissions,
+        }),
+        graph,
+        sender,
     );
+}
+
+/// Copy all properties of a pipewire object into a map.
+fn dict_to_map(props: &ForeignDict) -> HashMap<String, String> {
+    props
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Get the nicest possible name for a device, using a fallback chain of possible name attributes.
+pub(crate) fn device_name(props: &HashMap<String, String>) -> String {
+    props
+        .get("device.description")
+        .or_else(|| props.get("device.nick"))
+        .or_else(|| props.get("device.name"))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Get the nicest possible name for a node, using a fallback chain of possible name attributes.
+pub(crate) fn node_name(props: &HashMap<String, String>) -> String {
+    props
+        .get("node.nick")
+        .or_else(|| props.get("node.description"))
+        .or_else(|| props.get("node.name"))
+        .cloned()
+        .unwrap_or_default()
+}
 
+/// Guess the media type of a node from its properties.
+pub(crate) fn node_media_type(props: &HashMap<String, String>) -> Option<MediaType> {
     // FIXME: Instead of checking these props, the "EnumFormat" parameter should be checked instead.
-    let media_type = props.get("media.class").and_then(|class| {
+    props.get("media.class").and_then(|class| {
         if class.contains("Audio") {
             Some(MediaType::Audio)
         } else if class.contains("Video") {
@@ -129,9 +393,12 @@ fn handle_node(
         } else {
             None
         }
-    });
+    })
+}
 
-    let media_class = |class: &str| {
+/// Find out whether a node is an input or an output node from its properties.
+pub(crate) fn node_type(props: &HashMap<String, String>) -> Option<NodeType> {
+    let media_class = |class: &String| {
         if class.contains("Sink") || class.contains("Input") {
             Some(NodeType::Input)
         } else if class.contains("Source") || class.contains("Output") {
@@ -141,7 +408,7 @@ fn handle_node(
         }
     };
 
-    let node_type = props
+    props
         .get("media.category")
         .and_then(|class| {
             if class.contains("Duplex") {
@@ -150,123 +417,105 @@ fn handle_node(
                 props.get("media.class").and_then(media_class)
             }
         })
-        .or_else(|| props.get("media.class").and_then(media_class));
-
-    state.borrow_mut().insert(
-        node.id,
-        Item::Node {
-            // widget: node_widget,
-            media_type,
-        },
-    );
-
-    sender
-        .send(PipewireMessage::NodeAdded {
-            id: node.id,
-            name,
-            node_type,
-        })
-        .expect("Failed to send message");
+        .or_else(|| props.get("media.class").and_then(media_class))
 }
 
 /// Handle a new port being added
 fn handle_port(
-    port: &GlobalObject<ForeignDict>,
+    id: u32,
+    properties: &HashMap<String, String>,
+    graph: &RefCell<Graph>,
     sender: &glib::Sender<PipewireMessage>,
-    state: &Rc<RefCell<State>>,
 ) {
-    let props = port
-        .props
-        .as_ref()
-        .expect("Port object is missing properties");
-    let name = props.get("port.name").unwrap_or_default().to_string();
-    let node_id: u32 = props
-        .get("node.id")
-        .expect("Port has no node.id property!")
-        .parse()
-        .expect("Could not parse node.id property");
-    let direction = if matches!(props.get("port.direction"), Some("in")) {
+    let name = properties.get("port.name").cloned().unwrap_or_default();
+    let node_id: u32 = match properties.get("node.id").and_then(|id| id.parse().ok()) {
+        Some(node_id) => node_id,
+        None => {
+            warn!("Port {} has no valid node.id property", id);
+            return;
+        }
+    };
+    let direction = if properties.get("port.direction").map(String::as_str) == Some("in") {
         Direction::Input
     } else {
         Direction::Output
     };
 
     // Find out the nodes media type so that the port can be colored.
-    let media_type = if let Some(Item::Node { media_type, .. }) = state.borrow().get(node_id) {
-        media_type.to_owned()
+    let media_type = if let Some(node) = graph.borrow().node(node_id) {
+        node.media_type
     } else {
-        warn!("Node not found for Port {}", port.id);
+        warn!("Node not found for Port {}", id);
         None
     };
 
-    // Save node_id so we can delete this port easily.
-    state.borrow_mut().insert(port.id, Item::Port { node_id });
-
-    sender
-        .send(PipewireMessage::PortAdded {
-            id: port.id,
+    emit(
+        GraphEvent::PortAdded(model::Port {
+            id,
             node_id,
             name,
             direction,
             media_type,
-        })
-        .expect("Failed to send message");
+            properties: properties.clone(),
+        }),
+        graph,
+        sender,
+    );
 }
 
-/// Handle a new link being added
-fn handle_link(
+/// Bind a proxy to a new link, so that its info is received.
+fn bind_link(
     link: &GlobalObject<ForeignDict>,
     sender: &glib::Sender<PipewireMessage>,
     registry: &Rc<Registry>,
     proxies: &Rc<RefCell<HashMap<u32, ProxyItem>>>,
-    state: &Rc<RefCell<State>>,
+    graph: &Rc<RefCell<Graph>>,
+    recorder: &Option<Rc<Recorder>>,
 ) {
     debug!(
         "New link (id:{}) appeared, setting up info listener.",
         link.id
     );
 
+    let properties = link.props.as_ref().map(dict_to_map).unwrap_or_default();
+
     let proxy: Link = registry.bind(link).expect("Failed to bind to link proxy");
     let listener = proxy
         .add_listener_local()
-        .info(clone!(@strong state, @strong sender => move |info| {
-            debug!("Received link info: {:?}", info);
-
-            let id = info.id();
-
-            let mut state = state.borrow_mut();
-            if let Some(Item::Link { .. }) = state.get(id) {
-                // Info was an update - figure out if we should notify the gtk thread
-                if info.change_mask().contains(LinkChangeMask::STATE) {
-                    sender.send(PipewireMessage::LinkStateChanged {
-                        id,
-                        active: matches!(info.state(), LinkState::Active)
-                    }).expect("Failed to send message");
+        .info(
+            clone!(@strong graph, @strong sender, @strong recorder => move |info| {
+                debug!("Received link info: {:?}", info);
+
+                // The info contains all properties of the link, while the global only contains some of them.
+                let mut properties = properties.clone();
+                if let Some(props) = info.props() {
+                    properties.extend(dict_to_map(props));
                 }
-                // TODO -- check other values that might have changed
-            } else {
-                // First time we get info. We can now notify the gtk thread of a new link.
-                let node_from = info.output_node_id();
-                let port_from = info.output_port_id();
-                let node_to = info.input_node_id();
-                let port_to = info.input_port_id();
-
-                state.insert(id, Item::Link {
-                    port_from, port_to
-                });
 
-                sender.send(PipewireMessage::LinkAdded {
-                    id,
-                    node_from,
-                    port_from,
-                    node_to,
-                    port_to,
-                    active: matches!(info.state(), LinkState::Active)
-                }).expect(
-                    "Failed to send message"
-                );
-            }
-        }))
+                let state = link_state(&info.state());
+                let change_mask = info.change_mask();
+                let event = RegistryEvent::LinkInfo(LinkInfoEvent {
+                    id: info.id(),
+                    output_node: info.output_node_id(),
+                    output_port: info.output_port_id(),
+                    input_node: info.input_node_id(),
+                    input_port: info.input_port_id(),
+                    state: state.name().to_string(),
+                    error: match state {
+                        model::LinkState::Error(message) => Some(message),
+                        _ => None,
+                    },
+                    format: link_format(info),
+                    properties,
+                    changed: LinkChanges {
+                        state: change_mask.contains(LinkChangeMask::STATE),
+                        format: change_mask.contains(LinkChangeMask::FORMAT),
+                        properties: change_mask.contains(LinkChangeMask::PROPS),
+                    },
+                });
+                handle_event(event, recorder.as_deref(), &graph, &sender);
+            }),
+        )
         .register();
 
     proxies.borrow_mut().insert(
@@ -278,44 +527,426 @@ fn handle_link(
     );
 }
 
+/// Bind a proxy to a new node, so that commands can be sent to it.
+fn bind_node(
+    node: &GlobalObject<ForeignDict>,
+    registry: &Rc<Registry>,
+    proxies: &Rc<RefCell<HashMap<u32, ProxyItem>>>,
+) {
+    match registry.bind::<Node, _>(node) {
+        Ok(proxy) => {
+            proxies
+                .borrow_mut()
+                .insert(node.id, ProxyItem::Node { proxy });
+        }
+        Err(e) => warn!("Failed to bind to node proxy (id:{}): {}", node.id, e),
+    }
+}
+
+/// Handle the info of a link, which is received when the link appeared and whenever it changes.
+fn handle_link_info(
+    info: &LinkInfoEvent,
+    graph: &RefCell<Graph>,
+    sender: &glib::Sender<PipewireMessage>,
+) {
+    let id = info.id;
+    let state =
+        model::LinkState::from_name(&info.state, info.error.as_deref()).unwrap_or_else(|| {
+            warn!("Link (id={}) has unknown state {}", id, info.state);
+            model::LinkState::Unlinked
+        });
+
+    let known = graph.borrow().link(id).is_some();
+    if known {
+        // Info was an update - figure out if we should notify the gtk thread
+        if info.changed.state {
+            emit(GraphEvent::LinkStateChanged { id, state }, graph, sender);
+        }
+        if info.changed.format {
+            emit(
+                GraphEvent::LinkFormatChanged {
+                    id,
+                    format: info.format.clone(),
+                },
+                graph,
+                sender,
+            );
+        }
+        if info.changed.properties {
+            emit(
+                GraphEvent::LinkPropertiesChanged {
+                    id,
+                    properties: info.properties.clone(),
+                },
+                graph,
+                sender,
+            );
+        }
+    } else {
+        // First time we get info. We can now notify the gtk thread of a new link.
+        emit(
+            GraphEvent::LinkAdded(model::Link {
+                id,
+                node_from: info.output_node,
+                port_from: info.output_port,
+                node_to: info.input_node,
+                port_to: info.input_port,
+                state,
+                format: info.format.clone(),
+                properties: info.properties.clone(),
+            }),
+            graph,
+            sender,
+        );
+    }
+}
+
+/// Convert the state of a link into the state used by the graph.
+fn link_state(state: &LinkState) -> model::LinkState {
+    match state {
+        LinkState::Error(message) => model::LinkState::Error(message.to_string()),
+        LinkState::Unlinked => model::LinkState::Unlinked,
+        LinkState::Init => model::LinkState::Init,
+        LinkState::Negotiating => model::LinkState::Negotiating,
+        LinkState::Allocating => model::LinkState::Allocating,
+        LinkState::Paused => model::LinkState::Paused,
+        LinkState::Active => model::LinkState::Active,
+    }
+}
+
+// Keys of the properties of a format object, see `spa/param/format.h`.
+const SPA_FORMAT_AUDIO_FORMAT: u32 = 0x10001;
+const SPA_FORMAT_AUDIO_RATE: u32 = 0x10003;
+const SPA_FORMAT_AUDIO_CHANNELS: u32 = 0x10004;
+
+/// Get the name of a raw audio sample format, see `enum spa_audio_format` in `spa/param/audio/raw.h`.
+fn sample_format_name(format: u32) -> Option<&'static str> {
+    let name = match format {
+        0x101 => "S8",
+        0x102 => "U8",
+        0x103 => "S16LE",
+        0x104 => "S16BE",
+        0x107 => "S24_32LE",
+        0x108 => "S24_32BE",
+        0x10b => "S32LE",
+        0x10c => "S32BE",
+        0x10f => "S24LE",
+        0x110 => "S24BE",
+        0x11b => "F32LE",
+        0x11c => "F32BE",
+        0x11d => "F64LE",
+        0x11e => "F64BE",
+        0x201 => "U8P",
+        0x202 => "S16P",
+        0x203 => "S24_32P",
+        0x204 => "S32P",
+        0x205 => "S24P",
+        0x206 => "F32P",
+        0x207 => "F64P",
+        0x208 => "S8P",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Read the format negotiated for a link from its info.
+///
+/// Returns `None` if no format was negotiated yet.
+fn link_format(info: &LinkInfo) -> Option<model::LinkFormat> {
+    let pod = info.format()?;
+    let (_, value) = PodDeserializer::deserialize_any_from(pod.as_bytes()).ok()?;
+
+    let mut format = model::LinkFormat::default();
+    if let Value::Object(object) = value {
+        for property in object.properties {
+            match (property.key, property.value) {
+                (SPA_FORMAT_AUDIO_FORMAT, Value::Id(Id(id))) => {
+                    format.sample_format = Some(
+                        sample_format_name(id)
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("format {:#x}", id)),
+                    );
+                }
+                (SPA_FORMAT_AUDIO_RATE, Value::Int(rate)) => format.rate = Some(rate as u32),
+                (SPA_FORMAT_AUDIO_CHANNELS, Value::Int(channels)) => {
+                    format.channels = Some(channels as u32)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Some(format)
+}
+
+/// Ask the server for a roundtrip, so that a `PipewireMessage::Synced` is sent once all previous
+/// requests have been processed and all events caused by them have been received.
+fn request_sync(core: &Rc<Core>, pending_syncs: &Rc<RefCell<Vec<i32>>>) {
+    match core.sync(0) {
+        Ok(seq) => pending_syncs.borrow_mut().push(seq.seq()),
+        Err(e) => warn!("Failed to request sync with the server: {}", e),
+    }
+}
+
 /// Toggle a link between the two specified ports.
+///
+/// If the link does not exist yet, it is created with the specified options.
 fn toggle_link(
     port_from: u32,
     port_to: u32,
+    options: LinkOptions,
     core: &Rc<Core>,
     registry: &Rc<Registry>,
-    state: &Rc<RefCell<State>>,
+    graph: &Rc<RefCell<Graph>>,
 ) {
-    let state = state.borrow_mut();
-    if let Some(id) = state.get_link_id(port_from, port_to) {
-        info!("Requesting removal of link with id {}", id);
+    let graph = graph.borrow();
+    if let Some(link) = graph.link_between(port_from, port_to) {
+        info!("Requesting removal of link with id {}", link.id);
 
         // FIXME: Handle error
-        registry.destroy_global(id);
+        registry.destroy_global(link.id);
     } else {
-        info!(
-            "Requesting creation of link from port id:{} to port id:{}",
-            port_from, port_to
+        create_link(port_from, port_to, options, core, &graph);
+    }
+}
+
+/// Create a link from the output port to the input port with the specified options.
+fn create_link(port_from: u32, port_to: u32, options: LinkOptions, core: &Rc<Core>, graph: &Graph) {
+    info!(
+        "Requesting creation of link from port id:{} to port id:{} with {:?}",
+        port_from, port_to, options
+    );
+
+    let node_from = graph
+        .port(port_from)
+        .expect("Requested port not in graph")
+        .node_id;
+    let node_to = graph
+        .port(port_to)
+        .expect("Requested port not in graph")
+        .node_id;
+
+    if let Err(e) = core.create_object::<Link, _>(
+        "link-factory",
+        &properties! {
+            "link.output.node" => node_from.to_string(),
+            "link.output.port" => port_from.to_string(),
+            "link.input.node" => node_to.to_string(),
+            "link.input.port" => port_to.to_string(),
+            "link.passive" => if options.passive { "true" } else { "false" },
+            "object.linger" => if options.linger { "true" } else { "false" }
+        },
+    ) {
+        warn!("Failed to create link: {}", e);
+    }
+}
+
+/// Remove all links from or to the specified node.
+fn disconnect_node(node_id: u32, registry: &Rc<Registry>, graph: &Rc<RefCell<Graph>>) {
+    for id in graph.borrow().links_of_node(node_id) {
+        info!("Requesting removal of link with id {}", id);
+        registry.destroy_global(id);
+    }
+}
+
+/// Link the ports of the node to the default sink or from the default source, keeping existing links.
+fn connect_node_to_default(
+    node_id: u32,
+    options: LinkOptions,
+    core: &Rc<Core>,
+    graph: &Rc<RefCell<Graph>>,
+) {
+    let graph = graph.borrow();
+    let default = match graph.default_node_for(node_id) {
+        Ok(default) => default.id,
+        Err(e) => {
+            warn!("Can not connect node {} to a default node: {}", node_id, e);
+            return;
+        }
+    };
+    let pairs = match graph.node(node_id).and_then(|node| node.node_type) {
+        Some(NodeType::Input) => graph.channel_pairs(default, node_id),
+        _ => graph.channel_pairs(node_id, default),
+    };
+
+    for (port_from, port_to) in pairs {
+        if graph.link_between(port_from, port_to).is_none() {
+            create_link(port_from, port_to, options, core, &graph);
+        }
+    }
+}
+
+/// Destroy the node, which also removes its ports and links.
+fn destroy_node(node_id: u32, registry: &Rc<Registry>, graph: &Rc<RefCell<Graph>>) {
+    if graph.borrow().node(node_id).is_none() {
+        warn!(
+            "Can not destroy node {}, as it is not in the graph",
+            node_id
         );
+        return;
+    }
+
+    info!("Requesting removal of node with id {}", node_id);
+    registry.destroy_global(node_id);
+}
 
-        let node_from = state
-            .get_node_of_port(port_from)
-            .expect("Requested port not in state");
-        let node_to = state
-            .get_node_of_port(port_to)
-            .expect("Requested port not in state");
-
-        if let Err(e) = core.create_object::<Link, _>(
-            "link-factory",
-            &properties! {
-                "link.output.node" => node_from.to_string(),
-                "link.output.port" => port_from.to_string(),
-                "link.input.node" => node_to.to_string(),
-                "link.input.port" => port_to.to_string(),
-                "object.linger" => "1"
-            },
+/// Create the virtual node, unless a virtual node with the same name already exists.
+fn create_virtual_node(
+    node: VirtualNode,
+    context: &Rc<Context<MainLoop>>,
+    core: &Rc<Core>,
+    graph: &Rc<RefCell<Graph>>,
+    virtual_nodes: &Rc<RefCell<HashMap<String, VirtualNodeHandle>>>,
+) {
+    let exists = virtual_nodes.borrow().contains_key(&node.name)
+        || virtual_nodes::nodes_of(&graph.borrow(), &node.name)
+            .next()
+            .is_some();
+    if exists {
+        info!("Virtual node {} already exists", node.name);
+        return;
+    }
+
+    info!("Requesting creation of {} {}", node.kind.name(), node.name);
+    let handle = match node.kind {
+        VirtualNodeKind::NullSink => {
+            let mut properties = Properties::new();
+            for (key, value) in node.null_sink_properties() {
+                properties.insert(key, value);
+            }
+            match core.create_object::<Node, _>("adapter", &properties) {
+                Ok(proxy) => VirtualNodeHandle::NullSink(proxy),
+                Err(e) => {
+                    warn!("Failed to create null sink {}: {}", node.name, e);
+                    return;
+                }
+            }
+        }
+        VirtualNodeKind::Loopback => match load_module(
+            context,
+            "libpipewire-module-loopback",
+            &node.loopback_arguments(),
         ) {
-            warn!("Failed to create link: {}", e);
+            Ok(module) => VirtualNodeHandle::Loopback(module),
+            Err(e) => {
+                warn!("Failed to create loopback {}: {}", node.name, e);
+                return;
+            }
+        },
+    };
+    virtual_nodes.borrow_mut().insert(node.name, handle);
+}
+
+/// Remove all nodes of the virtual node with the specified name,
+/// including null sinks that were kept from an earlier run.
+fn remove_virtual_node(
+    name: &str,
+    registry: &Rc<Registry>,
+    graph: &Rc<RefCell<Graph>>,
+    virtual_nodes: &Rc<RefCell<HashMap<String, VirtualNodeHandle>>>,
+) {
+    info!("Requesting removal of virtual node {}", name);
+    if let Some(VirtualNodeHandle::Loopback(module)) = virtual_nodes.borrow_mut().remove(name) {
+        // SAFETY: The module was loaded by us and is only destroyed here or together with the context.
+        unsafe { pipewire::sys::pw_impl_module_destroy(module.as_ptr()) };
+        return;
+    }
+
+    let ids: Vec<u32> = virtual_nodes::nodes_of(&graph.borrow(), name)
+        .map(|node| node.id)
+        .collect();
+    for id in ids {
+        registry.destroy_global(id);
+    }
+}
+
+/// Load a module into our context, as loading modules is not wrapped by the pipewire crate.
+fn load_module(
+    context: &Context<MainLoop>,
+    name: &str,
+    arguments: &str,
+) -> Result<NonNull<pipewire::sys::pw_impl_module>, String> {
+    let name = CString::new(name).map_err(|e| e.to_string())?;
+    let arguments = CString::new(arguments).map_err(|e| e.to_string())?;
+
+    // SAFETY: The strings are only read during the call.
+    // The context destroys the module when it is destroyed itself.
+    let module = unsafe {
+        pipewire::sys::pw_context_load_module(
+            context.as_ptr(),
+            name.as_ptr(),
+            arguments.as_ptr(),
+            std::ptr::null_mut(),
+        )
+    };
+    // The reason of the failure is stored in errno.
+    NonNull::new(module).ok_or_else(|| std::io::Error::last_os_error().to_string())
+}
+
+// Types and ids of commands, see `spa/utils/type.h` and `spa/node/command.h`.
+const SPA_TYPE_OBJECT: u32 = 0x0f;
+const SPA_TYPE_COMMAND_NODE: u32 = 0x30002;
+const SPA_NODE_COMMAND_SUSPEND: u32 = 0;
+
+/// A command without any properties, laid out like a `struct spa_command` from `spa/pod/command.h`.
+#[repr(C)]
+struct NodeCommand {
+    pod_size: u32,
+    pod_type: u32,
+    command_type: u32,
+    id: u32,
+}
+
+/// The start of every interface, like a `struct spa_interface` from `spa/utils/hook.h`.
+#[repr(C)]
+struct SpaInterface {
+    type_: *const std::os::raw::c_char,
+    version: u32,
+    funcs: *const std::os::raw::c_void,
+    data: *mut std::os::raw::c_void,
+}
+
+/// Suspend the node, so that it stops processing and releases its device until it is needed again.
+///
+/// The node methods are not wrapped by the pipewire crate, so the command is sent through the
+/// interface of the proxy like `pw_node_send_command()` does.
+fn suspend_node(node_id: u32, proxies: &Rc<RefCell<HashMap<u32, ProxyItem>>>) {
+    let proxies = proxies.borrow();
+    let proxy = match proxies.get(&node_id) {
+        Some(ProxyItem::Node { proxy }) => proxy,
+        _ => {
+            warn!(
+                "Can not suspend node {}, as it is not bound or not permitted",
+                node_id
+            );
+            return;
         }
+    };
+
+    info!("Requesting suspension of node with id {}", node_id);
+    let command = NodeCommand {
+        // The size of the pod body, which only consists of the command type and id.
+        pod_size: 8,
+        pod_type: SPA_TYPE_OBJECT,
+        command_type: SPA_TYPE_COMMAND_NODE,
+        id: SPA_NODE_COMMAND_SUSPEND,
+    };
+
+    // SAFETY: A proxy starts with the interface it implements, whose functions are the node methods
+    // for a node proxy. The command is only read during the call.
+    let res = unsafe {
+        let interface = proxy.upcast_ref().as_ptr() as *const SpaInterface;
+        let methods = (*interface).funcs as *const pipewire::sys::pw_node_methods;
+        (*methods).send_command.map(|send_command| {
+            send_command(
+                (*interface).data,
+                &command as *const NodeCommand as *const _,
+            )
+        })
+    };
+    match res {
+        Some(res) if res < 0 => warn!("Failed to suspend node {}: error {}", node_id, res),
+        Some(_) => {}
+        None => warn!("Node {} does not accept commands", node_id),
     }
 }
@@ -0,0 +1,246 @@
+// presets.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Presets: named sets of links that can be applied to restore a routing.
+//!
+//! Links are stored as a pair of port name patterns, see [`model::matches_pattern`](crate::model::matches_pattern),
+//! so that they still apply when the ids of ports change.
+//! Presets saved by the user are stored in a local file, separately for each remote.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    path::PathBuf,
+};
+
+use gtk::glib;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::model::Graph;
+
+/// A link of a preset.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PresetLink {
+    /// Pattern matching the full names of the output ports.
+    pub output: String,
+    /// Pattern matching the full names of the input ports.
+    pub input: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preset {
+    pub links: Vec<PresetLink>,
+    /// Keyboard shortcut applying the preset, in the format of `gtk::accelerator_parse`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accelerator: Option<String>,
+}
+
+/// The changes needed to make the links of a graph match a preset.
+#[derive(Debug, Clone, Default)]
+pub struct PresetDiff {
+    /// Pairs of `(output port, input port)` of the preset that are not linked yet.
+    pub added: Vec<(u32, u32)>,
+    /// Ids of links that are not part of the preset, which are only removed if the preset is applied exclusively.
+    pub removed: Vec<u32>,
+    /// Reasons why links of the preset could not be resolved.
+    pub errors: Vec<String>,
+}
+
+impl Preset {
+    /// Create a preset containing all links of the graph.
+    pub fn from_graph(graph: &Graph) -> Self {
+        let mut links: Vec<PresetLink> = graph
+            .links()
+            .filter_map(|link| {
+                Some(PresetLink {
+                    output: graph.port_full_name(link.port_from)?,
+                    input: graph.port_full_name(link.port_to)?,
+                })
+            })
+            .collect();
+        links.sort();
+        links.dedup();
+
+        Self {
+            links,
+            accelerator: None,
+        }
+    }
+
+    /// Parse a preset file, which contains one link per line in the form `<output> -> <input>`.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let links = content
+            .lines()
+            .enumerate()
+            .map(|(num, line)| (num + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(num, line)| {
+                line.split_once("->")
+                    .map(|(output, input)| PresetLink {
+                        output: output.trim().to_string(),
+                        input: input.trim().to_string(),
+                    })
+                    .ok_or_else(|| format!("Line {}: expected \"<output> -> <input>\"", num))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            links,
+            accelerator: None,
+        })
+    }
+
+    /// Resolve the links of the preset into pairs of `(output port, input port)` of the graph that should be linked.
+    ///
+    /// Links whose patterns can not be resolved are skipped, and the reasons are returned as well.
+    pub fn resolve(&self, graph: &Graph) -> (Vec<(u32, u32)>, Vec<String>) {
+        let mut pairs = Vec::new();
+        let mut errors = Vec::new();
+        for link in &self.links {
+            match graph.resolve_pairs(&link.output, &link.input) {
+                Ok(resolved) => pairs.extend(resolved),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        (pairs, errors)
+    }
+
+    /// Compare the links of the graph with the preset.
+    pub fn diff(&self, graph: &Graph) -> PresetDiff {
+        let (pairs, errors) = self.resolve(graph);
+        let pairs: HashSet<(u32, u32)> = pairs.into_iter().collect();
+
+        let mut added: Vec<(u32, u32)> = pairs
+            .iter()
+            .copied()
+            .filter(|(port_from, port_to)| graph.link_between(*port_from, *port_to).is_none())
+            .collect();
+        added.sort_unstable();
+        let mut removed: Vec<u32> = graph
+            .links()
+            .filter(|link| !pairs.contains(&(link.port_from, link.port_to)))
+            .map(|link| link.id)
+            .collect();
+        removed.sort_unstable();
+
+        PresetDiff {
+            added,
+            removed,
+            errors,
+        }
+    }
+}
+
+/// The presets of all remotes, keyed by the display name of the remote and then by the name of the preset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Presets {
+    remotes: HashMap<String, BTreeMap<String, Preset>>,
+}
+
+impl Presets {
+    /// Get the path of the file the presets are stored in.
+    fn path() -> PathBuf {
+        glib::user_config_dir().join("helvum").join("presets.json")
+    }
+
+    /// Load the stored presets, or start without any presets if there are none or they can not be read.
+    pub fn load() -> Self {
+        let path = Self::path();
+        if !path.exists() {
+            return Self::default();
+        }
+
+        std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("Failed to load presets from {}: {}", path.display(), e);
+                Self::default()
+            })
+    }
+
+    /// Store the presets, so that they are available again the next time helvum is started.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, remote: &str, name: &str) -> Option<&Preset> {
+        self.remotes.get(remote)?.get(name)
+    }
+
+    pub fn get_mut(&mut self, remote: &str, name: &str) -> Option<&mut Preset> {
+        self.remotes.get_mut(remote)?.get_mut(name)
+    }
+
+    /// Get the names of all presets of the remote, in alphabetical order.
+    pub fn names(&self, remote: &str) -> Vec<String> {
+        self.remotes
+            .get(remote)
+            .map(|presets| presets.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Store the preset under the specified name, replacing any preset with the same name.
+    pub fn set(&mut self, remote: &str, name: &str, preset: Preset) {
+        self.remotes
+            .entry(remote.to_string())
+            .or_default()
+            .insert(name.to_string(), preset);
+    }
+
+    pub fn remove(&mut self, remote: &str, name: &str) -> Option<Preset> {
+        let presets = self.remotes.get_mut(remote)?;
+        let preset = presets.remove(name);
+        if presets.is_empty() {
+            self.remotes.remove(remote);
+        }
+
+        preset
+    }
+
+    /// Give the preset a new name, which must not be used by another preset of the remote yet.
+    pub fn rename(&mut self, remote: &str, name: &str, new_name: &str) -> Result<(), String> {
+        if name == new_name {
+            return Ok(());
+        }
+        let presets = self
+            .remotes
+            .get_mut(remote)
+            .ok_or_else(|| format!("No preset named \"{}\"", name))?;
+        if presets.contains_key(new_name) {
+            return Err(format!("A preset named \"{}\" already exists", new_name));
+        }
+        let preset = presets
+            .remove(name)
+            .ok_or_else(|| format!("No preset named \"{}\"", name))?;
+        presets.insert(new_name.to_string(), preset);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+// recording.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Recordings of the events the pipewire thread receives from the server.
+//!
+//! Whether a bug shows up often depends on the order in which objects appear and disappear,
+//! so the raw events are recorded with the time they arrived at, one JSON object per line.
+//! A recording can then be replayed without a pipewire server, see `backend::replay`.
+
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    time::Instant,
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{LinkFormat, Permissions};
+
+/// An event of the registry or of a proxy bound by the pipewire thread.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum RegistryEvent {
+    /// A global object appeared in the registry.
+    Global {
+        id: u32,
+        /// Type of the object, like `node` or `port`.
+        #[serde(rename = "type")]
+        object_type: String,
+        #[serde(default)]
+        properties: HashMap<String, String>,
+        /// What helvum may do with the object, which older recordings do not contain.
+        #[serde(default)]
+        permissions: Permissions,
+    },
+    /// A global object was removed from the registry.
+    GlobalRemove { id: u32 },
+    /// The info of a link was received, either for the first time or because parts of it changed.
+    LinkInfo(LinkInfoEvent),
+}
+
+/// The info of a link.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkInfoEvent {
+    pub id: u32,
+    pub output_node: u32,
+    pub output_port: u32,
+    pub input_node: u32,
+    pub input_port: u32,
+    /// Name of the state of the link, as used by `LinkState::from_name`.
+    pub state: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub format: Option<LinkFormat>,
+    /// All properties of the link, from its global and its info.
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+    #[serde(default)]
+    pub changed: LinkChanges,
+}
+
+/// The parts of the info of a link that changed since the last info was received.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct LinkChanges {
+    pub state: bool,
+    pub format: bool,
+    pub properties: bool,
+}
+
+/// An event together with the time it was received at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Seconds since the recording was started.
+    pub time: f64,
+    #[serde(flatten)]
+    pub event: RegistryEvent,
+}
+
+/// Writes received events into a recording file.
+pub struct Recorder {
+    file: RefCell<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Start a new recording in the file at the specified path, replacing the file if it exists.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            file: RefCell::new(File::create(path)?),
+            start: Instant::now(),
+        })
+    }
+
+    /// Append the event to the recording.
+    ///
+    /// Every event is written immediately, so that the recording is complete even if helvum crashes.
+    pub fn record(&self, event: &RegistryEvent) {
+        let recorded = RecordedEvent {
+            time: self.start.elapsed().as_secs_f64(),
+            event: event.clone(),
+        };
+
+        let result = serde_json::to_string(&recorded)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.file.borrow_mut(), "{}", line));
+        if let Err(e) = result {
+            warn!("Failed to record event: {}", e);
+        }
+    }
+}
+
+/// Read all events of a recording, ordered by the time they were received at.
+pub fn load(path: &Path) -> Result<Vec<RecordedEvent>, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+    let mut events = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str::<RecordedEvent>(line)
+                .map_err(|e| format!("Line {}: {}", index + 1, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // The sort is stable, so events received at the same time keep their order.
+    events.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+
+    Ok(events)
+}
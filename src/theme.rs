@@ -0,0 +1,221 @@
+// theme.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Loading of our stylesheets and following the color scheme and contrast chosen by the user.
+//!
+//! Colors in `style.css` are derived from the colors of the theme, so they adapt to light and dark themes.
+//! `style-high-contrast.css` is loaded on top of it while a high contrast theme is used.
+//! The color scheme of the system is followed unless another one is chosen in the preferences.
+
+use std::{cell::Cell, rc::Rc};
+
+use gtk::{
+    gdk, gio,
+    glib::{self, clone},
+    prelude::*,
+};
+use log::{info, warn};
+
+static STYLE: &str = include_str!("style.css");
+static HIGH_CONTRAST_STYLE: &str = include_str!("style-high-contrast.css");
+
+/// Namespace and key of the color scheme in the settings of the freedesktop settings portal.
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// The color scheme chosen in the preferences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    /// Follow the color scheme of the system.
+    System,
+    Light,
+    Dark,
+}
+
+impl ColorScheme {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::System),
+            "light" => Some(Self::Light),
+            "dark" => Some(Self::Dark),
+            _ => None,
+        }
+    }
+}
+
+/// Our stylesheets along with the color scheme they are shown in.
+pub struct Theme {
+    settings: gtk::Settings,
+    color_scheme: Rc<Cell<ColorScheme>>,
+    /// Whether the system prefers a dark color scheme.
+    system_prefers_dark: Rc<Cell<bool>>,
+    /// Proxy of the settings portal, as the color scheme of the system is only followed while it is alive.
+    _settings_portal: Option<gio::DBusProxy>,
+}
+
+impl Theme {
+    /// Show the application in the specified color scheme from now on.
+    pub fn set_color_scheme(&self, color_scheme: ColorScheme) {
+        self.color_scheme.set(color_scheme);
+        apply_color_scheme(&self.settings, color_scheme, self.system_prefers_dark.get());
+    }
+}
+
+/// Load our stylesheets for the display and keep following the color scheme and contrast of the system.
+pub fn setup(display: &gdk::Display) -> Theme {
+    let provider = gtk::CssProvider::new();
+    provider.load_from_data(STYLE.as_bytes());
+    gtk::StyleContext::add_provider_for_display(
+        display,
+        &provider,
+        gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+
+    let settings = gtk::Settings::for_display(display);
+
+    let high_contrast_provider = gtk::CssProvider::new();
+    high_contrast_provider.load_from_data(HIGH_CONTRAST_STYLE.as_bytes());
+    let update_contrast = clone!(@weak display, @strong high_contrast_provider => move |settings: &gtk::Settings| {
+        let high_contrast = settings
+            .gtk_theme_name()
+            .map_or(false, |name| name.starts_with("HighContrast"));
+
+        gtk::StyleContext::remove_provider_for_display(&display, &high_contrast_provider);
+        if high_contrast {
+            gtk::StyleContext::add_provider_for_display(
+                &display,
+                &high_contrast_provider,
+                gtk::STYLE_PROVIDER_PRIORITY_APPLICATION + 1,
+            );
+        }
+    });
+    update_contrast(&settings);
+    settings.connect_gtk_theme_name_notify(update_contrast);
+
+    let color_scheme = Rc::new(Cell::new(ColorScheme::System));
+    let system_prefers_dark = Rc::new(Cell::new(settings.is_gtk_application_prefer_dark_theme()));
+    let settings_portal = follow_color_scheme(&settings, &color_scheme, &system_prefers_dark);
+
+    Theme {
+        settings,
+        color_scheme,
+        system_prefers_dark,
+        _settings_portal: settings_portal,
+    }
+}
+
+/// Prefer the dark variant of the theme if the color scheme asks for it.
+fn apply_color_scheme(
+    settings: &gtk::Settings,
+    color_scheme: ColorScheme,
+    system_prefers_dark: bool,
+) {
+    let prefer_dark = match color_scheme {
+        ColorScheme::System => system_prefers_dark,
+        ColorScheme::Light => false,
+        ColorScheme::Dark => true,
+    };
+    settings.set_gtk_application_prefer_dark_theme(prefer_dark);
+}
+
+/// Keep track of whether the user chose a dark color scheme for the whole system,
+/// and apply it while the color scheme of the system is followed.
+///
+/// The color scheme is read from the freedesktop settings portal.
+/// If it is not available or the user has no preference, the `gtk-application-prefer-dark-theme` setting
+/// is taken as the preference of the system.
+fn follow_color_scheme(
+    settings: &gtk::Settings,
+    color_scheme: &Rc<Cell<ColorScheme>>,
+    system_prefers_dark: &Rc<Cell<bool>>,
+) -> Option<gio::DBusProxy> {
+    let proxy = match gio::DBusProxy::for_bus_sync(
+        gio::BusType::Session,
+        gio::DBusProxyFlags::DO_NOT_LOAD_PROPERTIES,
+        None,
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        "org.freedesktop.portal.Settings",
+        None::<&gio::Cancellable>,
+    ) {
+        Ok(proxy) => proxy,
+        Err(e) => {
+            info!(
+                "Settings portal not available, not following the system color scheme: {}",
+                e
+            );
+            return None;
+        }
+    };
+
+    let gtk_prefers_dark = system_prefers_dark.get();
+    let apply = clone!(@weak settings, @strong color_scheme, @strong system_prefers_dark => move |value: &glib::Variant| {
+        // 0 means no preference, 1 prefers dark and 2 prefers light.
+        let prefer_dark = match unwrap_variant(value).get::<u32>() {
+            Some(1) => true,
+            Some(2) => false,
+            _ => gtk_prefers_dark,
+        };
+        system_prefers_dark.set(prefer_dark);
+        apply_color_scheme(&settings, color_scheme.get(), prefer_dark);
+    });
+
+    proxy.call(
+        "Read",
+        Some(&(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY).to_variant()),
+        gio::DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+        clone!(@strong apply => move |result| match result {
+            // The result is a tuple containing the value.
+            Ok(result) => apply(&result.child_value(0)),
+            Err(e) => info!("Failed to read the system color scheme: {}", e),
+        }),
+    );
+
+    if let Err(e) = proxy.connect_local("g-signal", false, move |args| {
+        // Args always look like this: &[proxy, sender_name, signal_name, parameters]
+        let signal_name = args[2].get::<String>().ok()?;
+        let parameters = args[3].get::<glib::Variant>().ok()?;
+        if signal_name == "SettingChanged"
+            && parameters.child_value(0).str() == Some(APPEARANCE_NAMESPACE)
+            && parameters.child_value(1).str() == Some(COLOR_SCHEME_KEY)
+        {
+            apply(&parameters.child_value(2));
+        }
+        None
+    }) {
+        warn!(
+            "Failed to connect to \"g-signal\" signal of the settings portal: {}",
+            e
+        );
+    }
+
+    Some(proxy)
+}
+
+/// Get the innermost value of a variant that may be boxed into variants several times,
+/// as some versions of the settings portal do.
+fn unwrap_variant(value: &glib::Variant) -> glib::Variant {
+    let mut value = value.clone();
+    while let Some(inner) = value.as_variant() {
+        value = inner;
+    }
+    value
+}
@@ -0,0 +1,277 @@
+// command_palette.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! A searchable list of commands for connecting ports by typing their names.
+//!
+//! Queries look like `connect <output> to <input>`, where both port names are matched fuzzily
+//! against the `<node name>:<port name>` of all ports.
+
+use std::{cell::RefCell, cmp::Reverse, rc::Rc};
+
+use gtk::{gdk, glib::clone, prelude::*};
+use pipewire::spa::Direction;
+
+use crate::model::Graph;
+
+/// Maximum number of results shown at once.
+const MAX_RESULTS: usize = 50;
+/// Maximum number of ports per side that are combined into connect results.
+const MAX_PORTS: usize = 25;
+
+/// What happens when a result is activated.
+#[derive(Debug, Clone)]
+enum PaletteAction {
+    /// Complete the query with the output port and let the user continue with the input port.
+    ChooseOutput { name: String },
+    /// Create the link between the two ports, or remove it if it already exists.
+    ToggleLink {
+        output: u32,
+        input: u32,
+        output_name: String,
+        input_name: String,
+    },
+}
+
+impl PaletteAction {
+    fn description(&self, graph: &Graph) -> String {
+        match self {
+            Self::ChooseOutput { name } => format!("Connect {} to …", name),
+            Self::ToggleLink {
+                output,
+                input,
+                output_name,
+                input_name,
+            } => {
+                let verb = if graph.link_between(*output, *input).is_some() {
+                    "Disconnect"
+                } else {
+                    "Connect"
+                };
+                format!("{} {} to {}", verb, output_name, input_name)
+            }
+        }
+    }
+}
+
+/// Show the command palette for the graph.
+///
+/// `toggle_link` is called with the ids of the output and input port when a link should be toggled.
+pub fn show_command_palette(
+    parent: Option<&gtk::Window>,
+    graph: Graph,
+    toggle_link: impl Fn(u32, u32) + 'static,
+) {
+    let entry = gtk::SearchEntryBuilder::new()
+        .placeholder_text("connect <output> to <input>")
+        .build();
+    let list = gtk::ListBox::new();
+    let scrollwindow = gtk::ScrolledWindowBuilder::new()
+        .child(&list)
+        .vexpand(true)
+        .build();
+
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    vbox.append(&entry);
+    vbox.append(&scrollwindow);
+
+    let window = gtk::WindowBuilder::new()
+        .title("Connect Ports")
+        .modal(true)
+        .default_width(600)
+        .default_height(400)
+        .child(&vbox)
+        .build();
+    window.set_transient_for(parent);
+
+    let graph = Rc::new(graph);
+    let actions = Rc::new(RefCell::new(Vec::new()));
+
+    let activate = Rc::new(
+        clone!(@weak window, @weak entry, @strong actions => move |index: usize| {
+            let action = actions.borrow().get(index).cloned();
+            match action {
+                Some(PaletteAction::ChooseOutput { name }) => {
+                    entry.set_text(&format!("connect {} to ", name));
+                    entry.grab_focus();
+                    entry.set_position(-1);
+                }
+                Some(PaletteAction::ToggleLink { output, input, .. }) => {
+                    toggle_link(output, input);
+                    window.close();
+                }
+                None => {}
+            }
+        }),
+    );
+
+    entry.connect_search_changed(
+        clone!(@weak list, @strong actions, @strong graph => move |entry| {
+            update_results(&list, &actions, &graph, &entry.text());
+        }),
+    );
+    entry.connect_activate(clone!(@strong activate => move |_| activate(0)));
+    list.connect_row_activated(clone!(@strong activate => move |_, row| {
+        activate(row.index() as usize)
+    }));
+
+    // Close the palette on escape, no matter which of its widgets has the focus.
+    let key_controller = gtk::EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    key_controller.connect_key_pressed(
+        clone!(@weak window => @default-return gtk::Inhibit(false), move |_, key, _, _| {
+            if key == gdk::keys::constants::Escape {
+                window.close();
+                gtk::Inhibit(true)
+            } else {
+                gtk::Inhibit(false)
+            }
+        }),
+    );
+    window.add_controller(&key_controller);
+
+    update_results(&list, &actions, &graph, "");
+    window.present();
+    entry.grab_focus();
+}
+
+/// Split a query into the part matching the output port and the part matching the input port, if there is one.
+fn parse_query(query: &str) -> (&str, Option<&str>) {
+    let query = query.trim_start();
+    let query = query
+        .strip_prefix("connect ")
+        .or_else(|| query.strip_prefix("Connect "))
+        .unwrap_or(query);
+
+    match query.split_once(" to ").or_else(|| query.split_once("->")) {
+        Some((output, input)) => (output.trim(), Some(input.trim())),
+        None => (query.trim(), None),
+    }
+}
+
+/// Score how well `query` fuzzily matches `candidate`, or `None` if it does not match at all.
+///
+/// All characters of the query except whitespace must appear in the candidate in the same order,
+/// ignoring case. Consecutive characters and characters at the start of a word score higher.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<u32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+
+    let mut score = 0;
+    let mut start = 0;
+    let mut previous_match = None;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let index = (start..candidate.len()).find(|&i| candidate[i] == c)?;
+
+        score += 1;
+        if index > 0 && previous_match == Some(index - 1) {
+            score += 5;
+        }
+        if index == 0 || !candidate[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        previous_match = Some(index);
+        start = index + 1;
+    }
+
+    Some(score)
+}
+
+/// Get the score, id and full name of all ports with the specified direction matching the query,
+/// ordered by descending score and then by name.
+fn matching_ports(graph: &Graph, query: &str, direction: Direction) -> Vec<(u32, u32, String)> {
+    let mut ports: Vec<(u32, u32, String)> = graph
+        .ports()
+        .filter(|port| port.direction == direction)
+        .filter_map(|port| {
+            let name = graph.port_full_name(port.id)?;
+            let score = fuzzy_score(query, &name)?;
+            Some((score, port.id, name))
+        })
+        .collect();
+    ports.sort_by(|(a_score, _, a_name), (b_score, _, b_name)| {
+        b_score.cmp(a_score).then_with(|| a_name.cmp(b_name))
+    });
+
+    ports
+}
+
+/// Replace the results shown in the list with the results of the query.
+fn update_results(
+    list: &gtk::ListBox,
+    actions: &RefCell<Vec<PaletteAction>>,
+    graph: &Graph,
+    query: &str,
+) {
+    while let Some(row) = list.first_child() {
+        list.remove(&row);
+    }
+
+    let mut actions = actions.borrow_mut();
+    actions.clear();
+
+    let (output_query, input_query) = parse_query(query);
+    let outputs = matching_ports(graph, output_query, Direction::Output);
+    match input_query {
+        None => {
+            actions.extend(
+                outputs
+                    .into_iter()
+                    .take(MAX_RESULTS)
+                    .map(|(_, _, name)| PaletteAction::ChooseOutput { name }),
+            );
+        }
+        Some(input_query) => {
+            let inputs = matching_ports(graph, input_query, Direction::Input);
+
+            let mut pairs: Vec<_> = outputs
+                .iter()
+                .take(MAX_PORTS)
+                .flat_map(|output| {
+                    inputs
+                        .iter()
+                        .take(MAX_PORTS)
+                        .map(move |input| (output.0 + input.0, output, input))
+                })
+                .collect();
+            // The sort is stable, so pairs with the same score stay ordered by name.
+            pairs.sort_by_key(|(score, _, _)| Reverse(*score));
+
+            actions.extend(pairs.into_iter().take(MAX_RESULTS).map(
+                |(_, (_, output, output_name), (_, input, input_name))| PaletteAction::ToggleLink {
+                    output: *output,
+                    input: *input,
+                    output_name: output_name.clone(),
+                    input_name: input_name.clone(),
+                },
+            ));
+        }
+    }
+
+    for action in actions.iter() {
+        let label = gtk::LabelBuilder::new()
+            .label(&action.description(graph))
+            .xalign(0.0)
+            .build();
+        list.append(&label);
+    }
+
+    if let Some(row) = list.row_at_index(0) {
+        list.select_row(Some(&row));
+    }
+}
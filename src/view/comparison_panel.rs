@@ -0,0 +1,200 @@
+// comparison_panel.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! A side panel listing how the shown links differ from a reference, such as a preset,
+//! with a button for fixing each difference.
+
+use gtk::{
+    glib::{self, clone, subclass::Signal},
+    prelude::*,
+    subclass::prelude::*,
+};
+
+/// How the shown links differ from the reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifferenceKind {
+    /// A link of the reference does not exist.
+    Missing,
+    /// A link exists that is not part of the reference.
+    Extra,
+    /// An entry of the reference can not be matched to any ports, so it can not be fixed here.
+    Unresolved,
+}
+
+mod imp {
+    use super::*;
+
+    use once_cell::{sync::Lazy, unsync::OnceCell};
+
+    #[derive(Default)]
+    pub struct ComparisonPanel {
+        pub(super) title: OnceCell<gtk::Label>,
+        pub(super) list: OnceCell<gtk::ListBox>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ComparisonPanel {
+        const NAME: &'static str = "ComparisonPanel";
+        type Type = super::ComparisonPanel;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.set_css_name("comparisonpanel");
+        }
+    }
+
+    impl ObjectImpl for ComparisonPanel {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            obj.set_orientation(gtk::Orientation::Vertical);
+            obj.set_spacing(6);
+            obj.set_width_request(300);
+
+            let title = gtk::LabelBuilder::new()
+                .xalign(0.0)
+                .wrap(true)
+                .hexpand(true)
+                .build();
+            title.add_css_class("heading");
+            let close_button = gtk::ButtonBuilder::new()
+                .icon_name("window-close-symbolic")
+                .has_frame(false)
+                .tooltip_text("Stop Comparing")
+                .build();
+            close_button.connect_clicked(clone!(@weak obj => move |_| {
+                obj.emit_by_name("closed", &[])
+                    .expect("Failed to emit \"closed\" signal");
+            }));
+            let header = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            header.append(&title);
+            header.append(&close_button);
+            obj.append(&header);
+
+            let list = gtk::ListBoxBuilder::new()
+                .selection_mode(gtk::SelectionMode::None)
+                .build();
+            let scrollwindow = gtk::ScrolledWindowBuilder::new()
+                .child(&list)
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .vexpand(true)
+                .build();
+            obj.append(&scrollwindow);
+
+            let _ = self.title.set(title);
+            let _ = self.list.set(list);
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    // Emitted with the index of a difference when its "Fix" button is clicked.
+                    Signal::builder(
+                        "fix",
+                        &[u32::static_type().into()],
+                        <()>::static_type().into(),
+                    )
+                    .build(),
+                    // Emitted when the user wants to stop comparing.
+                    Signal::builder("closed", &[], <()>::static_type().into()).build(),
+                ]
+            });
+
+            SIGNALS.as_ref()
+        }
+    }
+
+    impl WidgetImpl for ComparisonPanel {}
+    impl BoxImpl for ComparisonPanel {}
+}
+
+glib::wrapper! {
+    pub struct ComparisonPanel(ObjectSubclass<imp::ComparisonPanel>)
+        @extends gtk::Box, gtk::Widget,
+        @implements gtk::Orientable;
+}
+
+impl ComparisonPanel {
+    pub fn new() -> Self {
+        glib::Object::new(&[]).expect("Failed to create ComparisonPanel")
+    }
+
+    /// Show the differences to the reference with the specified name,
+    /// each described by its kind and a description of the affected link.
+    pub fn set_differences(&self, reference: &str, differences: &[(DifferenceKind, String)]) {
+        let private = imp::ComparisonPanel::from_instance(self);
+        let (title, list) = match (private.title.get(), private.list.get()) {
+            (Some(title), Some(list)) => (title, list),
+            _ => return,
+        };
+
+        title.set_text(&match differences.len() {
+            0 => format!("The links match {}", reference),
+            1 => format!("1 difference to {}", reference),
+            n => format!("{} differences to {}", n, reference),
+        });
+
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+        for (index, (kind, description)) in differences.iter().enumerate() {
+            let kind_label = match kind {
+                DifferenceKind::Missing => "Missing",
+                DifferenceKind::Extra => "Extra",
+                DifferenceKind::Unresolved => "Not found",
+            };
+            let label = gtk::LabelBuilder::new()
+                .label(&format!("{}: {}", kind_label, description))
+                .xalign(0.0)
+                .wrap(true)
+                .hexpand(true)
+                .build();
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+            row.set_margin_top(3);
+            row.set_margin_bottom(3);
+            row.append(&label);
+
+            if *kind != DifferenceKind::Unresolved {
+                let fix_button = gtk::ButtonBuilder::new()
+                    .label("Fix")
+                    .valign(gtk::Align::Center)
+                    .tooltip_text(match kind {
+                        DifferenceKind::Missing => "Create the link",
+                        _ => "Remove the link",
+                    })
+                    .build();
+                let index = index as u32;
+                fix_button.connect_clicked(clone!(@weak self as panel => move |_| {
+                    panel
+                        .emit_by_name("fix", &[&index])
+                        .expect("Failed to emit \"fix\" signal");
+                }));
+                row.append(&fix_button);
+            }
+
+            list.append(&row);
+        }
+    }
+}
+
+impl Default for ComparisonPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,82 @@
+// dialog.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Helpers for building dialogs, so that all dialogs of the application look alike.
+
+use gtk::prelude::*;
+
+/// Space between the content of a dialog and its border.
+const MARGIN: i32 = 12;
+
+/// Create a modal dialog for the window with buttons given by their mnemonic label and response.
+///
+/// If one of the buttons accepts the dialog, it is the default that entries can activate.
+pub fn new_dialog(
+    title: &str,
+    parent: Option<&gtk::Window>,
+    buttons: &[(&str, gtk::ResponseType)],
+) -> gtk::Dialog {
+    let dialog = gtk::Dialog::with_buttons(
+        Some(title),
+        parent,
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        buttons,
+    );
+    if buttons
+        .iter()
+        .any(|(_, response)| *response == gtk::ResponseType::Accept)
+    {
+        dialog.set_default_response(gtk::ResponseType::Accept);
+    }
+    dialog
+}
+
+/// Show the widget in the content area of the dialog, keeping some space to its border.
+pub fn set_dialog_content(dialog: &gtk::Dialog, content: &impl IsA<gtk::Widget>) {
+    content.set_margin_start(MARGIN);
+    content.set_margin_end(MARGIN);
+    content.set_margin_top(MARGIN);
+    content.set_margin_bottom(MARGIN);
+    dialog.content_area().append(content);
+}
+
+/// Create a label for the widget, which focuses the widget when its mnemonic is used.
+pub fn mnemonic_label(label: &str, widget: &impl IsA<gtk::Widget>) -> gtk::Label {
+    gtk::LabelBuilder::new()
+        .label(label)
+        .use_underline(true)
+        .mnemonic_widget(widget)
+        .xalign(0.0)
+        .build()
+}
+
+/// Create a grid with a row for each of the widgets, next to a label with the specified mnemonic.
+///
+/// Widgets without a label of their own can be attached in rows below the labeled ones.
+pub fn form_grid(rows: &[(&str, &gtk::Widget)]) -> gtk::Grid {
+    let grid = gtk::GridBuilder::new()
+        .row_spacing(6)
+        .column_spacing(12)
+        .build();
+    for (row, (label, widget)) in rows.iter().enumerate() {
+        grid.attach(&mnemonic_label(label, *widget), 0, row as i32, 1, 1);
+        grid.attach(*widget, 1, row as i32, 1, 1);
+    }
+    grid
+}
@@ -17,32 +17,158 @@
 //
 // SPDX-License-Identifier: GPL-3.0-only
 
-use super::{Node, Port};
+use super::{port::link_options_for_modifiers, Node, Port, PortNameStyle};
 
 use gtk::{
-    glib::{self, clone},
-    graphene, gsk,
+    cairo, gdk,
+    glib::{self, clone, subclass::Signal},
+    graphene, gsk, pango,
     prelude::*,
     subclass::prelude::*,
 };
 use log::{error, warn};
+use pipewire::spa::Direction;
 
-use std::{cmp::Ordering, collections::HashMap};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    model::{LinkFormat, LinkState},
+    node_labels::NodeLabel,
+    LinkOptions, NodeType,
+};
+
+/// Distance a node is moved by a single key press.
+const KEYBOARD_MOVE_STEP: f32 = 20.0;
+/// Maximum distance of the pointer from a link for showing the tooltip of the link.
+const LINK_TOOLTIP_DISTANCE: f64 = 5.0;
+
+// Sizes of the drawn graph, relative to the font size so that they scale with it.
+/// Distance between two lines of the background grid, in em.
+const GRID_SPACING: f64 = 1.4;
+/// Width of active links, in em. Passive links are half as wide.
+const LINK_WIDTH: f64 = 0.14;
+/// Length of the dashes and the gaps between them of inactive links, in em.
+const LINK_DASH: [f64; 2] = [0.7, 0.35];
+
+/// Font size used if the font of the widget can not be determined, in pixels.
+const DEFAULT_FONT_SIZE: f64 = 14.6;
+
+/// Range of the factor the graph can be zoomed by.
+pub const MIN_ZOOM: f64 = 0.5;
+pub const MAX_ZOOM: f64 = 2.0;
+
+/// How nodes are aligned with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeAlignment {
+    /// Align the left edges of the nodes.
+    Left,
+    /// Align the right edges of the nodes.
+    Right,
+    /// Align the top edges of the nodes.
+    Top,
+}
+
+/// Style of the background grid, with sizes in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GridStyle {
+    size: (i32, i32),
+    spacing: f64,
+    color: (f64, f64, f64, f64),
+}
+
+/// Style of the drawn links, with sizes in pixels and colors as `(red, green, blue, alpha)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LinkStyle {
+    lingering: (f64, f64, f64, f64),
+    transient: (f64, f64, f64, f64),
+    error: (f64, f64, f64, f64),
+    selected: (f64, f64, f64, f64),
+    /// Color of links that are not part of the reference the links are compared with.
+    extra: (f64, f64, f64, f64),
+    /// Color of the ghost lines of links of the reference that do not exist.
+    missing: (f64, f64, f64, f64),
+    width: f64,
+    dash: [f64; 2],
+}
+
+impl LinkStyle {
+    /// Get the width and dash pattern a link is drawn with.
+    fn line(&self, link: &Link) -> (f64, &[f64]) {
+        // Passive links are drawn thinner, inactive links are dashed.
+        let width = if link.passive {
+            self.width / 2.0
+        } else {
+            self.width
+        };
+        let dash: &[f64] = if link.state.is_active() {
+            &[]
+        } else {
+            &self.dash
+        };
+        (width, dash)
+    }
+}
+
+/// How the shown links differ from a reference they are compared with, such as a preset.
+#[derive(Debug, Clone, Default)]
+pub struct LinkComparison {
+    /// Links of the reference that do not exist, which are drawn as ghost lines.
+    pub missing: Vec<crate::PipewireLink>,
+    /// Ids of the links that are not part of the reference, which are drawn in a warning color.
+    pub extra: HashSet<u32>,
+}
 
-use crate::NodeType;
+/// A link drawn between two ports, along with its current state.
+#[derive(Debug, Clone)]
+pub(super) struct Link {
+    pub(super) link: crate::PipewireLink,
+    pub(super) state: LinkState,
+    pub(super) format: Option<LinkFormat>,
+    pub(super) passive: bool,
+    pub(super) linger: bool,
+}
 
 mod imp {
     use super::*;
 
-    use std::{cell::RefCell, rc::Rc};
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
 
     use log::warn;
+    use once_cell::{sync::Lazy, unsync::OnceCell};
 
     #[derive(Default)]
     pub struct GraphView {
         pub(super) nodes: RefCell<HashMap<u32, Node>>,
-        /// Stores the link and whether it is currently active.
-        pub(super) links: RefCell<HashMap<u32, (crate::PipewireLink, bool)>>,
+        pub(super) links: RefCell<HashMap<u32, super::Link>>,
+        /// The port a link is being created from with the keyboard, if any.
+        pub(super) link_source: Cell<Option<u32>>,
+        /// The link selected with the keyboard, if any.
+        pub(super) selected_link: Cell<Option<u32>>,
+        pub(super) port_name_style: Cell<PortNameStyle>,
+        /// Ids of the nodes selected for being moved and arranged together.
+        pub(super) selected_nodes: RefCell<HashSet<u32>>,
+        /// Whether dragged nodes are placed on the background grid.
+        pub(super) snap_to_grid: Cell<bool>,
+        /// Rendered links, which are drawn again only when they change or their nodes move.
+        pub(super) link_render_nodes: RefCell<HashMap<u32, gsk::RenderNode>>,
+        /// The style the cached links were rendered with.
+        pub(super) link_style: Cell<Option<LinkStyle>>,
+        /// Allocations of all nodes as `(x, y, width, height)` when the links were last drawn.
+        pub(super) node_allocations: RefCell<HashMap<u32, (i32, i32, i32, i32)>>,
+        /// The rendered background grid, along with the style it was rendered with.
+        pub(super) grid_render_node: RefCell<Option<(GridStyle, gsk::RenderNode)>>,
+        /// The differences to the reference the links are compared with, if they are compared.
+        pub(super) comparison: RefCell<Option<LinkComparison>>,
+        /// Factor the graph is zoomed by.
+        pub(super) zoom: Cell<f64>,
+        /// Provider scaling the font of the graphview, and with it all sizes derived from it, by the zoom.
+        pub(super) zoom_provider: OnceCell<gtk::CssProvider>,
     }
 
     #[glib::object_subclass]
@@ -62,7 +188,11 @@ mod imp {
         fn constructed(&self, obj: &Self::Type) {
             self.parent_constructed(obj);
 
-            let drag_state = Rc::new(RefCell::new(None));
+            self.zoom.set(1.0);
+
+            // The dragged nodes along with their position at the start of the drag, starting with the grabbed node.
+            let drag_state: Rc<RefCell<Vec<(gtk::Widget, f32, f32)>>> =
+                Rc::new(RefCell::new(Vec::new()));
             let drag_controller = gtk::GestureDrag::new();
 
             drag_controller.connect_drag_begin(
@@ -75,21 +205,49 @@ mod imp {
                         .expect("drag-begin event is not on the GraphView");
                     // pick() should at least return the widget itself.
                     let target = widget.pick(x, y, gtk::PickFlags::DEFAULT).expect("drag-begin pick() did not return a widget");
-                    *drag_state = if target.ancestor(Port::static_type()).is_some() {
+                    let extend_selection = drag_controller
+                        .current_event_state()
+                        .contains(gdk::ModifierType::CONTROL_MASK);
+                    drag_state.clear();
+                    if target.ancestor(Port::static_type()).is_some() {
                         // The user targeted a port, so the dragging should be handled by the Port
                         // component instead of here.
-                        None
                     } else if let Some(target) = target.ancestor(Node::static_type()) {
                         // The user targeted a Node without targeting a specific Port.
-                        // Drag the Node around the screen.
-                        if let Some((x, y)) = widget.get_node_position(&target) {
-                            Some((target, x, y))
-                        } else {
-                            error!("Failed to obtain position of dragged node, drag aborted.");
-                            None
+                        // Select the Node and drag it around the screen, along with all other selected nodes.
+                        let id = match widget.node_id(&target) {
+                            Some(id) => id,
+                            None => return,
+                        };
+                        if extend_selection {
+                            widget.set_node_selected(id, !widget.is_node_selected(id));
+                        } else if !widget.is_node_selected(id) {
+                            widget.clear_node_selection();
+                            widget.set_node_selected(id, true);
                         }
-                    } else {
-                        None
+
+                        let mut dragged = vec![target.clone()];
+                        if widget.is_node_selected(id) {
+                            dragged.extend(
+                                widget
+                                    .selected_nodes()
+                                    .into_iter()
+                                    .map(|node| node.upcast::<gtk::Widget>())
+                                    .filter(|node| *node != target),
+                            );
+                        }
+                        for node in dragged {
+                            if let Some((x, y)) = widget.get_node_position(&node) {
+                                drag_state.push((node, x, y));
+                            } else {
+                                error!("Failed to obtain position of dragged node, drag aborted.");
+                                drag_state.clear();
+                                return;
+                            }
+                        }
+                    } else if !extend_selection {
+                        // Clicking the background clears the selection.
+                        widget.clear_node_selection();
                     }
                 }
             ));
@@ -101,13 +259,48 @@ mod imp {
                         .dynamic_cast::<Self::Type>()
                         .expect("drag-update event is not on the GraphView");
                     let drag_state = drag_state.borrow();
-                    if let Some((ref node, x1, y1)) = *drag_state {
-                        widget.move_node(node, x1 + x as f32, y1 + y as f32);
+                    if let Some((_, x1, y1)) = drag_state.first() {
+                        // Snap the grabbed node to the grid and keep the others at the same distance from it.
+                        let (x2, y2) = widget.snap_position(x1 + x as f32, y1 + y as f32);
+                        let (dx, dy) = (x2 - x1, y2 - y1);
+                        for (node, x1, y1) in drag_state.iter() {
+                            widget.move_node(node, x1 + dx, y1 + dy);
+                        }
                     }
                 }
                 ),
             );
             obj.add_controller(&drag_controller);
+
+            let key_controller = gtk::EventControllerKey::new();
+            key_controller.connect_key_pressed(|key_controller, key, _, modifiers| {
+                let widget = key_controller
+                    .widget()
+                    .expect("key-pressed event has no widget")
+                    .dynamic_cast::<Self::Type>()
+                    .expect("key-pressed event is not on the GraphView");
+                gtk::Inhibit(widget.handle_key(key, modifiers))
+            });
+            obj.add_controller(&key_controller);
+
+            // Show the state and format of links when hovering over them.
+            obj.set_has_tooltip(true);
+            obj.connect_query_tooltip(|widget, x, y, keyboard_mode, tooltip| {
+                let private = Self::from_instance(widget);
+                let link_id = if keyboard_mode {
+                    private.selected_link.get()
+                } else {
+                    widget.link_at(f64::from(x), f64::from(y))
+                };
+
+                match link_id.and_then(|id| widget.link_description(id)) {
+                    Some(description) => {
+                        tooltip.set_text(Some(&description));
+                        true
+                    }
+                    None => false,
+                }
+            });
         }
 
         fn dispose(&self, _obj: &Self::Type) {
@@ -116,39 +309,81 @@ mod imp {
                 .values()
                 .for_each(|node| node.unparent())
         }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                // Emitted whenever nodes or links are added, removed, moved or change how they are drawn.
+                vec![Signal::builder("graph-changed", &[], <()>::static_type().into()).build()]
+            });
+
+            SIGNALS.as_ref()
+        }
     }
 
     impl WidgetImpl for GraphView {
         fn snapshot(&self, widget: &Self::Type, snapshot: &gtk::Snapshot) {
-            /* FIXME: A lot of hardcoded values in here.
-            Try to use relative units (em) and colours from the theme as much as possible. */
-
             let alloc = widget.allocation();
             let widget_bounds =
                 graphene::Rect::new(0.0, 0.0, alloc.width as f32, alloc.height as f32);
 
-            let background_cr = snapshot
-                .append_cairo(&widget_bounds)
-                .expect("Failed to get cairo context");
+            let em = widget.em();
+            let color = |name: &str| -> (f64, f64, f64, f64) {
+                let gtk::gdk::RGBA {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                } = widget
+                    .style_context()
+                    .lookup_color(name)
+                    .unwrap_or(gtk::gdk::RGBA {
+                        red: 0.5,
+                        green: 0.5,
+                        blue: 0.5,
+                        alpha: 1.0,
+                    });
+                (red.into(), green.into(), blue.into(), alpha.into())
+            };
+
+            // Draw a nice grid on the background, which only needs to be drawn again when its style or size changes.
+            let grid_style = GridStyle {
+                size: (alloc.width, alloc.height),
+                spacing: GRID_SPACING * em,
+                color: color("graphview-grid"),
+            };
+            let mut grid_render_node = self.grid_render_node.borrow_mut();
+            if grid_render_node.as_ref().map(|(style, _)| style) != Some(&grid_style) {
+                let grid_snapshot = gtk::Snapshot::new();
+                let background_cr = grid_snapshot
+                    .append_cairo(&widget_bounds)
+                    .expect("Failed to get cairo context");
 
-            // Draw a nice grid on the background.
-            background_cr.set_source_rgb(0.18, 0.18, 0.18);
-            background_cr.set_line_width(0.2); // TODO: Set to 1px
-            let mut y = 0.0;
-            while y < alloc.height.into() {
-                background_cr.move_to(0.0, y);
-                background_cr.line_to(alloc.width.into(), y);
-                y += 20.0; // TODO: Change to em;
+                let (red, green, blue, alpha) = grid_style.color;
+                background_cr.set_source_rgba(red, green, blue, alpha);
+                background_cr.set_line_width(1.0);
+                // Offset the lines by half a pixel, so that they cover exactly one row or column of pixels.
+                let mut y = 0.5;
+                while y < alloc.height.into() {
+                    background_cr.move_to(0.0, y);
+                    background_cr.line_to(alloc.width.into(), y);
+                    y += grid_style.spacing.round();
+                }
+                let mut x = 0.5;
+                while x < alloc.width.into() {
+                    background_cr.move_to(x, 0.0);
+                    background_cr.line_to(x, alloc.height.into());
+                    x += grid_style.spacing.round();
+                }
+                if let Err(e) = background_cr.stroke() {
+                    warn!("Failed to draw graphview grid: {}", e);
+                };
+                drop(background_cr);
+
+                *grid_render_node = grid_snapshot.to_node().map(|node| (grid_style, node));
             }
-            let mut x = 0.0;
-            while x < alloc.width.into() {
-                background_cr.move_to(x, 0.0);
-                background_cr.line_to(x, alloc.height.into());
-                x += 20.0; // TODO: Change to em;
+            if let Some((_, node)) = grid_render_node.as_ref() {
+                snapshot.append_node(node);
             }
-            if let Err(e) = background_cr.stroke() {
-                warn!("Failed to draw graphview grid: {}", e);
-            };
 
             // Draw all children
             self.nodes
@@ -157,120 +392,332 @@ mod imp {
                 .for_each(|node| self.instance().snapshot_child(node, snapshot));
 
             // Draw all links
-            let link_cr = snapshot
-                .append_cairo(&graphene::Rect::new(
-                    0.0,
-                    0.0,
-                    alloc.width as f32,
-                    alloc.height as f32,
-                ))
-                .expect("Failed to get cairo context");
+            let link_style = LinkStyle {
+                lingering: color("graphview-link"),
+                transient: color("graphview-link-transient"),
+                error: color("graphview-link-error"),
+                selected: color("graphview-link-selected"),
+                extra: color("graphview-link-extra"),
+                missing: color("graphview-link-missing"),
+                width: LINK_WIDTH * em,
+                dash: [LINK_DASH[0] * em, LINK_DASH[1] * em],
+            };
+            if self.link_style.get() != Some(link_style) {
+                self.link_render_nodes.borrow_mut().clear();
+                self.link_style.set(Some(link_style));
+            }
+            self.invalidate_links_of_moved_nodes();
 
-            link_cr.set_line_width(2.0);
-
-            let gtk::gdk::RGBA {
-                red,
-                green,
-                blue,
-                alpha,
-            } = widget
-                .style_context()
-                .lookup_color("graphview-link")
-                .unwrap_or(gtk::gdk::RGBA {
-                    red: 0.0,
-                    green: 0.0,
-                    blue: 0.0,
-                    alpha: 0.0,
+            // Only links that changed are rendered again, all others are reused from the cache.
+            for (id, link) in self.links.borrow().iter() {
+                if self.is_link_hidden(&link.link) {
+                    continue;
+                }
+                let cached = self.link_render_nodes.borrow().get(id).cloned();
+                let node = cached.or_else(|| {
+                    let node = self.render_link(*id, link, &link_style)?;
+                    self.link_render_nodes
+                        .borrow_mut()
+                        .insert(*id, node.clone());
+                    Some(node)
                 });
-            link_cr.set_source_rgba(red.into(), green.into(), blue.into(), alpha.into());
+                if let Some(node) = node {
+                    snapshot.append_node(&node);
+                }
+            }
 
-            for (link, active) in self.links.borrow().values() {
-                if let Some((from_x, from_y, to_x, to_y)) = self.get_link_coordinates(link) {
-                    link_cr.move_to(from_x, from_y);
+            // Links missing compared to the reference are drawn as dashed ghost lines.
+            if let Some(comparison) = self.comparison.borrow().as_ref() {
+                let ghost_cr = snapshot
+                    .append_cairo(&widget_bounds)
+                    .expect("Failed to get cairo context");
 
-                    // Use dashed line for inactive links, full line otherwise.
-                    if *active {
-                        link_cr.set_dash(&[], 0.0);
-                    } else {
-                        link_cr.set_dash(&[10.0, 5.0], 0.0);
+                let (red, green, blue, alpha) = link_style.missing;
+                ghost_cr.set_source_rgba(red, green, blue, alpha);
+                ghost_cr.set_line_width(link_style.width);
+                ghost_cr.set_dash(&link_style.dash, 0.0);
+                for link in &comparison.missing {
+                    if let Some((from_x, from_y, to_x, to_y)) = self.get_link_coordinates(link) {
+                        draw_link_curve(&ghost_cr, from_x, from_y, to_x, to_y);
                     }
+                }
 
-                    // If the output port is farther right than the input port and they have
-                    // a similar y coordinate, apply a y offset to the control points
-                    // so that the curve sticks out a bit.
-                    let y_control_offset = if from_x > to_x {
-                        f64::max(0.0, 25.0 - (from_y - to_y).abs())
-                    } else {
-                        0.0
-                    };
+                if let Err(e) = ghost_cr.stroke() {
+                    warn!("Failed to draw missing graphview links: {}", e);
+                };
+            }
+
+            // Draw the link selected with the keyboard again on top of the others, so that it stands out.
+            let selected_link = self
+                .selected_link
+                .get()
+                .and_then(|id| self.links.borrow().get(&id).cloned());
+            if let Some(link) = selected_link {
+                if let Some((from_x, from_y, to_x, to_y)) = self.get_link_coordinates(&link.link) {
+                    let link_cr = snapshot
+                        .append_cairo(&widget_bounds)
+                        .expect("Failed to get cairo context");
+
+                    let (red, green, blue, alpha) = link_style.selected;
+                    link_cr.set_source_rgba(red, green, blue, alpha);
+                    let (width, dash) = link_style.line(&link);
+                    link_cr.set_line_width(width + link_style.width);
+                    link_cr.set_dash(dash, 0.0);
 
-                    // Place curve control offset by half the x distance between the two points.
-                    // This makes the curve scale well for varying distances between the two ports,
-                    // especially when the output port is farther right than the input port.
-                    let half_x_dist = f64::abs(from_x - to_x) / 2.0;
-                    link_cr.curve_to(
-                        from_x + half_x_dist,
-                        from_y - y_control_offset,
-                        to_x - half_x_dist,
-                        to_y - y_control_offset,
-                        to_x,
-                        to_y,
-                    );
+                    draw_link_curve(&link_cr, from_x, from_y, to_x, to_y);
 
                     if let Err(e) = link_cr.stroke() {
-                        warn!("Failed to draw graphview links: {}", e);
+                        warn!("Failed to draw selected graphview link: {}", e);
                     };
-                } else {
-                    warn!("Could not get allocation of ports of link: {:?}", link);
                 }
             }
         }
     }
 
     impl GraphView {
+        /// Render a single link into a render node covering only the area of its curve.
+        ///
+        /// Returns `None` if the ports of the link do not exist as widgets.
+        fn render_link(
+            &self,
+            id: u32,
+            link: &super::Link,
+            style: &LinkStyle,
+        ) -> Option<gsk::RenderNode> {
+            let (from_x, from_y, to_x, to_y) = match self.get_link_coordinates(&link.link) {
+                Some(coordinates) => coordinates,
+                None => {
+                    warn!("Could not get allocation of ports of link: {:?}", link.link);
+                    return None;
+                }
+            };
+
+            let (line_width, dash) = style.line(link);
+
+            // The curve lies within the bounding box of its start, end and control points.
+            let (c1_x, c1_y, c2_x, c2_y) = link_control_points(from_x, from_y, to_x, to_y);
+            let xs = [from_x, c1_x, c2_x, to_x];
+            let ys = [from_y, c1_y, c2_y, to_y];
+            let min_x = xs.iter().copied().fold(f64::INFINITY, f64::min) - line_width;
+            let max_x = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max) + line_width;
+            let min_y = ys.iter().copied().fold(f64::INFINITY, f64::min) - line_width;
+            let max_y = ys.iter().copied().fold(f64::NEG_INFINITY, f64::max) + line_width;
+
+            let link_snapshot = gtk::Snapshot::new();
+            let link_cr = link_snapshot
+                .append_cairo(&graphene::Rect::new(
+                    min_x as f32,
+                    min_y as f32,
+                    (max_x - min_x) as f32,
+                    (max_y - min_y) as f32,
+                ))
+                .expect("Failed to get cairo context");
+
+            link_cr.set_line_width(line_width);
+            link_cr.set_dash(dash, 0.0);
+
+            // Links that go away once their creator exits are drawn in another color.
+            // Links that failed are always drawn in the error color, and links that are not part
+            // of the reference they are compared with in the warning color.
+            let extra = self
+                .comparison
+                .borrow()
+                .as_ref()
+                .map_or(false, |comparison| comparison.extra.contains(&id));
+            let (red, green, blue, alpha) = if let LinkState::Error(_) = link.state {
+                style.error
+            } else if extra {
+                style.extra
+            } else if link.linger {
+                style.lingering
+            } else {
+                style.transient
+            };
+            link_cr.set_source_rgba(red, green, blue, alpha);
+
+            draw_link_curve(&link_cr, from_x, from_y, to_x, to_y);
+
+            if let Err(e) = link_cr.stroke() {
+                warn!("Failed to draw graphview links: {}", e);
+            };
+            drop(link_cr);
+
+            link_snapshot.to_node()
+        }
+
+        /// Drop the rendered links of all nodes that moved or changed their size since the links were drawn,
+        /// as their ports may have moved as well.
+        pub(super) fn invalidate_links_of_moved_nodes(&self) {
+            let nodes = self.nodes.borrow();
+            let mut node_allocations = self.node_allocations.borrow_mut();
+
+            node_allocations.retain(|id, _| nodes.contains_key(id));
+            for (id, node) in nodes.iter() {
+                let alloc = node.allocation();
+                let alloc = (alloc.x, alloc.y, alloc.width, alloc.height);
+                if node_allocations.insert(*id, alloc) != Some(alloc) {
+                    self.invalidate_links_of_node(*id);
+                }
+            }
+        }
+
+        /// Drop the rendered links from or to the specified node, so that they are drawn again.
+        pub(super) fn invalidate_links_of_node(&self, node_id: u32) {
+            let links = self.links.borrow();
+            self.link_render_nodes.borrow_mut().retain(|id, _| {
+                links.get(id).map_or(false, |link| {
+                    link.link.node_from != node_id && link.link.node_to != node_id
+                })
+            });
+        }
+
         /// Get coordinates for the drawn link to start at and to end at.
         ///
         /// # Returns
         /// `Some((from_x, from_y, to_x, to_y))` if all objects the links refers to exist as widgets.
-        fn get_link_coordinates(&self, link: &crate::PipewireLink) -> Option<(f64, f64, f64, f64)> {
+        pub(super) fn get_link_coordinates(
+            &self,
+            link: &crate::PipewireLink,
+        ) -> Option<(f64, f64, f64, f64)> {
+            if self.is_link_hidden(link) {
+                return None;
+            }
             let nodes = self.nodes.borrow();
 
             // For some reason, gtk4::WidgetExt::translate_coordinates gives me incorrect values,
             // so we manually calculate the needed offsets here.
 
-            let from_port = &nodes.get(&link.node_from)?.get_port(link.port_from)?;
+            // Links of collapsed nodes are attached to the middle of their sides, as their ports are not shown.
+            let from_node = nodes.get(&link.node_from)?;
+            let from_port = from_node.get_port(link.port_from)?;
             let gtk::Allocation {
-                x: mut fx,
-                y: mut fy,
-                width: fw,
-                height: fh,
-            } = from_port.allocation();
-            let from_node = from_port
-                .ancestor(Node::static_type())
-                .expect("Port is not a child of a node");
-            let gtk::Allocation { x: fnx, y: fny, .. } = from_node.allocation();
-            fx += fnx + fw;
-            fy += fny + (fh / 2);
-
-            let to_port = &nodes.get(&link.node_to)?.get_port(link.port_to)?;
+                x: fnx,
+                y: fny,
+                width: fnw,
+                height: fnh,
+            } = from_node.allocation();
+            let (fx, fy) = if from_node.is_collapsed() {
+                (fnx + fnw, fny + (fnh / 2))
+            } else {
+                let gtk::Allocation {
+                    x: fx,
+                    y: fy,
+                    width: fw,
+                    height: fh,
+                } = from_port.allocation();
+                (fx + fnx + fw, fy + fny + (fh / 2))
+            };
+
+            let to_node = nodes.get(&link.node_to)?;
+            let to_port = to_node.get_port(link.port_to)?;
             let gtk::Allocation {
-                x: mut tx,
-                y: mut ty,
-                height: th,
+                x: tnx,
+                y: tny,
+                height: tnh,
                 ..
-            } = to_port.allocation();
-            let to_node = to_port
-                .ancestor(Node::static_type())
-                .expect("Port is not a child of a node");
-            let gtk::Allocation { x: tnx, y: tny, .. } = to_node.allocation();
-            tx += tnx;
-            ty += tny + (th / 2);
+            } = to_node.allocation();
+            let (tx, ty) = if to_node.is_collapsed() {
+                (tnx, tny + (tnh / 2))
+            } else {
+                let gtk::Allocation {
+                    x: tx,
+                    y: ty,
+                    height: th,
+                    ..
+                } = to_port.allocation();
+                (tx + tnx, ty + tny + (th / 2))
+            };
 
             Some((fx.into(), fy.into(), tx.into(), ty.into()))
         }
+
+        /// Check whether the link is from or to a hidden node, so that it is not drawn either.
+        fn is_link_hidden(&self, link: &crate::PipewireLink) -> bool {
+            let nodes = self.nodes.borrow();
+            [link.node_from, link.node_to]
+                .iter()
+                .any(|id| nodes.get(id).map_or(false, |node| !node.is_visible()))
+        }
+
+        /// Get the widget of the port with the specified id.
+        pub(super) fn port(&self, id: u32) -> Option<Port> {
+            self.nodes
+                .borrow()
+                .values()
+                .find_map(|node| node.get_port(id))
+        }
+
+        /// Get the ids of all links from or to the specified port, ordered by id.
+        pub(super) fn links_of_port(&self, port_id: u32) -> Vec<u32> {
+            let mut links: Vec<u32> = self
+                .links
+                .borrow()
+                .iter()
+                .filter(|(_, link)| link.link.port_from == port_id || link.link.port_to == port_id)
+                .map(|(id, _)| *id)
+                .collect();
+            links.sort_unstable();
+            links
+        }
     }
 }
 
+/// Add the curve of a link from an output port at `(from_x, from_y)` to an input port at `(to_x, to_y)`
+/// to the current path of the cairo context.
+pub(super) fn draw_link_curve(cr: &cairo::Context, from_x: f64, from_y: f64, to_x: f64, to_y: f64) {
+    let (c1_x, c1_y, c2_x, c2_y) = link_control_points(from_x, from_y, to_x, to_y);
+
+    cr.move_to(from_x, from_y);
+    cr.curve_to(c1_x, c1_y, c2_x, c2_y, to_x, to_y);
+}
+
+/// Get the two control points `(c1_x, c1_y, c2_x, c2_y)` of the bezier curve of a link.
+fn link_control_points(from_x: f64, from_y: f64, to_x: f64, to_y: f64) -> (f64, f64, f64, f64) {
+    // If the output port is farther right than the input port and they have
+    // a similar y coordinate, apply a y offset to the control points
+    // so that the curve sticks out a bit.
+    let y_control_offset = if from_x > to_x {
+        f64::max(0.0, 25.0 - (from_y - to_y).abs())
+    } else {
+        0.0
+    };
+
+    // Place curve control offset by half the x distance between the two points.
+    // This makes the curve scale well for varying distances between the two ports,
+    // especially when the output port is farther right than the input port.
+    let half_x_dist = f64::abs(from_x - to_x) / 2.0;
+    (
+        from_x + half_x_dist,
+        from_y - y_control_offset,
+        to_x - half_x_dist,
+        to_y - y_control_offset,
+    )
+}
+
+/// Get the approximate distance of the point `(x, y)` from the curve of a link.
+fn distance_to_link_curve(
+    (from_x, from_y, to_x, to_y): (f64, f64, f64, f64),
+    x: f64,
+    y: f64,
+) -> f64 {
+    const SAMPLES: u32 = 32;
+
+    let (c1_x, c1_y, c2_x, c2_y) = link_control_points(from_x, from_y, to_x, to_y);
+    let bezier = |t: f64, p0: f64, p1: f64, p2: f64, p3: f64| {
+        let u = 1.0 - t;
+        u * u * u * p0 + 3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t * p3
+    };
+
+    (0..=SAMPLES)
+        .map(|i| {
+            let t = f64::from(i) / f64::from(SAMPLES);
+            let dx = bezier(t, from_x, c1_x, c2_x, to_x) - x;
+            let dy = bezier(t, from_y, c1_y, c2_y, to_y) - y;
+            (dx * dx + dy * dy).sqrt()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
 glib::wrapper! {
     pub struct GraphView(ObjectSubclass<imp::GraphView>)
         @extends gtk::Widget;
@@ -281,19 +728,21 @@ impl GraphView {
         glib::Object::new(&[]).expect("Failed to create GraphView")
     }
 
-    pub fn add_node(&self, id: u32, node: Node, node_type: Option<NodeType>) {
+    pub fn add_node(&self, id: u32, node: Node) {
         let private = imp::GraphView::from_instance(self);
         node.set_parent(self);
 
         // Place widgets in colums of 3, growing down
-        let x = if let Some(node_type) = node_type {
+        // The columns are spread further apart the further the graph is zoomed in.
+        let zoom = private.zoom.get() as f32;
+        let x = if let Some(node_type) = node.node_type() {
             match node_type {
                 NodeType::Output => 20.0,
                 NodeType::Input => 820.0,
             }
         } else {
             420.0
-        };
+        } * zoom;
 
         let y = private
             .nodes
@@ -305,34 +754,167 @@ impl GraphView {
             })
             .filter(|(x2, _)| {
                 // Only look for other nodes that have a similar x coordinate
-                (x - x2).abs() < 50.0
+                (x - x2).abs() < 50.0 * zoom
             })
             .max_by(|y1, y2| {
                 // Get max in column
                 y1.partial_cmp(y2).unwrap_or(Ordering::Equal)
             })
-            .map_or(20_f32, |(_x, y)| y + 100.0);
+            .map_or(20.0 * zoom, |(_x, y)| y + 100.0 * zoom);
 
         self.move_node(&node.clone().upcast(), x, y);
 
         private.nodes.borrow_mut().insert(id, node);
+        self.graph_changed();
     }
 
+    /// Remove all nodes and links.
+    pub fn clear(&self) {
+        let private = imp::GraphView::from_instance(self);
+
+        let nodes: Vec<Node> = private
+            .nodes
+            .borrow_mut()
+            .drain()
+            .map(|(_, node)| node)
+            .collect();
+        for node in nodes {
+            node.unparent();
+        }
+        private.links.borrow_mut().clear();
+        private.selected_nodes.borrow_mut().clear();
+        private.selected_link.set(None);
+        private.link_source.set(None);
+        private.link_render_nodes.borrow_mut().clear();
+        private.comparison.borrow_mut().take();
+
+        self.graph_changed();
+    }
+
+    /// Zoom the graph by the specified factor, which is limited to the range from [`MIN_ZOOM`] to [`MAX_ZOOM`].
+    ///
+    /// The font grows with the zoom, and with it all sizes derived from it, while the nodes move apart
+    /// so that the arrangement of the graph keeps its shape.
+    pub fn set_zoom(&self, zoom: f64) {
+        let private = imp::GraphView::from_instance(self);
+
+        let zoom = zoom.max(MIN_ZOOM).min(MAX_ZOOM);
+        let old_zoom = private.zoom.get();
+        if (zoom - old_zoom).abs() < f64::EPSILON {
+            return;
+        }
+        private.zoom.set(zoom);
+
+        let provider = private.zoom_provider.get_or_init(|| {
+            let provider = gtk::CssProvider::new();
+            self.style_context()
+                .add_provider(&provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+            provider
+        });
+        // The font size of the graphview is inherited by the nodes and ports inside it.
+        provider.load_from_data(
+            format!("graphview {{ font-size: {}%; }}", (zoom * 100.0).round()).as_bytes(),
+        );
+
+        let ratio = (zoom / old_zoom) as f32;
+        for node in self.nodes() {
+            if let Some((x, y)) = self.get_node_position(node.upcast_ref()) {
+                self.move_node(node.upcast_ref(), x * ratio, y * ratio);
+            }
+        }
+    }
+
+    pub fn zoom(&self) -> f64 {
+        let private = imp::GraphView::from_instance(self);
+        private.zoom.get()
+    }
+
+    /// Show how the links differ from a reference, or stop comparing them if `comparison` is `None`.
+    pub fn set_comparison(&self, comparison: Option<LinkComparison>) {
+        let private = imp::GraphView::from_instance(self);
+        *private.comparison.borrow_mut() = comparison;
+        // The color of extra links is part of their cached rendering.
+        private.link_render_nodes.borrow_mut().clear();
+        self.graph_changed();
+    }
+
+    /// Remove the node with the specified id along with all links from or to it.
     pub fn remove_node(&self, id: u32) {
         let private = imp::GraphView::from_instance(self);
-        let mut nodes = private.nodes.borrow_mut();
-        if let Some(node) = nodes.remove(&id) {
+        let links: Vec<u32> = private
+            .links
+            .borrow()
+            .iter()
+            .filter(|(_, link)| link.link.node_from == id || link.link.node_to == id)
+            .map(|(link_id, _)| *link_id)
+            .collect();
+        for link_id in links {
+            self.remove_link(link_id);
+        }
+
+        private.selected_nodes.borrow_mut().remove(&id);
+        let removed = private.nodes.borrow_mut().remove(&id);
+        if let Some(node) = removed {
             node.unparent();
+            self.graph_changed();
         } else {
             warn!("Tried to remove non-existant node (id={}) from graph", id);
         }
     }
 
+    /// Hide the node with the specified id along with its links, until the hidden nodes are shown again.
+    pub fn hide_node(&self, id: u32) {
+        let private = imp::GraphView::from_instance(self);
+        let node = private.nodes.borrow().get(&id).cloned();
+        if let Some(node) = node {
+            self.set_node_selected(id, false);
+            node.set_visible(false);
+            self.graph_changed();
+        }
+    }
+
+    /// Show all nodes hidden with [`Self::hide_node`] again.
+    pub fn show_hidden_nodes(&self) {
+        for node in self.nodes() {
+            node.set_visible(true);
+        }
+        self.graph_changed();
+    }
+
     pub fn add_port(&self, node_id: u32, port_id: u32, port: crate::view::port::Port) {
         let private = imp::GraphView::from_instance(self);
 
+        port.set_name_style(private.port_name_style.get());
+
+        // Start or complete a link when the port is activated with the keyboard or by assistive technologies.
+        if let Err(e) = port.connect_local(
+            "activate",
+            false,
+            clone!(@weak self as graphview => @default-return None, move |args| {
+                let port = args[0].get::<Port>().expect("activate signal was not emitted by a port");
+                graphview.activate_port(&port, LinkOptions::default());
+                None
+            }),
+        ) {
+            warn!("Failed to connect to \"activate\" signal: {}", e);
+        }
+
+        // Select the first link of the port while it has the keyboard focus,
+        // so that it can be removed by pressing delete.
+        let focus_controller = gtk::EventControllerFocus::new();
+        focus_controller.connect_enter(clone!(@weak self as graphview => move |_| {
+            let private = imp::GraphView::from_instance(&graphview);
+            graphview.select_link(private.links_of_port(port_id).first().copied());
+        }));
+        focus_controller.connect_leave(clone!(@weak self as graphview => move |_| {
+            graphview.select_link(None);
+        }));
+        port.add_controller(&focus_controller);
+
         if let Some(node) = private.nodes.borrow_mut().get_mut(&node_id) {
             node.add_port(port_id, port);
+            // Other ports of the node may have moved to make space for the new one.
+            private.invalidate_links_of_node(node_id);
         } else {
             error!(
                 "Node with id {} not found when trying to add port with id {} to graph",
@@ -341,35 +923,335 @@ impl GraphView {
         }
     }
 
+    /// Label all ports with their name of the specified style.
+    pub fn set_port_name_style(&self, style: PortNameStyle) {
+        let private = imp::GraphView::from_instance(self);
+        private.port_name_style.set(style);
+
+        for node in private.nodes.borrow().values() {
+            for port in node.ports() {
+                port.set_name_style(style);
+            }
+        }
+    }
+
+    /// Remove the port with the specified id from its node along with all links from or to it.
     pub fn remove_port(&self, id: u32, node_id: u32) {
         let private = imp::GraphView::from_instance(self);
+        for link_id in private.links_of_port(id) {
+            self.remove_link(link_id);
+        }
+
         let nodes = private.nodes.borrow();
         if let Some(node) = nodes.get(&node_id) {
             node.remove_port(id);
+            // The remaining ports of the node may have moved to close the gap.
+            private.invalidate_links_of_node(node_id);
         }
     }
 
-    pub fn add_link(&self, link_id: u32, link: crate::PipewireLink, active: bool) {
+    pub fn add_link(&self, link_id: u32, link: crate::PipewireLink, state: LinkState) {
         let private = imp::GraphView::from_instance(self);
-        private.links.borrow_mut().insert(link_id, (link, active));
-        self.queue_draw();
+        private.links.borrow_mut().insert(
+            link_id,
+            Link {
+                link: link.clone(),
+                state,
+                format: None,
+                passive: false,
+                linger: true,
+            },
+        );
+
+        self.update_port_connections(link.port_from);
+        self.update_port_connections(link.port_to);
+        self.graph_changed();
     }
 
-    pub fn set_link_state(&self, link_id: u32, active: bool) {
+    pub fn set_link_state(&self, link_id: u32, state: LinkState) {
         let private = imp::GraphView::from_instance(self);
-        if let Some((_, state)) = private.links.borrow_mut().get_mut(&link_id) {
-            *state = active;
-            self.queue_draw();
+        if let Some(link) = private.links.borrow_mut().get_mut(&link_id) {
+            link.state = state;
+            private.link_render_nodes.borrow_mut().remove(&link_id);
+            self.graph_changed();
         } else {
             warn!("Link state changed on unknown link (id={})", link_id);
         }
     }
 
+    /// Set the negotiated format of a link, which is shown in its tooltip.
+    pub fn set_link_format(&self, link_id: u32, format: Option<LinkFormat>) {
+        let private = imp::GraphView::from_instance(self);
+        if let Some(link) = private.links.borrow_mut().get_mut(&link_id) {
+            link.format = format;
+        } else {
+            warn!("Link format changed on unknown link (id={})", link_id);
+        }
+    }
+
+    /// Get the id of the link closest to the point `(x, y)`, if one is close enough to it.
+    fn link_at(&self, x: f64, y: f64) -> Option<u32> {
+        let private = imp::GraphView::from_instance(self);
+        private
+            .links
+            .borrow()
+            .iter()
+            .filter_map(|(id, link)| {
+                let coordinates = private.get_link_coordinates(&link.link)?;
+                Some((*id, distance_to_link_curve(coordinates, x, y)))
+            })
+            .filter(|(_, distance)| *distance <= LINK_TOOLTIP_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(id, _)| id)
+    }
+
+    /// Describe the ports, state and format of a link for its tooltip.
+    fn link_description(&self, link_id: u32) -> Option<String> {
+        let private = imp::GraphView::from_instance(self);
+        let link = private.links.borrow().get(&link_id)?.clone();
+
+        let port_name = |node_id: u32, port_id: u32| {
+            let nodes = private.nodes.borrow();
+            let node = nodes.get(&node_id)?;
+            Some(format!(
+                "{}: {}",
+                node.name(),
+                node.get_port(port_id)?.name()
+            ))
+        };
+        let from = port_name(link.link.node_from, link.link.port_from).unwrap_or_default();
+        let to = port_name(link.link.node_to, link.link.port_to).unwrap_or_default();
+
+        let mut description = format!("{} → {}\nState: {}", from, to, link.state);
+        if let Some(format) = link.format {
+            description.push_str(&format!("\nFormat: {}", format));
+        }
+        if link.passive {
+            description.push_str("\nPassive");
+        }
+        if !link.linger {
+            description.push_str("\nRemoved when its creator exits");
+        }
+
+        Some(description)
+    }
+
+    /// Set the properties of a link that determine how it is drawn.
+    pub fn set_link_properties(&self, link_id: u32, passive: bool, linger: bool) {
+        let private = imp::GraphView::from_instance(self);
+        if let Some(link) = private.links.borrow_mut().get_mut(&link_id) {
+            link.passive = passive;
+            link.linger = linger;
+            private.link_render_nodes.borrow_mut().remove(&link_id);
+            self.graph_changed();
+        } else {
+            warn!("Link properties changed on unknown link (id={})", link_id);
+        }
+    }
+
     pub fn remove_link(&self, id: u32) {
         let private = imp::GraphView::from_instance(self);
-        let mut links = private.links.borrow_mut();
-        links.remove(&id);
+        let removed = private.links.borrow_mut().remove(&id);
+        private.link_render_nodes.borrow_mut().remove(&id);
 
+        if private.selected_link.get() == Some(id) {
+            private.selected_link.set(None);
+        }
+
+        if let Some(removed) = removed {
+            self.update_port_connections(removed.link.port_from);
+            self.update_port_connections(removed.link.port_to);
+        }
+
+        self.graph_changed();
+    }
+
+    /// Tell the port with the specified id which ports it is connected to,
+    /// so that it can describe its connections to assistive technologies and let them navigate to them.
+    fn update_port_connections(&self, port_id: u32) {
+        let private = imp::GraphView::from_instance(self);
+        let port = match private.port(port_id) {
+            Some(port) => port,
+            None => return,
+        };
+
+        let nodes = private.nodes.borrow();
+        let links = private.links.borrow();
+        let (connected, peers): (Vec<String>, Vec<Port>) = private
+            .links_of_port(port_id)
+            .iter()
+            .filter_map(|id| links.get(id))
+            .filter_map(|link| {
+                let (node_id, other_port_id) = if link.link.port_from == port_id {
+                    (link.link.node_to, link.link.port_to)
+                } else {
+                    (link.link.node_from, link.link.port_from)
+                };
+                let node = nodes.get(&node_id)?;
+                let other_port = node.get_port(other_port_id)?;
+                Some((
+                    format!("{}: {}", node.name(), other_port.name()),
+                    other_port,
+                ))
+            })
+            .unzip();
+
+        port.set_connections(&connected, &peers);
+    }
+
+    /// Handle a key press on the graphview or one of its children.
+    ///
+    /// Returns `true` if the key was handled and should not be propagated further.
+    fn handle_key(&self, key: gdk::keys::Key, modifiers: gdk::ModifierType) -> bool {
+        use gdk::keys::constants;
+
+        let private = imp::GraphView::from_instance(self);
+        let focused_port = self
+            .root()
+            .and_then(|root| root.focus())
+            .and_then(|focus| focus.ancestor(Port::static_type()))
+            .and_then(|port| port.downcast::<Port>().ok());
+
+        match key {
+            constants::Up | constants::Down | constants::Left | constants::Right
+                if modifiers.contains(gdk::ModifierType::ALT_MASK) =>
+            {
+                // Move the focused node, as an alternative to dragging it.
+                let node = match self
+                    .root()
+                    .and_then(|root| root.focus())
+                    .and_then(|focus| focus.downcast::<Node>().ok())
+                {
+                    Some(node) => node,
+                    None => return false,
+                };
+                let (x, y) = match self.get_node_position(node.upcast_ref()) {
+                    Some(position) => position,
+                    None => return false,
+                };
+                let (dx, dy) = match key {
+                    constants::Up => (0.0, -KEYBOARD_MOVE_STEP),
+                    constants::Down => (0.0, KEYBOARD_MOVE_STEP),
+                    constants::Left => (-KEYBOARD_MOVE_STEP, 0.0),
+                    _ => (KEYBOARD_MOVE_STEP, 0.0),
+                };
+                self.move_node(node.upcast_ref(), x + dx, y + dy);
+                true
+            }
+            constants::Return | constants::KP_Enter
+                if modifiers.intersects(
+                    gdk::ModifierType::SHIFT_MASK | gdk::ModifierType::CONTROL_MASK,
+                ) =>
+            {
+                // Unmodified presses activate the port, which is handled by its "activate" signal.
+                match focused_port {
+                    Some(port) => {
+                        self.activate_port(&port, link_options_for_modifiers(modifiers));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            constants::Escape if private.link_source.get().is_some() => {
+                self.set_link_source(None);
+                true
+            }
+            constants::Delete | constants::KP_Delete => {
+                let link = private
+                    .selected_link
+                    .get()
+                    .and_then(|id| private.links.borrow().get(&id).cloned());
+                if let Some(link) = link {
+                    // Toggling an existing link removes it.
+                    if let Some(port) = private.port(link.link.port_from) {
+                        port.toggle_link(
+                            link.link.port_from,
+                            link.link.port_to,
+                            LinkOptions::default(),
+                        );
+                    }
+                    true
+                } else {
+                    false
+                }
+            }
+            constants::Up | constants::Down
+                if modifiers.contains(gdk::ModifierType::CONTROL_MASK) =>
+            {
+                // Cycle through the links of the focused port.
+                let port = match focused_port {
+                    Some(port) => port,
+                    None => return false,
+                };
+                let links = private.links_of_port(port.id());
+                if links.is_empty() {
+                    return true;
+                }
+
+                let current = private
+                    .selected_link
+                    .get()
+                    .and_then(|id| links.iter().position(|link| *link == id));
+                let next = match (current, key == constants::Down) {
+                    (None, _) => 0,
+                    (Some(i), true) => (i + 1) % links.len(),
+                    (Some(i), false) => (i + links.len() - 1) % links.len(),
+                };
+                self.select_link(Some(links[next]));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Start or complete a link from the specified port using the keyboard.
+    ///
+    /// The first activated port becomes the source of the link. Activating a port of the opposite direction
+    /// afterwards toggles the link between the two ports, while activating the source again cancels the link.
+    /// A new link is created with the specified options.
+    fn activate_port(&self, port: &Port, options: LinkOptions) {
+        let private = imp::GraphView::from_instance(self);
+
+        let source = private
+            .link_source
+            .get()
+            .and_then(|source_id| private.port(source_id));
+        match source {
+            Some(source) if source.id() == port.id() => self.set_link_source(None),
+            Some(source) => match (source.direction(), port.direction()) {
+                (Direction::Output, Direction::Input) | (Direction::Input, Direction::Output) => {
+                    let (port_from, port_to) = match port.direction() {
+                        Direction::Input => (source.id(), port.id()),
+                        Direction::Output => (port.id(), source.id()),
+                    };
+                    port.toggle_link(port_from, port_to, options);
+                    self.set_link_source(None);
+                }
+                // Both ports have the same direction, so start a new link from the activated port instead.
+                _ => self.set_link_source(Some(port)),
+            },
+            None => self.set_link_source(Some(port)),
+        }
+    }
+
+    /// Set the port a link is being created from with the keyboard and highlight it.
+    fn set_link_source(&self, port: Option<&Port>) {
+        let private = imp::GraphView::from_instance(self);
+
+        if let Some(old_source) = private.link_source.get().and_then(|id| private.port(id)) {
+            old_source.remove_css_class("link-source");
+        }
+        if let Some(port) = port {
+            port.add_css_class("link-source");
+        }
+
+        private.link_source.set(port.map(Port::id));
+    }
+
+    /// Select the specified link, so that it is highlighted and can be removed with the keyboard.
+    fn select_link(&self, id: Option<u32>) {
+        let private = imp::GraphView::from_instance(self);
+        private.selected_link.set(id);
         self.queue_draw();
     }
 
@@ -412,9 +1294,256 @@ impl GraphView {
             .expect("Could not cast to FixedLayoutChild")
             .set_transform(&transform);
 
-        // FIXME: If links become proper widgets,
-        // we don't need to redraw the full graph everytime.
+        // Only the links of the moved node are rendered again, see `invalidate_links_of_moved_nodes`.
+        self.graph_changed();
+    }
+
+    /// Redraw the graph and notify everyone drawing it elsewhere, such as the minimap, that it changed.
+    fn graph_changed(&self) {
         self.queue_draw();
+        self.emit_by_name("graph-changed", &[])
+            .expect("Failed to emit \"graph-changed\" signal");
+    }
+
+    /// Get the id of the specified node widget.
+    fn node_id(&self, node: &gtk::Widget) -> Option<u32> {
+        let private = imp::GraphView::from_instance(self);
+        private
+            .nodes
+            .borrow()
+            .iter()
+            .find(|(_, other)| *other == node)
+            .map(|(id, _)| *id)
+    }
+
+    fn is_node_selected(&self, id: u32) -> bool {
+        let private = imp::GraphView::from_instance(self);
+        private.selected_nodes.borrow().contains(&id)
+    }
+
+    /// Add the node with the specified id to the selection or remove it from the selection.
+    fn set_node_selected(&self, id: u32, selected: bool) {
+        let private = imp::GraphView::from_instance(self);
+        let node = match private.nodes.borrow().get(&id) {
+            Some(node) => node.clone(),
+            None => return,
+        };
+
+        if selected {
+            private.selected_nodes.borrow_mut().insert(id);
+            node.add_css_class("selected");
+        } else {
+            private.selected_nodes.borrow_mut().remove(&id);
+            node.remove_css_class("selected");
+        }
+    }
+
+    fn clear_node_selection(&self) {
+        let private = imp::GraphView::from_instance(self);
+        let selected: Vec<u32> = private.selected_nodes.borrow().iter().copied().collect();
+        for id in selected {
+            self.set_node_selected(id, false);
+        }
+    }
+
+    /// Get the widgets of all selected nodes.
+    fn selected_nodes(&self) -> Vec<Node> {
+        let private = imp::GraphView::from_instance(self);
+        let nodes = private.nodes.borrow();
+        private
+            .selected_nodes
+            .borrow()
+            .iter()
+            .filter_map(|id| nodes.get(id).cloned())
+            .collect()
+    }
+
+    /// Get the widgets of all selected nodes along with their position and size as `(x, y, width, height)`.
+    fn selected_node_geometries(&self) -> Vec<(gtk::Widget, (f32, f32, f32, f32))> {
+        self.selected_nodes()
+            .into_iter()
+            .map(|node| node.upcast::<gtk::Widget>())
+            .filter_map(|node| {
+                let (x, y) = self.get_node_position(&node)?;
+                let (width, height) = (node.width() as f32, node.height() as f32);
+                Some((node, (x, y, width, height)))
+            })
+            .collect()
+    }
+
+    /// Set whether dragged nodes are placed on the background grid.
+    pub fn set_snap_to_grid(&self, snap_to_grid: bool) {
+        let private = imp::GraphView::from_instance(self);
+        private.snap_to_grid.set(snap_to_grid);
+    }
+
+    /// Move the position to the closest intersection of the background grid, if snapping to the grid is enabled.
+    fn snap_position(&self, x: f32, y: f32) -> (f32, f32) {
+        let private = imp::GraphView::from_instance(self);
+        if !private.snap_to_grid.get() {
+            return (x, y);
+        }
+
+        // The grid is drawn with the spacing rounded to whole pixels.
+        let spacing = (GRID_SPACING * self.em()).round() as f32;
+        (
+            (x / spacing).round() * spacing,
+            (y / spacing).round() * spacing,
+        )
+    }
+
+    /// Align the edges of all selected nodes with the outermost edge among them.
+    pub fn align_selected_nodes(&self, alignment: NodeAlignment) {
+        let nodes = self.selected_node_geometries();
+
+        match alignment {
+            NodeAlignment::Left => {
+                let left = nodes
+                    .iter()
+                    .map(|(_, (x, ..))| *x)
+                    .fold(f32::INFINITY, f32::min);
+                for (node, (_, y, _, _)) in &nodes {
+                    self.move_node(node, left, *y);
+                }
+            }
+            NodeAlignment::Right => {
+                let right = nodes
+                    .iter()
+                    .map(|(_, (x, _, width, _))| x + width)
+                    .fold(f32::NEG_INFINITY, f32::max);
+                for (node, (_, y, width, _)) in &nodes {
+                    self.move_node(node, right - width, *y);
+                }
+            }
+            NodeAlignment::Top => {
+                let top = nodes
+                    .iter()
+                    .map(|(_, (_, y, ..))| *y)
+                    .fold(f32::INFINITY, f32::min);
+                for (node, (x, ..)) in &nodes {
+                    self.move_node(node, *x, top);
+                }
+            }
+        }
+    }
+
+    /// Move the selected nodes vertically so that the gaps between them are equal,
+    /// keeping the topmost and bottommost node in place.
+    pub fn distribute_selected_nodes_vertically(&self) {
+        let mut nodes = self.selected_node_geometries();
+        if nodes.len() < 3 {
+            return;
+        }
+        nodes.sort_by(|(_, (_, a, ..)), (_, (_, b, ..))| {
+            a.partial_cmp(b).unwrap_or(Ordering::Equal)
+        });
+
+        let top = nodes.first().map_or(0.0, |(_, (_, y, ..))| *y);
+        let bottom = nodes
+            .iter()
+            .map(|(_, (_, y, _, height))| y + height)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let total_height: f32 = nodes.iter().map(|(_, (.., height))| height).sum();
+        let gap = (bottom - top - total_height) / (nodes.len() - 1) as f32;
+
+        let mut y = top;
+        for (node, (x, _, _, height)) in &nodes {
+            self.move_node(node, *x, y);
+            y += height + gap;
+        }
+    }
+
+    /// Stack the selected nodes in a column below the topmost of them, keeping their order and not letting them overlap.
+    pub fn tidy_selected_nodes(&self) {
+        let mut nodes = self.selected_node_geometries();
+        nodes.sort_by(|(_, (_, a, ..)), (_, (_, b, ..))| {
+            a.partial_cmp(b).unwrap_or(Ordering::Equal)
+        });
+
+        let left = nodes
+            .iter()
+            .map(|(_, (x, ..))| *x)
+            .fold(f32::INFINITY, f32::min);
+        let gap = (GRID_SPACING * self.em()) as f32;
+        let mut y = nodes.first().map_or(0.0, |(_, (_, y, ..))| *y);
+        for (node, (_, _, _, height)) in &nodes {
+            let (x, top) = self.snap_position(left, y);
+            self.move_node(node, x, top);
+            y = top + height + gap;
+        }
+    }
+
+    /// Get the size of the font of the graphview in pixels, which sizes in em are relative to.
+    fn em(&self) -> f64 {
+        let font = match self.pango_context().font_description() {
+            Some(font) if font.size() > 0 => font,
+            _ => return DEFAULT_FONT_SIZE,
+        };
+
+        let size = f64::from(font.size()) / f64::from(pango::SCALE);
+        if font.is_size_absolute() {
+            size
+        } else {
+            // The size is in points, so convert it using the resolution of the screen.
+            let dpi = gtk::Settings::default()
+                .map(|settings| settings.gtk_xft_dpi())
+                .filter(|dpi| *dpi > 0)
+                .map_or(96.0, |dpi| f64::from(dpi) / 1024.0);
+            size * dpi / 72.0
+        }
+    }
+
+    /// Show the custom label and color of all nodes with the specified label key.
+    pub fn set_custom_node_label(&self, label_key: &str, custom_label: Option<&NodeLabel>) {
+        for node in self.nodes() {
+            if node.label_key().as_deref() == Some(label_key) {
+                node.set_custom_label(custom_label);
+            }
+        }
+    }
+
+    /// Get all nodes currently shown.
+    pub(super) fn nodes(&self) -> Vec<Node> {
+        let private = imp::GraphView::from_instance(self);
+        private.nodes.borrow().values().cloned().collect()
+    }
+
+    /// Get all links currently shown, along with the coordinates of their start and end points.
+    ///
+    /// Links whose ports do not exist as widgets are skipped.
+    pub(super) fn links_with_coordinates(&self) -> Vec<(Link, (f64, f64, f64, f64))> {
+        let private = imp::GraphView::from_instance(self);
+        private
+            .links
+            .borrow()
+            .values()
+            .filter_map(|link| {
+                private
+                    .get_link_coordinates(&link.link)
+                    .map(|coordinates| (link.clone(), coordinates))
+            })
+            .collect()
+    }
+
+    /// Move the node with the specified id to the specified position inside the graphview.
+    ///
+    /// Positions passed to and returned from the graphview are those at a zoom of 1, so that they can be stored.
+    pub fn set_node_position(&self, id: u32, x: f32, y: f32) {
+        let private = imp::GraphView::from_instance(self);
+        let node = private.nodes.borrow().get(&id).cloned();
+        if let Some(node) = node {
+            let zoom = private.zoom.get() as f32;
+            self.move_node(&node.upcast(), x * zoom, y * zoom);
+        }
+    }
+
+    /// Get the position of the node with the specified id inside the graphview.
+    pub fn node_position(&self, id: u32) -> Option<(f32, f32)> {
+        let private = imp::GraphView::from_instance(self);
+        let node = private.nodes.borrow().get(&id)?.clone();
+        let zoom = private.zoom.get() as f32;
+        self.get_node_position(&node.upcast())
+            .map(|(x, y)| (x / zoom, y / zoom))
     }
 }
 
@@ -423,3 +1552,229 @@ impl Default for GraphView {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        application,
+        dump::GraphDump,
+        model::{self, Graph, GraphEvent},
+        view::{
+            testing::{self, with_gtk},
+            PortNames,
+        },
+        MediaType, PipewireLink,
+    };
+
+    /// Add a node with an audio port for each of the port ids to the graphview.
+    fn add_node(graphview: &GraphView, id: u32, node_type: NodeType, port_ids: &[u32]) {
+        graphview.add_node(id, Node::new(&id.to_string(), None, None, Some(node_type)));
+        let direction = match node_type {
+            NodeType::Output => Direction::Output,
+            NodeType::Input => Direction::Input,
+        };
+        for port_id in port_ids {
+            let names = PortNames {
+                name: port_id.to_string(),
+                alias: None,
+                full_name: format!("{}:{}", id, port_id),
+                channel: None,
+                path: None,
+            };
+            let port = Port::new(
+                *port_id,
+                names,
+                direction,
+                Some(MediaType::Audio),
+                (u32::MAX, u32::MAX, *port_id),
+            );
+            graphview.add_port(id, *port_id, port);
+        }
+    }
+
+    fn add_link(
+        graphview: &GraphView,
+        id: u32,
+        (node_from, port_from): (u32, u32),
+        (node_to, port_to): (u32, u32),
+    ) {
+        let link = PipewireLink {
+            node_from,
+            port_from,
+            node_to,
+            port_to,
+        };
+        graphview.add_link(id, link, LinkState::Active);
+    }
+
+    #[test]
+    fn removing_nodes_and_ports_removes_their_links() {
+        with_gtk(|| {
+            let graphview = GraphView::new();
+            add_node(&graphview, 1, NodeType::Output, &[10, 11]);
+            add_node(&graphview, 2, NodeType::Input, &[20, 21]);
+            add_node(&graphview, 3, NodeType::Input, &[30]);
+            add_link(&graphview, 100, (1, 10), (2, 20));
+            add_link(&graphview, 101, (1, 11), (2, 21));
+            add_link(&graphview, 102, (1, 11), (3, 30));
+
+            graphview.remove_port(21, 2);
+            assert_eq!(
+                testing::links(&graphview),
+                [(10, 20, LinkState::Active), (11, 30, LinkState::Active)]
+            );
+
+            graphview.remove_node(1);
+            assert!(testing::links(&graphview).is_empty());
+            let private = imp::GraphView::from_instance(&graphview);
+            assert!(private.links.borrow().is_empty());
+            assert!(private.link_render_nodes.borrow().is_empty());
+        });
+    }
+
+    /// Number of nodes of each direction in the benchmark graph.
+    const NODES_PER_DIRECTION: u32 = 250;
+    const ITERATIONS: u32 = 20;
+
+    /// Build a graph of 500 stereo nodes, where each output node is linked to two input nodes.
+    ///
+    /// Output node `i` has the id `1000 + i` and the ports `3000 + 2 * i` and `3001 + 2 * i`.
+    fn benchmark_events() -> Vec<GraphEvent> {
+        let mut events = Vec::new();
+        for (first_node, node_type, direction) in [
+            (1000, NodeType::Output, Direction::Output),
+            (2000, NodeType::Input, Direction::Input),
+        ] {
+            for i in 0..NODES_PER_DIRECTION {
+                let node_id = first_node + i;
+                events.push(GraphEvent::NodeAdded(model::Node {
+                    id: node_id,
+                    name: format!("Node {}", node_id),
+                    node_type: Some(node_type),
+                    media_type: Some(MediaType::Audio),
+                    device_id: None,
+                    properties: HashMap::new(),
+                    permissions: model::Permissions::default(),
+                }));
+                for (n, channel) in ["FL", "FR"].iter().enumerate() {
+                    events.push(GraphEvent::PortAdded(model::Port {
+                        id: first_node * 3 + 2 * i + n as u32,
+                        node_id,
+                        name: channel.to_string(),
+                        direction,
+                        media_type: Some(MediaType::Audio),
+                        properties: [("audio.channel".to_string(), channel.to_string())]
+                            .iter()
+                            .cloned()
+                            .collect(),
+                    }));
+                }
+            }
+        }
+
+        let mut link_id = 10000;
+        for i in 0..NODES_PER_DIRECTION {
+            for target in [i, (i + 1) % NODES_PER_DIRECTION] {
+                for n in 0..2 {
+                    events.push(GraphEvent::LinkAdded(model::Link {
+                        id: link_id,
+                        node_from: 1000 + i,
+                        port_from: 3000 + 2 * i + n,
+                        node_to: 2000 + target,
+                        port_to: 6000 + 2 * target + n,
+                        state: model::LinkState::Active,
+                        format: None,
+                        properties: HashMap::new(),
+                    }));
+                    link_id += 1;
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Compare drawing the links after moving one node with drawing all links from scratch.
+    ///
+    /// The timings depend too much on the machine to be checked, so they are only reported on stderr.
+    /// Run with `cargo test --release -- --ignored --nocapture link_cache` to see them.
+    #[test]
+    #[ignore]
+    fn link_cache_benchmark() {
+        with_gtk(|| {
+            // The graph is shown by an application, which simulates it from a dump.
+            let mut graph = Graph::new();
+            for event in benchmark_events() {
+                graph.apply(&event);
+            }
+            let json = GraphDump::from_graph(&graph, |_| None)
+                .to_json()
+                .expect("Failed to dump benchmark graph");
+            let path = testing::temp_file(Some(&json));
+            let app = application::tests::open_fake_graph(&path);
+            let _ = std::fs::remove_file(&path);
+
+            let graphview = application::tests::current_graphview(&app);
+            let link_count = testing::links(&graphview).len();
+            assert_eq!(link_count, 4 * NODES_PER_DIRECTION as usize);
+
+            let private = imp::GraphView::from_instance(&graphview);
+            let allocate = || {
+                graphview.size_allocate(
+                    &gtk::Allocation {
+                        x: 0,
+                        y: 0,
+                        width: 2000,
+                        height: 100 * NODES_PER_DIRECTION as i32,
+                    },
+                    -1,
+                )
+            };
+            let draw = || {
+                let snapshot = gtk::Snapshot::new();
+                private.snapshot(&graphview, &snapshot);
+                snapshot.to_node();
+            };
+            let timed_draw = || {
+                let start = Instant::now();
+                draw();
+                start.elapsed()
+            };
+
+            allocate();
+            draw();
+            assert_eq!(private.link_render_nodes.borrow().len(), link_count);
+
+            let mut rebuild = Duration::ZERO;
+            for _ in 0..ITERATIONS {
+                private.link_render_nodes.borrow_mut().clear();
+                rebuild += timed_draw();
+            }
+
+            let mut moved = Duration::ZERO;
+            for iteration in 0..ITERATIONS {
+                graphview.set_node_position(1000, 40.0, 40.0 + iteration as f32);
+                allocate();
+
+                // Only the four links of the moved node are drawn again.
+                private.invalidate_links_of_moved_nodes();
+                assert_eq!(private.link_render_nodes.borrow().len(), link_count - 4);
+
+                moved += timed_draw();
+                assert_eq!(private.link_render_nodes.borrow().len(), link_count);
+            }
+
+            eprintln!(
+                "{} links: full rebuild {:?}, after moving one node {:?} (average of {} draws)",
+                link_count,
+                rebuild / ITERATIONS,
+                moved / ITERATIONS,
+                ITERATIONS
+            );
+        });
+    }
+}
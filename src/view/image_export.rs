@@ -0,0 +1,309 @@
+// image_export.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Rendering of the whole graph into SVG, PDF or PNG files.
+//!
+//! Nodes and ports are drawn as boxes at the position and size of their widgets,
+//! links are drawn with the same curves as in the graph view.
+
+use std::{error::Error, fs::File, path::Path};
+
+use gtk::{cairo, prelude::*};
+use pipewire::spa::Direction;
+
+use super::{graph_view::draw_link_curve, GraphView};
+use crate::{model::LinkState, MediaType};
+
+/// Margin between the outermost nodes and the border of the image.
+const MARGIN: f64 = 20.0;
+/// Distance between two lines of the background grid.
+const GRID_SPACING: f64 = 20.0;
+/// Color of links in the error state, for both palettes.
+const ERROR_LINK_COLOR: Color = (0.88, 0.11, 0.14);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Svg,
+    Pdf,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "svg" => Some(Self::Svg),
+            "pdf" => Some(Self::Pdf),
+            "png" => Some(Self::Png),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Svg => "svg",
+            Self::Pdf => "pdf",
+            Self::Png => "png",
+        }
+    }
+}
+
+type Color = (f64, f64, f64);
+
+/// Colors of everything drawn that is not colored by the port colors of the theme.
+struct Palette {
+    background: Color,
+    grid: Color,
+    node_background: Color,
+    node_border: Color,
+    text: Color,
+    link: Color,
+}
+
+const DARK_PALETTE: Palette = Palette {
+    background: (0.14, 0.14, 0.14),
+    grid: (0.18, 0.18, 0.18),
+    node_background: (0.21, 0.21, 0.21),
+    node_border: (0.1, 0.1, 0.1),
+    text: (1.0, 1.0, 1.0),
+    link: (0.5, 0.5, 0.5),
+};
+
+/// Light colors that are better suited for printing.
+const LIGHT_PALETTE: Palette = Palette {
+    background: (1.0, 1.0, 1.0),
+    grid: (0.9, 0.9, 0.9),
+    node_background: (0.96, 0.96, 0.96),
+    node_border: (0.6, 0.6, 0.6),
+    text: (0.0, 0.0, 0.0),
+    link: (0.3, 0.3, 0.3),
+};
+
+/// Render the whole graph into an image file, regardless of which part of it is currently visible.
+pub fn export_image(
+    graphview: &GraphView,
+    path: &Path,
+    format: ImageFormat,
+    transparent: bool,
+    light_theme: bool,
+) -> Result<(), Box<dyn Error>> {
+    let palette = if light_theme {
+        &LIGHT_PALETTE
+    } else {
+        &DARK_PALETTE
+    };
+
+    // Find the area covered by nodes.
+    let bounds = graphview
+        .nodes()
+        .iter()
+        .map(|node| node.allocation())
+        .map(|alloc| {
+            (
+                f64::from(alloc.x),
+                f64::from(alloc.y),
+                f64::from(alloc.x + alloc.width),
+                f64::from(alloc.y + alloc.height),
+            )
+        })
+        .reduce(|(ax1, ay1, ax2, ay2), (bx1, by1, bx2, by2)| {
+            (ax1.min(bx1), ay1.min(by1), ax2.max(bx2), ay2.max(by2))
+        })
+        .unwrap_or((0.0, 0.0, 0.0, 0.0));
+    let width = bounds.2 - bounds.0 + 2.0 * MARGIN;
+    let height = bounds.3 - bounds.1 + 2.0 * MARGIN;
+
+    let draw = |cr: &cairo::Context| {
+        cr.translate(MARGIN - bounds.0, MARGIN - bounds.1);
+        draw_graph(
+            graphview,
+            cr,
+            palette,
+            transparent,
+            (bounds.0 - MARGIN, bounds.1 - MARGIN, width, height),
+        )
+    };
+
+    match format {
+        ImageFormat::Svg => {
+            let surface = cairo::SvgSurface::new(width, height, Some(path))?;
+            draw(&cairo::Context::new(&surface)?)?;
+            surface.finish();
+        }
+        ImageFormat::Pdf => {
+            let surface = cairo::PdfSurface::new(width, height, path)?;
+            draw(&cairo::Context::new(&surface)?)?;
+            surface.finish();
+        }
+        ImageFormat::Png => {
+            let surface = cairo::ImageSurface::create(
+                cairo::Format::ARgb32,
+                width.ceil() as i32,
+                height.ceil() as i32,
+            )?;
+            draw(&cairo::Context::new(&surface)?)?;
+            let mut file = File::create(path)?;
+            surface.write_to_png(&mut file)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Look up a color defined in the stylesheet, or use the fallback if it is not defined.
+fn lookup_color(widget: &impl IsA<gtk::Widget>, name: &str, fallback: Color) -> Color {
+    widget
+        .style_context()
+        .lookup_color(name)
+        .map_or(fallback, |color| {
+            (color.red.into(), color.green.into(), color.blue.into())
+        })
+}
+
+fn set_color(cr: &cairo::Context, (red, green, blue): Color) {
+    cr.set_source_rgb(red, green, blue);
+}
+
+/// Draw the graph, where `area` is the `(x, y, width, height)` of the image in graph coordinates.
+fn draw_graph(
+    graphview: &GraphView,
+    cr: &cairo::Context,
+    palette: &Palette,
+    transparent: bool,
+    area: (f64, f64, f64, f64),
+) -> Result<(), cairo::Error> {
+    let (area_x, area_y, area_width, area_height) = area;
+
+    if !transparent {
+        set_color(cr, palette.background);
+        cr.rectangle(area_x, area_y, area_width, area_height);
+        cr.fill()?;
+
+        // Align the grid with the one shown in the graph view.
+        set_color(cr, palette.grid);
+        cr.set_line_width(0.2);
+        let mut y = (area_y / GRID_SPACING).floor() * GRID_SPACING;
+        while y < area_y + area_height {
+            cr.move_to(area_x, y);
+            cr.line_to(area_x + area_width, y);
+            y += GRID_SPACING;
+        }
+        let mut x = (area_x / GRID_SPACING).floor() * GRID_SPACING;
+        while x < area_x + area_width {
+            cr.move_to(x, area_y);
+            cr.line_to(x, area_y + area_height);
+            x += GRID_SPACING;
+        }
+        cr.stroke()?;
+    }
+
+    cr.select_font_face("Sans", cairo::FontSlant::Normal, cairo::FontWeight::Normal);
+    cr.set_font_size(13.0);
+
+    for node in graphview.nodes() {
+        let node_alloc = node.allocation();
+        let (node_x, node_y) = (f64::from(node_alloc.x), f64::from(node_alloc.y));
+
+        cr.rectangle(
+            node_x,
+            node_y,
+            node_alloc.width.into(),
+            node_alloc.height.into(),
+        );
+        set_color(cr, palette.node_background);
+        cr.fill_preserve()?;
+        set_color(cr, palette.node_border);
+        cr.set_line_width(1.0);
+        cr.stroke()?;
+
+        // The node label is in the row above the first port.
+        let ports = node.ports();
+        let label_height = ports
+            .iter()
+            .map(|port| port.allocation().y)
+            .min()
+            .unwrap_or(node_alloc.height);
+        set_color(cr, palette.text);
+        cr.move_to(node_x + 6.0, node_y + f64::from(label_height) / 2.0 + 4.5);
+        cr.show_text(&node.name())?;
+
+        for port in ports {
+            let port_alloc = port.allocation();
+            let (port_x, port_y) = (
+                node_x + f64::from(port_alloc.x),
+                node_y + f64::from(port_alloc.y),
+            );
+
+            let color = match port.media_type() {
+                Some(MediaType::Audio) => lookup_color(graphview, "audio", (0.2, 0.39, 0.94)),
+                Some(MediaType::Video) => lookup_color(graphview, "video", (0.78, 0.78, 0.0)),
+                Some(MediaType::Midi) => lookup_color(graphview, "midi", (0.78, 0.0, 0.2)),
+                None => palette.node_background,
+            };
+            cr.rectangle(
+                port_x + 1.0,
+                port_y + 1.0,
+                f64::from(port_alloc.width) - 2.0,
+                f64::from(port_alloc.height) - 2.0,
+            );
+            set_color(cr, color);
+            cr.fill_preserve()?;
+            set_color(cr, palette.node_border);
+            cr.stroke()?;
+
+            // Colored ports use black text, like in the stylesheet.
+            set_color(
+                cr,
+                if port.media_type().is_some() {
+                    (0.0, 0.0, 0.0)
+                } else {
+                    palette.text
+                },
+            );
+            let name = port.name();
+            let text_x = match port.direction() {
+                Direction::Input => port_x + 6.0,
+                Direction::Output => {
+                    port_x + f64::from(port_alloc.width) - 6.0 - cr.text_extents(&name)?.x_advance
+                }
+            };
+            cr.move_to(text_x, port_y + f64::from(port_alloc.height) / 2.0 + 4.5);
+            cr.show_text(&name)?;
+        }
+    }
+
+    for (link, (from_x, from_y, to_x, to_y)) in graphview.links_with_coordinates() {
+        // Links that failed are red, like in the graph view.
+        if let LinkState::Error(_) = link.state {
+            set_color(cr, ERROR_LINK_COLOR);
+        } else {
+            set_color(cr, palette.link);
+        }
+        // Passive links are thinner, like in the graph view.
+        cr.set_line_width(if link.passive { 1.0 } else { 2.0 });
+        if link.state.is_active() {
+            cr.set_dash(&[], 0.0);
+        } else {
+            cr.set_dash(&[10.0, 5.0], 0.0);
+        }
+        draw_link_curve(cr, from_x, from_y, to_x, to_y);
+        cr.stroke()?;
+    }
+
+    Ok(())
+}
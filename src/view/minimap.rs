@@ -0,0 +1,236 @@
+// minimap.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! A scaled-down overview of the whole graph, showing which part of it is currently visible.
+
+use gtk::{
+    glib::{self, clone},
+    graphene,
+    prelude::*,
+    subclass::prelude::*,
+};
+use log::warn;
+
+use super::{graph_view::draw_link_curve, GraphView};
+
+/// Size of the minimap, in pixels.
+const WIDTH: i32 = 200;
+const HEIGHT: i32 = 150;
+
+mod imp {
+    use super::*;
+
+    use once_cell::unsync::OnceCell;
+
+    #[derive(Default)]
+    pub struct Minimap {
+        pub(super) graphview: OnceCell<GraphView>,
+        /// The scrolled window showing the graphview, which determines the visible part of the graph.
+        pub(super) scrolled_window: OnceCell<gtk::ScrolledWindow>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Minimap {
+        const NAME: &'static str = "Minimap";
+        type Type = super::Minimap;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.set_css_name("minimap");
+            klass.set_accessible_role(gtk::AccessibleRole::Img);
+        }
+    }
+
+    impl ObjectImpl for Minimap {
+        fn constructed(&self, obj: &Self::Type) {
+            self.parent_constructed(obj);
+
+            obj.set_size_request(WIDTH, HEIGHT);
+            obj.set_cursor(gtk::gdk::Cursor::from_name("move", None).as_ref());
+            obj.update_property(&[(gtk::AccessibleProperty::Label, &"Overview of the graph")]);
+
+            // Clicking or dragging centers the view on the pointer.
+            let drag_controller = gtk::GestureDrag::new();
+            drag_controller.connect_drag_begin(clone!(@weak obj => move |_, x, y| {
+                obj.center_view_at(x, y);
+            }));
+            drag_controller.connect_drag_update(clone!(@weak obj => move |drag_controller, x, y| {
+                if let Some((start_x, start_y)) = drag_controller.start_point() {
+                    obj.center_view_at(start_x + x, start_y + y);
+                }
+            }));
+            obj.add_controller(&drag_controller);
+        }
+    }
+
+    impl WidgetImpl for Minimap {
+        fn snapshot(&self, widget: &Self::Type, snapshot: &gtk::Snapshot) {
+            let (graphview, scrolled_window) =
+                match (self.graphview.get(), self.scrolled_window.get()) {
+                    (Some(graphview), Some(scrolled_window)) => (graphview, scrolled_window),
+                    _ => return,
+                };
+            let scale = match widget.graph_scale() {
+                Some(scale) => scale,
+                None => return,
+            };
+
+            let cr = snapshot
+                .append_cairo(&graphene::Rect::new(
+                    0.0,
+                    0.0,
+                    widget.width() as f32,
+                    widget.height() as f32,
+                ))
+                .expect("Failed to get cairo context");
+            cr.scale(scale, scale);
+
+            let style_context = widget.style_context();
+            let color = |name: &str| {
+                let rgba = style_context.lookup_color(name).unwrap_or(gtk::gdk::RGBA {
+                    red: 0.5,
+                    green: 0.5,
+                    blue: 0.5,
+                    alpha: 1.0,
+                });
+                cr.set_source_rgba(
+                    rgba.red.into(),
+                    rgba.green.into(),
+                    rgba.blue.into(),
+                    rgba.alpha.into(),
+                );
+            };
+
+            // Draw all nodes as filled rectangles.
+            color("minimap-node");
+            for node in graphview.nodes().iter().filter(|node| node.is_visible()) {
+                let alloc = node.allocation();
+                cr.rectangle(
+                    alloc.x.into(),
+                    alloc.y.into(),
+                    alloc.width.into(),
+                    alloc.height.into(),
+                );
+            }
+            if let Err(e) = cr.fill() {
+                warn!("Failed to draw minimap nodes: {}", e);
+            }
+
+            // Links keep their width on screen, no matter how far the graph is scaled down.
+            color("graphview-link");
+            cr.set_line_width(1.0 / scale);
+            for (_, (from_x, from_y, to_x, to_y)) in graphview.links_with_coordinates() {
+                draw_link_curve(&cr, from_x, from_y, to_x, to_y);
+            }
+            if let Err(e) = cr.stroke() {
+                warn!("Failed to draw minimap links: {}", e);
+            }
+
+            // Outline the part of the graph that is currently visible.
+            let hadjustment = scrolled_window.hadjustment();
+            let vadjustment = scrolled_window.vadjustment();
+            color("minimap-viewport");
+            cr.set_line_width(2.0 / scale);
+            cr.rectangle(
+                hadjustment.value(),
+                vadjustment.value(),
+                hadjustment.page_size(),
+                vadjustment.page_size(),
+            );
+            if let Err(e) = cr.stroke() {
+                warn!("Failed to draw minimap viewport: {}", e);
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct Minimap(ObjectSubclass<imp::Minimap>)
+        @extends gtk::Widget;
+}
+
+impl Minimap {
+    /// Create a minimap of the graphview, which must be the child of the scrolled window.
+    pub fn new(graphview: &GraphView, scrolled_window: &gtk::ScrolledWindow) -> Self {
+        let res: Self = glib::Object::new(&[]).expect("Failed to create Minimap");
+        let private = imp::Minimap::from_instance(&res);
+
+        if let Err(e) = graphview.connect_local(
+            "graph-changed",
+            false,
+            clone!(@weak res => @default-return None, move |_| {
+                res.queue_draw();
+                None
+            }),
+        ) {
+            warn!("Failed to connect to \"graph-changed\" signal: {}", e);
+        }
+
+        // The adjustments change when the view is scrolled or resized, or the graph grows or shrinks.
+        for adjustment in [scrolled_window.hadjustment(), scrolled_window.vadjustment()] {
+            adjustment.connect_value_changed(clone!(@weak res => move |_| res.queue_draw()));
+            adjustment.connect_changed(clone!(@weak res => move |_| res.queue_draw()));
+        }
+
+        private
+            .graphview
+            .set(graphview.clone())
+            .expect("Minimap graphview already set");
+        private
+            .scrolled_window
+            .set(scrolled_window.clone())
+            .expect("Minimap scrolled window already set");
+
+        res
+    }
+
+    /// Get the factor the graph is scaled by so that all of it fits into the minimap.
+    ///
+    /// Returns `None` if there is nothing to draw.
+    fn graph_scale(&self) -> Option<f64> {
+        let private = imp::Minimap::from_instance(self);
+        let graphview = private.graphview.get()?;
+
+        let graph_width = f64::from(graphview.width());
+        let graph_height = f64::from(graphview.height());
+        if graph_width <= 0.0 || graph_height <= 0.0 {
+            return None;
+        }
+
+        Some(f64::min(
+            f64::from(self.width()) / graph_width,
+            f64::from(self.height()) / graph_height,
+        ))
+    }
+
+    /// Scroll the graphview so that the point of the minimap at the specified coordinates is in the center of the view.
+    fn center_view_at(&self, x: f64, y: f64) {
+        let private = imp::Minimap::from_instance(self);
+        let (scrolled_window, scale) = match (private.scrolled_window.get(), self.graph_scale()) {
+            (Some(scrolled_window), Some(scale)) => (scrolled_window, scale),
+            _ => return,
+        };
+
+        // Adjustments clamp their value to the scrollable range themselves.
+        let hadjustment = scrolled_window.hadjustment();
+        hadjustment.set_value(x / scale - hadjustment.page_size() / 2.0);
+        let vadjustment = scrolled_window.vadjustment();
+        vadjustment.set_value(y / scale - vadjustment.page_size() / 2.0);
+    }
+}
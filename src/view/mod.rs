@@ -21,10 +21,22 @@
 //!
 //! This module contains gtk widgets needed to present the graphical user interface.
 
+mod command_palette;
+mod comparison_panel;
+mod dialog;
 mod graph_view;
+mod image_export;
+mod minimap;
 mod node;
 mod port;
+#[cfg(test)]
+pub mod testing;
 
-pub use graph_view::GraphView;
+pub use command_palette::show_command_palette;
+pub use comparison_panel::{ComparisonPanel, DifferenceKind};
+pub use dialog::{form_grid, mnemonic_label, new_dialog, set_dialog_content};
+pub use graph_view::{GraphView, LinkComparison, NodeAlignment, MAX_ZOOM, MIN_ZOOM};
+pub use image_export::{export_image, ImageFormat};
+pub use minimap::Minimap;
 pub use node::Node;
-pub use port::Port;
+pub use port::{Port, PortNameStyle, PortNames};
@@ -17,22 +17,42 @@
 //
 // SPDX-License-Identifier: GPL-3.0-only
 
-use gtk::{glib, prelude::*, subclass::prelude::*};
+use gtk::{
+    gdk, gio,
+    glib::{self, clone, subclass::Signal},
+    prelude::*,
+    subclass::prelude::*,
+};
 use pipewire::spa::Direction;
 
 use std::collections::HashMap;
 
+use crate::{node_labels::NodeLabel, NodeType};
+
 mod imp {
     use super::*;
 
+    use once_cell::{sync::Lazy, unsync::OnceCell};
     use std::cell::{Cell, RefCell};
 
     pub struct Node {
         pub(super) grid: gtk::Grid,
         pub(super) label: gtk::Label,
+        /// Name of the node as reported by pipewire, shown if there is no custom label.
+        pub(super) name: OnceCell<String>,
+        /// Key the custom label and color of the node are stored under.
+        pub(super) label_key: OnceCell<Option<String>>,
+        /// Provider for the custom color of the node, if it has one.
+        pub(super) color_provider: RefCell<Option<gtk::CssProvider>>,
+        pub(super) node_type: OnceCell<Option<NodeType>>,
         pub(super) ports: RefCell<HashMap<u32, crate::view::port::Port>>,
-        pub(super) num_ports_in: Cell<i32>,
-        pub(super) num_ports_out: Cell<i32>,
+        /// Actions of the context menu, available as `node.<action>` inside the node.
+        pub(super) actions: gio::SimpleActionGroup,
+        pub(super) menu: OnceCell<gtk::PopoverMenu>,
+        /// The last section of the context menu, which further entries are added to.
+        pub(super) menu_section: OnceCell<gio::Menu>,
+        /// Whether the ports are hidden, so that only the label is shown.
+        pub(super) collapsed: Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -43,6 +63,8 @@ mod imp {
 
         fn class_init(klass: &mut Self::Class) {
             klass.set_layout_manager_type::<gtk::BinLayout>();
+            klass.set_css_name("node");
+            klass.set_accessible_role(gtk::AccessibleRole::Group);
         }
 
         fn new() -> Self {
@@ -57,9 +79,15 @@ mod imp {
             Self {
                 grid,
                 label,
+                name: OnceCell::new(),
+                label_key: OnceCell::new(),
+                color_provider: RefCell::new(None),
+                node_type: OnceCell::new(),
                 ports: RefCell::new(HashMap::new()),
-                num_ports_in: Cell::new(0),
-                num_ports_out: Cell::new(0),
+                actions: gio::SimpleActionGroup::new(),
+                menu: OnceCell::new(),
+                menu_section: OnceCell::new(),
+                collapsed: Cell::new(false),
             }
         }
     }
@@ -68,14 +96,110 @@ mod imp {
         fn constructed(&self, obj: &Self::Type) {
             self.parent_constructed(obj);
             self.grid.set_parent(obj);
+
+            // Double clicking the label asks for a custom label and color of the node.
+            let gesture = gtk::GestureClick::new();
+            gesture.connect_pressed(clone!(@weak obj => move |gesture, n_press, _, _| {
+                if n_press == 2 {
+                    gesture.set_state(gtk::EventSequenceState::Claimed);
+                    obj.emit_by_name("edit-label", &[])
+                        .expect("Failed to emit \"edit-label\" signal");
+                }
+            }));
+            self.label.add_controller(&gesture);
+
+            // Actions that concern more than the node itself are handled by whoever shows the node.
+            for name in [
+                "disconnect",
+                "connect-default",
+                "hide",
+                "properties",
+                "suspend",
+                "destroy",
+            ] {
+                let action = gio::SimpleAction::new(name, None);
+                action.connect_activate(clone!(@weak obj => move |action, _| {
+                    obj.emit_by_name("menu-action", &[&action.name().to_string()])
+                        .expect("Failed to emit \"menu-action\" signal");
+                }));
+                self.actions.add_action(&action);
+            }
+            let rename = gio::SimpleAction::new("rename", None);
+            rename.connect_activate(clone!(@weak obj => move |_, _| {
+                obj.emit_by_name("edit-label", &[])
+                    .expect("Failed to emit \"edit-label\" signal");
+            }));
+            self.actions.add_action(&rename);
+            let collapse = gio::SimpleAction::new_stateful("collapse", None, &false.to_variant());
+            collapse.connect_change_state(clone!(@weak obj => move |_, value| {
+                if let Some(collapsed) = value.and_then(|value| value.get::<bool>()) {
+                    obj.set_collapsed(collapsed);
+                }
+            }));
+            self.actions.add_action(&collapse);
+            obj.insert_action_group("node", Some(&self.actions));
+
+            // Right clicking the node opens its context menu at the pointer,
+            // as does the menu key while the node is focused.
+            let menu_gesture = gtk::GestureClick::new();
+            menu_gesture.set_button(gdk::BUTTON_SECONDARY);
+            menu_gesture.connect_pressed(clone!(@weak obj => move |gesture, _, x, y| {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                obj.show_menu(x, y);
+            }));
+            obj.add_controller(&menu_gesture);
+
+            let key_controller = gtk::EventControllerKey::new();
+            key_controller.connect_key_pressed(clone!(@weak obj => @default-return gtk::Inhibit(false), move |_, key, _, modifiers| {
+                use gdk::keys::constants;
+
+                let open = match key {
+                    constants::Menu => true,
+                    constants::F10 => modifiers.contains(gdk::ModifierType::SHIFT_MASK),
+                    _ => false,
+                };
+                if open {
+                    obj.show_menu(f64::from(obj.width()) / 2.0, f64::from(obj.height()) / 2.0);
+                }
+                gtk::Inhibit(open)
+            }));
+            obj.add_controller(&key_controller);
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("edit-label", &[], <()>::static_type().into()).build(),
+                    // Emitted with the name of the activated context menu action.
+                    Signal::builder(
+                        "menu-action",
+                        &[String::static_type().into()],
+                        <()>::static_type().into(),
+                    )
+                    .build(),
+                ]
+            });
+
+            SIGNALS.as_ref()
         }
 
         fn dispose(&self, _obj: &Self::Type) {
             self.grid.unparent();
+            if let Some(menu) = self.menu.get() {
+                menu.unparent();
+            }
         }
     }
 
-    impl WidgetImpl for Node {}
+    impl WidgetImpl for Node {
+        fn size_allocate(&self, widget: &Self::Type, width: i32, height: i32, baseline: i32) {
+            self.parent_size_allocate(widget, width, height, baseline);
+            // Popovers need to be positioned again whenever their parent moves.
+            if let Some(menu) = self.menu.get() {
+                menu.present();
+            }
+        }
+    }
 }
 
 glib::wrapper! {
@@ -84,34 +208,226 @@ glib::wrapper! {
 }
 
 impl Node {
-    pub fn new(name: &str) -> Self {
+    /// Create a new node.
+    ///
+    /// `label_key` is the key the custom label and color of the node are stored under, if it can have them,
+    /// and `custom_label` are its current customizations.
+    pub fn new(
+        name: &str,
+        label_key: Option<String>,
+        custom_label: Option<&NodeLabel>,
+        node_type: Option<NodeType>,
+    ) -> Self {
         let res: Self = glib::Object::new(&[]).expect("Failed to create Node");
         let private = imp::Node::from_instance(&res);
 
-        private.label.set_text(name);
+        // Nodes take the keyboard focus before their ports when navigating with the keyboard.
+        res.set_focusable(true);
+        private
+            .name
+            .set(name.to_string())
+            .expect("Node name already set");
+        private
+            .label_key
+            .set(label_key)
+            .expect("Node label key already set");
+        private
+            .node_type
+            .set(node_type)
+            .expect("Node type already set");
+
+        res.set_custom_label(custom_label);
+        res.update_accessible_description();
+
+        let menu = gtk::PopoverMenu::from_model(Some(&res.menu_model()));
+        menu.set_parent(&res);
+        menu.set_has_arrow(false);
+        private.menu.set(menu).expect("Node menu already set");
 
         res
     }
 
-    pub fn add_port(&mut self, id: u32, port: super::port::Port) {
+    /// Build the context menu, leaving out entries that do not apply to the node.
+    fn menu_model(&self) -> gio::Menu {
+        let links_section = gio::Menu::new();
+        links_section.append(Some("_Disconnect All Links"), Some("node.disconnect"));
+        match self.node_type() {
+            Some(NodeType::Output) => links_section.append(
+                Some("Connect to Default _Sink"),
+                Some("node.connect-default"),
+            ),
+            Some(NodeType::Input) => links_section.append(
+                Some("Connect to Default _Source"),
+                Some("node.connect-default"),
+            ),
+            None => {}
+        }
+
+        let view_section = gio::Menu::new();
+        view_section.append(Some("_Hide"), Some("node.hide"));
+        view_section.append(Some("_Collapse"), Some("node.collapse"));
+        if self.label_key().is_some() {
+            view_section.append(Some("_Rename…"), Some("node.rename"));
+        }
+        view_section.append(Some("_Properties"), Some("node.properties"));
+
+        let node_section = gio::Menu::new();
+        node_section.append(Some("S_uspend"), Some("node.suspend"));
+        node_section.append(Some("D_estroy…"), Some("node.destroy"));
         let private = imp::Node::from_instance(self);
+        let _ = private.menu_section.set(node_section.clone());
 
-        match port.direction() {
-            Direction::Input => {
-                private
-                    .grid
-                    .attach(&port, 0, private.num_ports_in.get() + 1, 1, 1);
-                private.num_ports_in.set(private.num_ports_in.get() + 1);
-            }
-            Direction::Output => {
-                private
-                    .grid
-                    .attach(&port, 1, private.num_ports_out.get() + 1, 1, 1);
-                private.num_ports_out.set(private.num_ports_out.get() + 1);
-            }
+        let menu = gio::Menu::new();
+        menu.append_section(None, &links_section);
+        menu.append_section(None, &view_section);
+        menu.append_section(None, &node_section);
+        menu
+    }
+
+    /// Open the context menu, pointing at the specified coordinates inside the node.
+    fn show_menu(&self, x: f64, y: f64) {
+        let private = imp::Node::from_instance(self);
+        if let Some(menu) = private.menu.get() {
+            menu.set_pointing_to(&gdk::Rectangle {
+                x: x as i32,
+                y: y as i32,
+                width: 1,
+                height: 1,
+            });
+            menu.popup();
         }
+    }
+
+    /// Add an entry to the end of the context menu, which emits the `menu-action` signal with `name` when chosen.
+    pub fn add_menu_action(&self, label: &str, name: &str) {
+        let private = imp::Node::from_instance(self);
+
+        let action = gio::SimpleAction::new(name, None);
+        action.connect_activate(clone!(@weak self as obj => move |action, _| {
+            obj.emit_by_name("menu-action", &[&action.name().to_string()])
+                .expect("Failed to emit \"menu-action\" signal");
+        }));
+        private.actions.add_action(&action);
+        if let Some(section) = private.menu_section.get() {
+            section.append(Some(label), Some(&format!("node.{}", name)));
+        }
+    }
 
+    /// Enable or disable the context menu action with the specified name,
+    /// e.g. because the node may not be changed.
+    pub fn set_action_enabled(&self, name: &str, enabled: bool) {
+        let private = imp::Node::from_instance(self);
+        if let Some(action) = private
+            .actions
+            .lookup_action(name)
+            .and_then(|action| action.downcast::<gio::SimpleAction>().ok())
+        {
+            action.set_enabled(enabled);
+        }
+    }
+
+    /// Hide the ports of the node, so that only its label is shown, or show them again.
+    ///
+    /// Links from or to the ports of a collapsed node are attached to its sides instead.
+    pub fn set_collapsed(&self, collapsed: bool) {
+        let private = imp::Node::from_instance(self);
+        private.collapsed.set(collapsed);
+        for port in private.ports.borrow().values() {
+            port.set_visible(!collapsed);
+        }
+        if let Some(action) = private
+            .actions
+            .lookup_action("collapse")
+            .and_then(|action| action.downcast::<gio::SimpleAction>().ok())
+        {
+            action.set_state(&collapsed.to_variant());
+        }
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        let private = imp::Node::from_instance(self);
+        private.collapsed.get()
+    }
+
+    /// Get the key the custom label and color of the node are stored under, if it can have them.
+    pub fn label_key(&self) -> Option<String> {
+        let private = imp::Node::from_instance(self);
+        private.label_key.get().cloned().flatten()
+    }
+
+    /// Show the custom label and color of the node, or its name and default color if `custom_label` is `None`.
+    pub fn set_custom_label(&self, custom_label: Option<&NodeLabel>) {
+        let private = imp::Node::from_instance(self);
+
+        let name = private.name.get().map_or("", String::as_str);
+        let label = custom_label
+            .and_then(|custom| custom.label.as_deref())
+            .filter(|label| !label.is_empty())
+            .unwrap_or(name);
+        private.label.set_text(label);
+        self.update_property(&[(gtk::AccessibleProperty::Label, &label)]);
+
+        let style_context = self.style_context();
+        if let Some(provider) = private.color_provider.borrow_mut().take() {
+            style_context.remove_provider(&provider);
+        }
+        if let Some(color) = custom_label.and_then(|custom| custom.color.as_deref()) {
+            let provider = gtk::CssProvider::new();
+            provider.load_from_data(format!("node {{ background-color: {}; }}", color).as_bytes());
+            style_context.add_provider(&provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+            *private.color_provider.borrow_mut() = Some(provider);
+        }
+    }
+
+    /// Describe the type of the node and the number of its ports to assistive technologies.
+    fn update_accessible_description(&self) {
+        let private = imp::Node::from_instance(self);
+
+        let ports = private.ports.borrow();
+        let num_inputs = ports
+            .values()
+            .filter(|port| matches!(port.direction(), Direction::Input))
+            .count();
+        let num_outputs = ports.len() - num_inputs;
+        let kind = match self.node_type() {
+            Some(NodeType::Input) => "Sink",
+            Some(NodeType::Output) => "Source",
+            None => "Node",
+        };
+
+        self.update_property(&[(
+            gtk::AccessibleProperty::Description,
+            &format!(
+                "{} with {} input ports and {} output ports",
+                kind, num_inputs, num_outputs
+            ),
+        )]);
+    }
+
+    /// Set the tooltip shown when hovering over the name of the node.
+    pub fn set_label_tooltip(&self, text: &str) {
+        let private = imp::Node::from_instance(self);
+        private.label.set_tooltip_text(Some(text));
+    }
+
+    /// Get the name the node is labeled with, which is its custom label if it has one.
+    pub fn name(&self) -> String {
+        let private = imp::Node::from_instance(self);
+        private.label.text().to_string()
+    }
+
+    pub fn node_type(&self) -> Option<NodeType> {
+        let private = imp::Node::from_instance(self);
+        private.node_type.get().copied().flatten()
+    }
+
+    pub fn add_port(&mut self, id: u32, port: super::port::Port) {
+        let private = imp::Node::from_instance(self);
+
+        port.set_visible(!private.collapsed.get());
         private.ports.borrow_mut().insert(id, port);
+        self.pack_ports();
+        self.update_accessible_description();
     }
 
     pub fn get_port(&self, id: u32) -> Option<super::port::Port> {
@@ -119,15 +435,49 @@ impl Node {
         private.ports.borrow_mut().get(&id).cloned()
     }
 
+    pub fn ports(&self) -> Vec<super::port::Port> {
+        let private = imp::Node::from_instance(self);
+        private.ports.borrow().values().cloned().collect()
+    }
+
     pub fn remove_port(&self, id: u32) {
         let private = imp::Node::from_instance(self);
-        if let Some(port) = private.ports.borrow_mut().remove(&id) {
-            match port.direction() {
-                Direction::Input => private.num_ports_in.set(private.num_ports_in.get() - 1),
-                Direction::Output => private.num_ports_in.set(private.num_ports_out.get() - 1),
+        let removed = private.ports.borrow_mut().remove(&id);
+        if let Some(port) = removed {
+            private.grid.remove(&port);
+            // Close the gap left by the port.
+            self.pack_ports();
+            self.update_accessible_description();
+        }
+    }
+
+    /// Attach all ports to the grid in the order of their sort key,
+    /// with input ports in the left column and output ports in the right column.
+    fn pack_ports(&self) {
+        let private = imp::Node::from_instance(self);
+
+        let ports = private.ports.borrow();
+        let mut ports: Vec<&super::port::Port> = ports.values().collect();
+        ports.sort_by_key(|port| port.sort_key());
+
+        // The first row contains the label of the node.
+        let mut next_input_row = 1;
+        let mut next_output_row = 1;
+        for port in ports {
+            if port.parent().is_some() {
+                private.grid.remove(port);
             }
 
-            port.unparent();
+            match port.direction() {
+                Direction::Input => {
+                    private.grid.attach(port, 0, next_input_row, 1, 1);
+                    next_input_row += 1;
+                }
+                Direction::Output => {
+                    private.grid.attach(port, 1, next_output_row, 1, 1);
+                    next_output_row += 1;
+                }
+            }
         }
     }
 }
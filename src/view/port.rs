@@ -19,14 +19,106 @@
 
 use gtk::{
     gdk,
-    glib::{self, clone, subclass::Signal},
+    glib::{self, clone, subclass::Signal, translate::ToGlibContainerFromSlice},
     prelude::*,
     subclass::prelude::*,
 };
 use log::{trace, warn};
 use pipewire::spa::Direction;
 
-use crate::MediaType;
+use crate::{LinkOptions, MediaType};
+
+/// Get the options for a link created while the specified modifier keys are held.
+///
+/// Holding shift creates a passive link, holding control creates a link that does not linger.
+pub(super) fn link_options_for_modifiers(modifiers: gdk::ModifierType) -> LinkOptions {
+    LinkOptions {
+        passive: modifiers.contains(gdk::ModifierType::SHIFT_MASK),
+        linger: !modifiers.contains(gdk::ModifierType::CONTROL_MASK),
+    }
+}
+
+/// The name a port is labeled with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortNameStyle {
+    /// The `port.name` property.
+    Name,
+    /// The `port.alias` property, which is usually set for ALSA and JACK ports.
+    Alias,
+    /// The name of the node followed by the name of the port.
+    FullName,
+    /// The audio channel position, like `FL`.
+    Channel,
+}
+
+impl PortNameStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Self::Name),
+            "alias" => Some(Self::Alias),
+            "full" => Some(Self::FullName),
+            "channel" => Some(Self::Channel),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Alias => "alias",
+            Self::FullName => "full",
+            Self::Channel => "channel",
+        }
+    }
+}
+
+impl Default for PortNameStyle {
+    fn default() -> Self {
+        Self::Name
+    }
+}
+
+/// All names of a port.
+#[derive(Debug, Clone, Default)]
+pub struct PortNames {
+    pub name: String,
+    pub alias: Option<String>,
+    pub full_name: String,
+    pub channel: Option<String>,
+    pub path: Option<String>,
+}
+
+impl PortNames {
+    /// Get the name of the specified style, or the `port.name` if the port does not have such a name.
+    pub fn get(&self, style: PortNameStyle) -> &str {
+        match style {
+            PortNameStyle::Name => Some(&self.name),
+            PortNameStyle::Alias => self.alias.as_ref(),
+            PortNameStyle::FullName => Some(&self.full_name),
+            PortNameStyle::Channel => self.channel.as_ref(),
+        }
+        .unwrap_or(&self.name)
+    }
+
+    /// List all names of the port, one per line.
+    fn tooltip(&self) -> String {
+        let mut lines = vec![
+            format!("Name: {}", self.name),
+            format!("Full name: {}", self.full_name),
+        ];
+        if let Some(alias) = &self.alias {
+            lines.push(format!("Alias: {}", alias));
+        }
+        if let Some(channel) = &self.channel {
+            lines.push(format!("Channel: {}", channel));
+        }
+        if let Some(path) = &self.path {
+            lines.push(format!("Path: {}", path));
+        }
+
+        lines.join("\n")
+    }
+}
 
 /// A helper struct for linking a output port to an input port.
 /// It carries the output ports id.
@@ -51,7 +143,10 @@ mod imp {
     pub struct Port {
         pub(super) label: OnceCell<gtk::Label>,
         pub(super) id: OnceCell<u32>,
+        pub(super) names: OnceCell<PortNames>,
         pub(super) direction: OnceCell<Direction>,
+        pub(super) media_type: OnceCell<Option<MediaType>>,
+        pub(super) sort_key: OnceCell<(u32, u32, u32)>,
     }
 
     #[glib::object_subclass]
@@ -65,6 +160,9 @@ mod imp {
 
             // Make it look like a GTK button.
             klass.set_css_name("button");
+            klass.set_accessible_role(gtk::AccessibleRole::Button);
+            // Activating the port with the keyboard or through assistive technologies emits "activate".
+            klass.set_activate_signal_from_name("activate");
         }
     }
 
@@ -77,14 +175,26 @@ mod imp {
 
         fn signals() -> &'static [Signal] {
             static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
-                vec![Signal::builder(
-                    "port-toggled",
-                    // Provide id of output port and input port to signal handler.
-                    &[<u32>::static_type().into(), <u32>::static_type().into()],
-                    // signal handler sends back nothing.
-                    <()>::static_type().into(),
-                )
-                .build()]
+                vec![
+                    Signal::builder(
+                        "port-toggled",
+                        // Provide id of output port and input port to signal handler,
+                        // along with whether a new link should be passive and lingering.
+                        &[
+                            <u32>::static_type().into(),
+                            <u32>::static_type().into(),
+                            <bool>::static_type().into(),
+                            <bool>::static_type().into(),
+                        ],
+                        // signal handler sends back nothing.
+                        <()>::static_type().into(),
+                    )
+                    .build(),
+                    // Emitted when the port is activated, which starts or completes a link from it.
+                    Signal::builder("activate", &[], <()>::static_type().into())
+                        .action()
+                        .build(),
+                ]
             });
 
             SIGNALS.as_ref()
@@ -99,7 +209,16 @@ glib::wrapper! {
 }
 
 impl Port {
-    pub fn new(id: u32, name: &str, direction: Direction, media_type: Option<MediaType>) -> Self {
+    /// Create a new port.
+    ///
+    /// The ports of a node are shown ordered by their `sort_key`.
+    pub fn new(
+        id: u32,
+        names: PortNames,
+        direction: Direction,
+        media_type: Option<MediaType>,
+        sort_key: (u32, u32, u32),
+    ) -> Self {
         // Create the widget and initialize needed fields
         let res: Self = glib::Object::new(&[]).expect("Failed to create Port");
 
@@ -109,9 +228,18 @@ impl Port {
             .direction
             .set(direction)
             .expect("Port direction already set");
+        private
+            .media_type
+            .set(media_type)
+            .expect("Port media type already set");
+        private
+            .sort_key
+            .set(sort_key)
+            .expect("Port sort key already set");
 
-        let label = gtk::Label::new(Some(name));
+        let label = gtk::Label::new(Some(&names.name));
         label.set_parent(&res);
+        res.set_tooltip_text(Some(&names.tooltip()));
         private
             .label
             .set(label)
@@ -152,12 +280,8 @@ impl Port {
                 drop_target.connect_drop(
                     clone!(@weak res as this => @default-panic, move |drop_target, val, _, _| {
                         if let Ok(ForwardLink(source_id)) = val.get::<ForwardLink>() {
-                            // Get the callback registered in the widget and call it
-                            drop_target
-                                .widget()
-                                .expect("Drop target has no widget")
-                                .emit_by_name("port-toggled", &[&source_id, &this.id()])
-                                .expect("Failed to send signal");
+                            let options = link_options_for_modifiers(drop_target.current_event_state());
+                            this.toggle_link(source_id, this.id(), options);
                         } else {
                             warn!("Invalid type dropped on ingoing port");
                         }
@@ -170,12 +294,8 @@ impl Port {
                 drop_target.connect_drop(
                     clone!(@weak res as this => @default-panic, move |drop_target, val, _, _| {
                         if let Ok(ReversedLink(target_id)) = val.get::<ReversedLink>() {
-                            // Get the callback registered in the widget and call it
-                            drop_target
-                                .widget()
-                                .expect("Drop target has no widget")
-                                .emit_by_name("port-toggled", &[&this.id(), &target_id])
-                                .expect("Failed to send signal");
+                            let options = link_options_for_modifiers(drop_target.current_event_state());
+                            this.toggle_link(this.id(), target_id, options);
                         } else {
                             warn!("Invalid type dropped on outgoing port");
                         }
@@ -187,6 +307,9 @@ impl Port {
         }
         res.add_controller(&drop_target);
 
+        // Allow navigating to the port with the keyboard, so that links can be created without a mouse.
+        res.set_focusable(true);
+
         // Display a grab cursor when the mouse is over the port so the user knows it can be dragged to another port.
         res.set_cursor(gtk::gdk::Cursor::from_name("grab", None).as_ref());
 
@@ -198,9 +321,75 @@ impl Port {
             None => {}
         }
 
+        res.update_property(&[(gtk::AccessibleProperty::Label, &names.name)]);
+        res.set_connections(&[], &[]);
+
+        private.names.set(names).expect("Port names already set");
+
         res
     }
 
+    /// Label the port with its name of the specified style.
+    pub fn set_name_style(&self, style: PortNameStyle) {
+        let private = imp::Port::from_instance(self);
+
+        let name = private
+            .names
+            .get()
+            .expect("Port names are not set")
+            .get(style);
+        if let Some(label) = private.label.get() {
+            label.set_text(name);
+        }
+        self.update_property(&[(gtk::AccessibleProperty::Label, &name)]);
+    }
+
+    /// Set the ports this port is linked to.
+    ///
+    /// Their names are part of the accessible description of the port, and assistive technologies
+    /// can navigate from the port to the `peers` through its flow-to relation.
+    pub fn set_connections(&self, connected: &[String], peers: &[Port]) {
+        let direction = match self.direction() {
+            Direction::Input => "input",
+            Direction::Output => "output",
+        };
+        let kind = match self.media_type() {
+            Some(MediaType::Audio) => format!("Audio {} port", direction),
+            Some(MediaType::Video) => format!("Video {} port", direction),
+            Some(MediaType::Midi) => format!("MIDI {} port", direction),
+            None => format!("Port, {}", direction),
+        };
+        let description = match connected.len() {
+            0 => format!("{}, not connected", kind),
+            1 => format!("{}, 1 connection: {}", kind, connected[0]),
+            n => format!("{}, {} connections: {}", kind, n, connected.join(", ")),
+        };
+
+        self.update_property(&[(gtk::AccessibleProperty::Description, &description)]);
+
+        if peers.is_empty() {
+            self.reset_relation(gtk::AccessibleRelation::FlowTo);
+        } else {
+            // Reference list relations are passed as a GList of accessibles in a pointer value,
+            // which gtk-rs has no wrapper for. GTK takes its own references to the list items.
+            let peers: Vec<gtk::Accessible> =
+                peers.iter().map(|peer| peer.clone().upcast()).collect();
+            let (list, _storage) = <gtk::Accessible as ToGlibContainerFromSlice<
+                *mut glib::ffi::GList,
+            >>::to_glib_none_from_slice(&peers);
+            self.update_relation(&[(gtk::AccessibleRelation::FlowTo, &(list as glib::Pointer))]);
+        }
+    }
+
+    /// Request toggling the link between the two ports by emitting the "port-toggled" signal.
+    pub fn toggle_link(&self, port_from: u32, port_to: u32, options: LinkOptions) {
+        self.emit_by_name(
+            "port-toggled",
+            &[&port_from, &port_to, &options.passive, &options.linger],
+        )
+        .expect("Failed to send signal");
+    }
+
     pub fn id(&self) -> u32 {
         let private = imp::Port::from_instance(self);
         private.id.get().copied().expect("Port id is not set")
@@ -210,4 +399,27 @@ impl Port {
         let private = imp::Port::from_instance(self);
         private.direction.get().expect("Port direction is not set")
     }
+
+    pub fn name(&self) -> String {
+        let private = imp::Port::from_instance(self);
+        private
+            .label
+            .get()
+            .map(|label| label.text().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn media_type(&self) -> Option<MediaType> {
+        let private = imp::Port::from_instance(self);
+        private.media_type.get().copied().flatten()
+    }
+
+    pub fn sort_key(&self) -> (u32, u32, u32) {
+        let private = imp::Port::from_instance(self);
+        private
+            .sort_key
+            .get()
+            .copied()
+            .expect("Port sort key is not set")
+    }
 }
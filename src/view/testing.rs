@@ -0,0 +1,144 @@
+// testing.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Helpers for tests that need widgets.
+//!
+//! GTK may only be used from the thread it was initialized on, while tests run on many threads.
+//! [`with_gtk`] therefore runs the tests on a single thread dedicated to GTK.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc, Mutex,
+    },
+    thread,
+};
+
+use once_cell::sync::Lazy;
+
+use super::GraphView;
+use crate::model::{Graph, LinkState};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The sender for running jobs on the GTK thread, or `None` if GTK could not be initialized.
+static GTK_THREAD: Lazy<Option<Mutex<mpsc::Sender<Job>>>> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::channel::<Job>();
+    let (init_sender, init_receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        // Applications created by tests must not change the configuration of the user.
+        std::env::set_var("XDG_CONFIG_HOME", temp_file(None));
+        let initialized = gtk::init().is_ok();
+        let _ = init_sender.send(initialized);
+        if initialized {
+            for job in receiver {
+                job();
+            }
+        }
+    });
+
+    if init_receiver.recv().unwrap_or(false) {
+        Some(Mutex::new(sender))
+    } else {
+        None
+    }
+});
+
+/// Run `f` on the GTK thread and return its result, resuming its panic on the calling thread.
+///
+/// Returns `None` without running `f` if GTK could not be initialized, e.g. because there is no display.
+pub fn with_gtk<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let sender = match GTK_THREAD.as_ref() {
+        Some(sender) => sender,
+        None => {
+            eprintln!("Skipping test, as GTK could not be initialized");
+            return None;
+        }
+    };
+
+    let (result_sender, result_receiver) = mpsc::channel();
+    sender
+        .lock()
+        .expect("GTK thread sender is poisoned")
+        .send(Box::new(move || {
+            let _ = result_sender.send(panic::catch_unwind(AssertUnwindSafe(f)));
+        }))
+        .expect("GTK thread has stopped");
+
+    match result_receiver.recv().expect("GTK thread has stopped") {
+        Ok(result) => Some(result),
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// Write the contents to a new file in the temporary directory, returning its path.
+///
+/// Without `contents`, only the path of a file that does not exist yet is returned.
+pub fn temp_file(contents: Option<&str>) -> PathBuf {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "helvum-test-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    if let Some(contents) = contents {
+        std::fs::write(&path, contents).expect("Failed to write temporary file");
+    }
+    path
+}
+
+/// Get the ids of all ports shown in the graphview, in ascending order.
+pub fn port_ids(graphview: &GraphView) -> Vec<u32> {
+    let mut ids: Vec<u32> = graphview
+        .nodes()
+        .iter()
+        .flat_map(|node| node.ports())
+        .map(|port| port.id())
+        .collect();
+    ids.sort_unstable();
+    ids
+}
+
+/// Get the `(output port, input port, state)` of all links shown in the graphview, ordered by their ports.
+pub fn links(graphview: &GraphView) -> Vec<(u32, u32, LinkState)> {
+    let mut links: Vec<(u32, u32, LinkState)> = graphview
+        .links_with_coordinates()
+        .into_iter()
+        .map(|(link, _)| (link.link.port_from, link.link.port_to, link.state))
+        .collect();
+    links.sort_by_key(|(port_from, port_to, _)| (*port_from, *port_to));
+    links
+}
+
+/// Get the `(output port, input port, state)` of all links in the graph, ordered by their ports.
+pub fn graph_links(graph: &Graph) -> Vec<(u32, u32, LinkState)> {
+    let mut links: Vec<(u32, u32, LinkState)> = graph
+        .links()
+        .map(|link| (link.port_from, link.port_to, link.state.clone()))
+        .collect();
+    links.sort_by_key(|(port_from, port_to, _)| (*port_from, *port_to));
+    links
+}
@@ -0,0 +1,259 @@
+// virtual_nodes.rs
+//
+// Copyright 2021 Tom A. Wagner <tom.a.wagner@protonmail.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// SPDX-License-Identifier: GPL-3.0-only
+
+//! Virtual nodes created by helvum: null sinks and loopbacks.
+//!
+//! Null sinks are created on the server through the `adapter` factory, while loopbacks are run by the
+//! `libpipewire-module-loopback` module loaded into helvum itself, so they only exist while helvum is running.
+//! The created nodes carry the [`VIRTUAL_NODE_PROPERTY`] so that they can be found again later.
+//! Virtual nodes chosen to be kept are stored in a local file and created again when helvum connects to their remote.
+
+use std::{collections::HashMap, error::Error, path::PathBuf};
+
+use gtk::glib;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{Graph, Node};
+
+/// Property of the nodes of a virtual node, with the name of the virtual node as its value.
+pub const VIRTUAL_NODE_PROPERTY: &str = "helvum.virtual-node";
+
+/// Channel positions used for the common channel counts, see `enum spa_audio_channel` in `spa/param/audio/raw.h`.
+const DEFAULT_POSITIONS: &[&[&str]] = &[
+    &["MONO"],
+    &["FL", "FR"],
+    &["FL", "FR", "LFE"],
+    &["FL", "FR", "RL", "RR"],
+    &["FL", "FR", "FC", "RL", "RR"],
+    &["FL", "FR", "FC", "LFE", "RL", "RR"],
+    &["FL", "FR", "FC", "LFE", "RC", "SL", "SR"],
+    &["FL", "FR", "FC", "LFE", "RL", "RR", "SL", "SR"],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VirtualNodeKind {
+    /// A sink discarding all audio, whose monitor ports can be linked to other nodes.
+    NullSink,
+    /// A sink that plays all audio it receives through an output stream.
+    Loopback,
+}
+
+impl VirtualNodeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NullSink => "null-sink",
+            Self::Loopback => "loopback",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "null-sink" => Some(Self::NullSink),
+            "loopback" => Some(Self::Loopback),
+            _ => None,
+        }
+    }
+}
+
+/// The configuration of a virtual node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VirtualNode {
+    pub kind: VirtualNodeKind,
+    /// The `node.name` of the created sink, which also identifies the virtual node.
+    pub name: String,
+    /// Description shown instead of the name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Position of each channel, like `FL`, so that there are as many channels as positions.
+    pub positions: Vec<String>,
+    /// Whether the virtual node is created again whenever helvum connects to its remote.
+    #[serde(default)]
+    pub persistent: bool,
+}
+
+impl VirtualNode {
+    /// Get the usual positions of the channels for the specified number of channels.
+    ///
+    /// Channel counts without a usual layout use numbered auxiliary channels.
+    pub fn default_positions(channels: usize) -> Vec<String> {
+        match DEFAULT_POSITIONS.get(channels.wrapping_sub(1)) {
+            Some(positions) => positions
+                .iter()
+                .map(|position| position.to_string())
+                .collect(),
+            None => (0..channels).map(|n| format!("AUX{}", n)).collect(),
+        }
+    }
+
+    /// Parse channel positions separated by commas or whitespace.
+    pub fn parse_positions(positions: &str) -> Vec<String> {
+        positions
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|position| !position.is_empty())
+            .map(str::to_uppercase)
+            .collect()
+    }
+
+    /// Check that the virtual node can be created.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err("The virtual node needs a name".to_string());
+        }
+        if self.positions.is_empty() {
+            return Err("The virtual node needs at least one channel".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Get the description of the node, which is its name if it has none.
+    pub fn description(&self) -> &str {
+        self.description.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Get the channel positions as a JSON array, as expected by the `audio.position` property.
+    pub fn position_array(&self) -> String {
+        format!("[ {} ]", self.positions.join(" "))
+    }
+
+    /// Get the properties a null sink is created with through the `adapter` factory.
+    pub fn null_sink_properties(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("factory.name", "support.null-audio-sink".to_string()),
+            ("node.name", self.name.clone()),
+            ("node.description", self.description().to_string()),
+            ("media.class", "Audio/Sink".to_string()),
+            ("audio.channels", self.positions.len().to_string()),
+            ("audio.position", self.position_array()),
+            (VIRTUAL_NODE_PROPERTY, self.name.clone()),
+            // Null sinks that are kept outlive helvum, so they are not created twice.
+            ("object.linger", self.persistent.to_string()),
+        ]
+    }
+
+    /// Get the arguments `libpipewire-module-loopback` is loaded with, in SPA JSON.
+    pub fn loopback_arguments(&self) -> String {
+        let name = json_string(&self.name);
+        format!(
+            "{{ node.description = {} audio.channels = {} audio.position = {} \
+             capture.props = {{ node.name = {} media.class = Audio/Sink {} = {} }} \
+             playback.props = {{ node.name = {} node.passive = true {} = {} }} }}",
+            json_string(self.description()),
+            self.positions.len(),
+            self.position_array(),
+            name,
+            VIRTUAL_NODE_PROPERTY,
+            name,
+            json_string(&format!("{}.output", self.name)),
+            VIRTUAL_NODE_PROPERTY,
+            name,
+        )
+    }
+}
+
+/// Quote the string for use in SPA JSON.
+fn json_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// Get the nodes of the graph belonging to the virtual node with the specified name.
+pub fn nodes_of<'a>(graph: &'a Graph, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+    graph.nodes().filter(move |node| {
+        node.properties
+            .get(VIRTUAL_NODE_PROPERTY)
+            .map(String::as_str)
+            == Some(name)
+    })
+}
+
+/// The virtual nodes that are kept, keyed by the display name of their remote.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VirtualNodes {
+    remotes: HashMap<String, Vec<VirtualNode>>,
+}
+
+impl VirtualNodes {
+    /// Get the path of the file the virtual nodes are stored in.
+    fn path() -> PathBuf {
+        glib::user_config_dir()
+            .join("helvum")
+            .join("virtual-nodes.json")
+    }
+
+    /// Load the stored virtual nodes, or start without any if there are none or they can not be read.
+    pub fn load() -> Self {
+        let path = Self::path();
+        if !path.exists() {
+            return Self::default();
+        }
+
+        std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to load virtual nodes from {}: {}",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            })
+    }
+
+    /// Store the virtual nodes, so that they are created again the next time helvum is started.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Get the virtual nodes kept for the remote, in the order they were added in.
+    pub fn get(&self, remote: &str) -> &[VirtualNode] {
+        self.remotes.get(remote).map_or(&[], Vec::as_slice)
+    }
+
+    /// Keep the virtual node for the remote, replacing any virtual node with the same name.
+    pub fn add(&mut self, remote: &str, node: VirtualNode) {
+        let nodes = self.remotes.entry(remote.to_string()).or_default();
+        nodes.retain(|other| other.name != node.name);
+        nodes.push(node);
+    }
+
+    /// Stop keeping the virtual node with the specified name, returning whether it was kept.
+    pub fn remove(&mut self, remote: &str, name: &str) -> bool {
+        let nodes = match self.remotes.get_mut(remote) {
+            Some(nodes) => nodes,
+            None => return false,
+        };
+        let count = nodes.len();
+        nodes.retain(|node| node.name != name);
+        let removed = nodes.len() != count;
+        if nodes.is_empty() {
+            self.remotes.remove(remote);
+        }
+
+        removed
+    }
+}